@@ -0,0 +1,237 @@
+use std::fs;
+use std::sync::Arc;
+
+use colored::Colorize;
+use log::warn;
+use rust_decimal::Decimal;
+use tokio::sync::{Mutex, RwLock};
+
+use crate::config::BacktestConfig;
+use crate::order_book::{OrderBook, PriceLevel};
+use crate::order_manager::OrderManager;
+use crate::position::PositionTracker;
+use crate::rusty_bot_models::{CurrencyPair, MarkPriceBucket, OrderSide};
+use crate::simulation::{SimulatedAccount, SimulatedOrderExecutor, SimulatedTrade};
+use crate::strategies::{create_candle_engine, Strategy, StrategyContext};
+
+/// Depth given to the single simulated book level so that order size is only limited by balances
+const SIMULATED_BOOK_QUANTITY: i64 = 1_000_000;
+
+#[derive(Debug)]
+pub struct BacktestReport {
+    pub candles: usize,
+    pub trades: Vec<SimulatedTrade>,
+    pub fees_paid: Decimal,
+    pub base_balance: Decimal,
+    pub quote_balance: Decimal,
+    pub starting_equity: Decimal,
+    pub ending_equity: Decimal,
+}
+
+impl BacktestReport {
+    pub fn profit_and_loss(&self) -> Decimal {
+        self.ending_equity - self.starting_equity
+    }
+
+    pub fn print(&self, currency_pair: &CurrencyPair) {
+        println!();
+        println!("{}", "Backtest Trades".on_bright_blue());
+        for trade in &self.trades {
+            println!(
+                "{}| {} {} {} at {} fee: {}",
+                trade.traded_at.blue(),
+                trade.order_id,
+                match trade.side {
+                    OrderSide::Buy => trade.side.to_string().green(),
+                    OrderSide::Sell => trade.side.to_string().red(),
+                },
+                trade.quantity,
+                trade.price.to_string().yellow(),
+                trade.fee
+            );
+        }
+        println!();
+        println!("{}", "Backtest Summary".on_bright_blue());
+        println!("Candles:         {}", self.candles);
+        println!("Trades:          {}", self.trades.len());
+        println!(
+            "Fees paid:       {} {}",
+            self.fees_paid, currency_pair.quote_currency
+        );
+        println!(
+            "Ending balances: {} {}, {} {}",
+            self.base_balance,
+            currency_pair.base_currency,
+            self.quote_balance,
+            currency_pair.quote_currency
+        );
+        println!(
+            "Starting equity: {} {}",
+            self.starting_equity, currency_pair.quote_currency
+        );
+        println!(
+            "Ending equity:   {} {}",
+            self.ending_equity, currency_pair.quote_currency
+        );
+        let pnl = self.profit_and_loss();
+        let pnl_text = format!(
+            "{} {} ({:.2}%)",
+            pnl,
+            currency_pair.quote_currency,
+            pnl.checked_div(self.starting_equity)
+                .unwrap_or(Decimal::ZERO)
+                * Decimal::ONE_HUNDRED
+        );
+        println!(
+            "P&L:             {}",
+            if pnl >= Decimal::ZERO {
+                pnl_text.green()
+            } else {
+                pnl_text.red()
+            }
+        );
+    }
+}
+
+pub async fn load_bucket_prices(
+    backtest_config: &BacktestConfig,
+    market: &String,
+) -> Vec<MarkPriceBucket> {
+    match &backtest_config.file {
+        Some(file) => {
+            let contents = fs::read_to_string(file)
+                .unwrap_or_else(|e| panic!("Cannot read backtest file {}: {}", file, e));
+            serde_json::from_str::<Vec<MarkPriceBucket>>(&contents)
+                .unwrap_or_else(|e| panic!("Cannot parse backtest file {}: {}", file, e))
+        }
+        None => match (&backtest_config.start_time, &backtest_config.end_time) {
+            (Some(start_time), Some(end_time)) => crate::fetch_mark_price_buckets(
+                market,
+                start_time,
+                end_time,
+                &backtest_config.period_seconds,
+            )
+            .await
+            .expect("Error downloading mark price buckets for backtest"),
+            _ => panic!("Backtest mode needs BACKTEST_FILE or BACKTEST_START and BACKTEST_END"),
+        },
+    }
+}
+
+/// Replays the buckets through the strategy hooks in start time order, rolled up into the
+/// strategy's timeframes. Orders placed on a candle can only fill from the next candle onwards.
+pub async fn run_backtest(
+    strategy: &mut dyn Strategy,
+    currency_pair: CurrencyPair,
+    mut bucket_prices: Vec<MarkPriceBucket>,
+    starting_quote_balance: Decimal,
+    fee_rate: Decimal,
+) -> BacktestReport {
+    bucket_prices.sort_by(|a, b| a.start_time.cmp(&b.start_time));
+    let account = Arc::new(Mutex::new(SimulatedAccount::new(
+        currency_pair.clone(),
+        starting_quote_balance,
+        Decimal::ZERO,
+        fee_rate,
+    )));
+    let initial_balances = account.lock().await.balance_updates();
+    let candle_engine = create_candle_engine(strategy);
+    let timeframes = candle_engine.timeframes();
+    if let Some(bucket) = bucket_prices.first() {
+        for timeframe in &timeframes {
+            if !timeframe.can_roll_up(bucket.bucket_period_in_seconds as i64) {
+                warn!(
+                    "{} candles cannot be built from {}s buckets, {} gets none",
+                    timeframe,
+                    bucket.bucket_period_in_seconds,
+                    strategy.name()
+                );
+            }
+        }
+    }
+    let context = StrategyContext {
+        currency_pair,
+        order_book: Arc::new(RwLock::new(OrderBook::default())),
+        balances: Arc::new(RwLock::new(initial_balances)),
+        orders: Arc::new(RwLock::new(OrderManager::default())),
+        positions: Arc::new(RwLock::new(PositionTracker::default())),
+        candles: Arc::new(RwLock::new(candle_engine)),
+        executor: Arc::new(SimulatedOrderExecutor {
+            account: account.clone(),
+        }),
+    };
+
+    for candle in &bucket_prices {
+        let mut account_writer = account.lock().await;
+        let trades = account_writer.match_candle(candle);
+        let balance_updates = account_writer.balance_updates();
+        let orders = account_writer.orders();
+        drop(account_writer);
+        context
+            .orders
+            .write()
+            .await
+            .replace_open_orders(orders.clone());
+
+        if !trades.is_empty() {
+            let mut positions_writer = context.positions.write().await;
+            for trade in &trades {
+                positions_writer.apply_simulated_trade(&context.currency_pair.symbol, trade);
+            }
+            drop(positions_writer);
+            *context.balances.write().await = balance_updates.clone();
+            for balance_update in &balance_updates {
+                strategy.on_balance(balance_update, &context).await;
+            }
+            strategy.on_order_update(&orders, &context).await;
+            for trade in &trades {
+                strategy
+                    .on_account_trade(&trade.to_account_trade(&context.currency_pair), &context)
+                    .await;
+            }
+        }
+
+        let level = PriceLevel {
+            price: candle.close,
+            quantity: Decimal::from(SIMULATED_BOOK_QUANTITY),
+        };
+        *context.order_book.write().await = OrderBook {
+            asks: vec![level],
+            bids: vec![level],
+        };
+        strategy.on_book(&context).await;
+        let mut candles_writer = context.candles.write().await;
+        let updated: Vec<_> = candles_writer
+            .apply_bucket(candle)
+            .into_iter()
+            .map(|timeframe| (timeframe, candles_writer.candles(timeframe).to_vec()))
+            .collect();
+        let smc_events = candles_writer.take_smc_events();
+        drop(candles_writer);
+        for (timeframe, candles) in &updated {
+            strategy.on_candle(*timeframe, candles, &context).await;
+        }
+        for (timeframe, event) in smc_events {
+            if let Some((_, candles)) = updated.iter().find(|(updated, _)| *updated == timeframe) {
+                strategy
+                    .on_smc_event(timeframe, &event, candles, &context)
+                    .await;
+            }
+        }
+    }
+
+    let last_close = bucket_prices
+        .last()
+        .map(|b| b.close)
+        .unwrap_or(Decimal::ZERO);
+    let account_reader = account.lock().await;
+    BacktestReport {
+        candles: bucket_prices.len(),
+        trades: account_reader.trades.clone(),
+        fees_paid: account_reader.fees_paid,
+        base_balance: account_reader.base_balance,
+        quote_balance: account_reader.quote_balance,
+        starting_equity: starting_quote_balance,
+        ending_equity: account_reader.equity(last_close),
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use log::warn;
+use rust_decimal::Decimal;
+
+use crate::indicators::{AttachedIndicator, Indicator, IndicatorValue};
+use crate::rusty_bot_models::MarkPriceBucket;
+use crate::smc::{SmcDetector, SmcEvent};
+
+/// Candles kept per timeframe, the oldest are dropped first
+pub const MAX_CANDLES: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Timeframe {
+    OneMinute,
+    FiveMinutes,
+    FifteenMinutes,
+    OneHour,
+    FourHours,
+}
+
+impl Timeframe {
+    pub fn seconds(&self) -> i64 {
+        match self {
+            Timeframe::OneMinute => 60,
+            Timeframe::FiveMinutes => 300,
+            Timeframe::FifteenMinutes => 900,
+            Timeframe::OneHour => 3600,
+            Timeframe::FourHours => 14400,
+        }
+    }
+
+    /// VALR has no 4h buckets, 4h history is rolled up from 1h buckets
+    pub fn history_period_seconds(&self) -> i64 {
+        match self {
+            Timeframe::FourHours => Timeframe::OneHour.seconds(),
+            _ => self.seconds(),
+        }
+    }
+
+    /// Start of the candle the time falls in
+    pub fn candle_start(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let seconds = at.timestamp();
+        let start = seconds - seconds.rem_euclid(self.seconds());
+        Utc.timestamp_opt(start, 0).unwrap()
+    }
+
+    /// Whether candles of this timeframe can be built out of buckets of the period
+    pub fn can_roll_up(&self, period_seconds: i64) -> bool {
+        period_seconds > 0
+            && self.seconds() >= period_seconds
+            && self.seconds() % period_seconds == 0
+    }
+}
+
+impl FromStr for Timeframe {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "1m" => Ok(Timeframe::OneMinute),
+            "5m" => Ok(Timeframe::FiveMinutes),
+            "15m" => Ok(Timeframe::FifteenMinutes),
+            "1h" => Ok(Timeframe::OneHour),
+            "4h" => Ok(Timeframe::FourHours),
+            _ => Err(format!(
+                "Timeframe: {} not supported, use 1m, 5m, 15m, 1h or 4h",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for Timeframe {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Timeframe::OneMinute => "1m",
+            Timeframe::FiveMinutes => "5m",
+            Timeframe::FifteenMinutes => "15m",
+            Timeframe::OneHour => "1h",
+            Timeframe::FourHours => "4h",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Builds candles for the subscribed timeframes by rolling up shorter trade buckets, seeded
+/// with the trade buckets of the history. The last candle of each timeframe is still forming. Indicators attached
+/// to a timeframe are kept up to date with its candles, and its smart money concepts events
+/// are collected as its candles close.
+#[derive(Debug, Default, Clone)]
+pub struct CandleEngine {
+    series: BTreeMap<Timeframe, Vec<MarkPriceBucket>>,
+    indicators: BTreeMap<Timeframe, Vec<AttachedIndicator>>,
+    smc_detectors: BTreeMap<Timeframe, SmcDetector>,
+    /// Events found since they were last taken
+    smc_events: Vec<(Timeframe, SmcEvent)>,
+    /// Live bucket updates repeat the forming bucket, only its extra volume is added
+    last_bucket: Option<MarkPriceBucket>,
+}
+
+impl CandleEngine {
+    pub fn new(timeframes: &[Timeframe]) -> Self {
+        let mut engine = CandleEngine::default();
+        for timeframe in timeframes {
+            engine.subscribe(*timeframe);
+        }
+        engine
+    }
+
+    pub fn subscribe(&mut self, timeframe: Timeframe) {
+        self.series.entry(timeframe).or_default();
+    }
+
+    pub fn timeframes(&self) -> Vec<Timeframe> {
+        self.series.keys().copied().collect()
+    }
+
+    /// Oldest first, empty for a timeframe that is not subscribed
+    pub fn candles(&self, timeframe: Timeframe) -> &[MarkPriceBucket] {
+        self.series
+            .get(&timeframe)
+            .map(|candles| candles.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Subscribes to the timeframe and feeds the indicator its candles so far. Replaces an
+    /// indicator attached under the same name.
+    pub fn attach_indicator(&mut self, timeframe: Timeframe, name: &str, indicator: Indicator) {
+        self.subscribe(timeframe);
+        let mut attached = AttachedIndicator::new(name, indicator);
+        attached.sync(self.candles(timeframe));
+        let indicators = self.indicators.entry(timeframe).or_default();
+        indicators.retain(|i| i.name != name);
+        indicators.push(attached);
+    }
+
+    /// Subscribes to the timeframe and detects the events of the candles that close from now on
+    pub fn attach_smc_detector(&mut self, timeframe: Timeframe, swing_width: usize) {
+        self.subscribe(timeframe);
+        let mut detector = SmcDetector::new(swing_width);
+        detector.new_events(self.candles(timeframe));
+        self.smc_detectors.insert(timeframe, detector);
+    }
+
+    /// Events found since the last call, with indexes into their timeframe's candles as they
+    /// are now
+    pub fn take_smc_events(&mut self) -> Vec<(Timeframe, SmcEvent)> {
+        std::mem::take(&mut self.smc_events)
+    }
+
+    /// Value including the forming candle, None until the indicator has seen enough candles
+    pub fn indicator(&self, timeframe: Timeframe, name: &str) -> Option<IndicatorValue> {
+        self.indicators
+            .get(&timeframe)?
+            .iter()
+            .find(|i| i.name == name)?
+            .value()
+    }
+
+    /// Name and value as of the last closed candle of every indicator attached to the timeframe
+    pub fn closed_indicator_values(&self, timeframe: Timeframe) -> Vec<(&str, IndicatorValue)> {
+        self.indicators
+            .get(&timeframe)
+            .into_iter()
+            .flatten()
+            .filter_map(|i| Some((i.name.as_str(), i.closed_value()?)))
+            .collect()
+    }
+
+    /// Replaces the timeframe's candles with the history, in any order and rolled up when the
+    /// buckets are shorter than the timeframe
+    pub fn seed(&mut self, timeframe: Timeframe, mut buckets: Vec<MarkPriceBucket>) {
+        buckets.sort_by(|a, b| a.start_time.cmp(&b.start_time));
+        let candles = self.series.entry(timeframe).or_default();
+        candles.clear();
+        for bucket in &buckets {
+            if !timeframe.can_roll_up(bucket.bucket_period_in_seconds as i64) {
+                warn!(
+                    "Cannot build {} candles from {}s buckets",
+                    timeframe, bucket.bucket_period_in_seconds
+                );
+                break;
+            }
+            if let Some(start) = parse_time(&bucket.start_time) {
+                update_candle(
+                    candles,
+                    timeframe,
+                    &bucket.currency_pair_symbol,
+                    start,
+                    bucket,
+                );
+            }
+        }
+        if let Some(indicators) = self.indicators.get_mut(&timeframe) {
+            for indicator in indicators {
+                indicator.reset();
+            }
+        }
+        if let Some(detector) = self.smc_detectors.get_mut(&timeframe) {
+            detector.reset();
+        }
+        self.sync_indicators(timeframe);
+    }
+
+    /// Rolls a bucket into every subscribed timeframe it fits in and returns the timeframes
+    /// whose candles changed. Repeated updates of the same bucket are merged.
+    pub fn apply_bucket(&mut self, bucket: &MarkPriceBucket) -> Vec<Timeframe> {
+        let Some(start) = parse_time(&bucket.start_time) else {
+            warn!(
+                "Ignoring bucket with invalid start time {}",
+                bucket.start_time
+            );
+            return vec![];
+        };
+        let period_seconds = bucket.bucket_period_in_seconds as i64;
+        let repeated = self.last_bucket.replace(bucket.clone()).filter(|last| {
+            last.start_time == bucket.start_time
+                && last.bucket_period_in_seconds == bucket.bucket_period_in_seconds
+        });
+        let bucket = &MarkPriceBucket {
+            volume: bucket.volume - repeated.map(|last| last.volume).unwrap_or(Decimal::ZERO),
+            ..bucket.clone()
+        };
+        let mut updated = vec![];
+        for (timeframe, candles) in self.series.iter_mut() {
+            if timeframe.can_roll_up(period_seconds)
+                && update_candle(
+                    candles,
+                    *timeframe,
+                    &bucket.currency_pair_symbol,
+                    start,
+                    bucket,
+                )
+            {
+                updated.push(*timeframe);
+            }
+        }
+        for timeframe in &updated {
+            self.sync_indicators(*timeframe);
+        }
+        updated
+    }
+
+    fn sync_indicators(&mut self, timeframe: Timeframe) {
+        let candles = self
+            .series
+            .get(&timeframe)
+            .map(|c| c.as_slice())
+            .unwrap_or(&[]);
+        if let Some(indicators) = self.indicators.get_mut(&timeframe) {
+            for indicator in indicators {
+                indicator.sync(candles);
+            }
+        }
+        if let Some(detector) = self.smc_detectors.get_mut(&timeframe) {
+            self.smc_events.extend(
+                detector
+                    .new_events(candles)
+                    .into_iter()
+                    .map(|event| (timeframe, event)),
+            );
+        }
+    }
+}
+
+/// Merges the prices into the candle `at` falls in. Periods without prices in between carry
+/// the previous close, prices for a candle that already closed are ignored.
+fn update_candle(
+    candles: &mut Vec<MarkPriceBucket>,
+    timeframe: Timeframe,
+    currency_pair: &str,
+    at: DateTime<Utc>,
+    prices: &MarkPriceBucket,
+) -> bool {
+    let start = timeframe.candle_start(at);
+    let last_start = candles.last().and_then(|c| parse_time(&c.start_time));
+    match last_start {
+        Some(last_start) if last_start == start => {
+            let last = candles.last_mut().unwrap();
+            last.high = last.high.max(prices.high);
+            last.low = last.low.min(prices.low);
+            last.close = prices.close;
+            last.volume += prices.volume;
+            return true;
+        }
+        Some(last_start) if last_start > start => return false,
+        _ => {}
+    }
+
+    if let (Some(last_start), Some(last_close)) = (last_start, candles.last().map(|c| c.close)) {
+        let flat = MarkPriceBucket {
+            open: last_close,
+            high: last_close,
+            low: last_close,
+            close: last_close,
+            volume: Decimal::ZERO,
+            ..prices.clone()
+        };
+        let mut gap_start = last_start + chrono::Duration::seconds(timeframe.seconds());
+        while gap_start < start {
+            candles.push(new_candle(timeframe, currency_pair, gap_start, &flat));
+            gap_start += chrono::Duration::seconds(timeframe.seconds());
+        }
+    }
+    candles.push(new_candle(timeframe, currency_pair, start, prices));
+    if candles.len() > MAX_CANDLES {
+        candles.drain(..candles.len() - MAX_CANDLES);
+    }
+    true
+}
+
+fn new_candle(
+    timeframe: Timeframe,
+    currency_pair: &str,
+    start: DateTime<Utc>,
+    prices: &MarkPriceBucket,
+) -> MarkPriceBucket {
+    MarkPriceBucket {
+        currency_pair_symbol: currency_pair.to_string(),
+        bucket_period_in_seconds: timeframe.seconds() as u16,
+        start_time: start.to_rfc3339_opts(SecondsFormat::Secs, true),
+        open: prices.open,
+        high: prices.high,
+        low: prices.low,
+        close: prices.close,
+        volume: prices.volume,
+    }
+}
+
+fn parse_time(time: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(time)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
@@ -1,11 +1,333 @@
+//! Settings read from the environment and the .env file. Each strategy's config is read on its
+//! own by its `from_env`, so a strategy can be created without the rest of the config.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use rust_decimal::Decimal;
+
+use crate::candles::Timeframe;
+use crate::indicators::Indicator;
+
+/// Parses the variable, or the default when it is unset. Panics naming the variable when the
+/// value does not parse or `valid` rejects it.
+fn env_value_where<T: FromStr>(name: &str, default: &str, valid: impl Fn(&T) -> bool) -> T {
+    std::env::var(name)
+        .unwrap_or(String::from(default))
+        .parse::<T>()
+        .ok()
+        .filter(valid)
+        .unwrap_or_else(|| panic!("Invalid {}", name))
+}
+
+fn env_value<T: FromStr>(name: &str, default: &str) -> T {
+    env_value_where(name, default, |_| true)
+}
+
+/// None when the variable is unset, panics naming the variable when it does not parse
+fn env_optional<T: FromStr>(name: &str) -> Option<T> {
+    std::env::var(name)
+        .ok()
+        .map(|v| v.parse::<T>().unwrap_or_else(|_| panic!("Invalid {}", name)))
+}
+
 pub trait ConfigProvider {
     fn get_config(&self) -> &Config;
 }
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mode {
+    Live,
+    Paper,
+    Backtest,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "live" => Ok(Mode::Live),
+            "paper" => Ok(Mode::Paper),
+            "backtest" => Ok(Mode::Backtest),
+            _ => Err(format!(
+                "Mode: {} not supported, use live, paper or backtest",
+                s
+            )),
+        }
+    }
+}
+
+/// What happens to the open orders for the market when the bot is stopped
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShutdownPolicy {
+    CancelOrders,
+    KeepOrders,
+}
+
+impl FromStr for ShutdownPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "cancel" => Ok(ShutdownPolicy::CancelOrders),
+            "keep" => Ok(ShutdownPolicy::KeepOrders),
+            _ => Err(format!(
+                "Shutdown policy: {} not supported, use cancel or keep",
+                s
+            )),
+        }
+    }
+}
+
+pub struct ShutdownConfig {
+    pub policy: ShutdownPolicy,
+    /// Where positions, open orders and fills are written on shutdown
+    pub state_file: String,
+}
+
+pub struct BacktestConfig {
+    pub file: Option<String>,
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    pub period_seconds: String,
+    pub starting_quote_balance: Decimal,
+    pub fee_rate: Decimal,
+}
+
+pub struct PaperConfig {
+    pub quote_balance: Decimal,
+    pub base_balance: Decimal,
+    pub fee_rate: Decimal,
+}
+
+/// Limits left unset are not enforced
+#[derive(Debug, Clone, Default)]
+pub struct RiskConfig {
+    /// Base quantity by pair symbol, open orders included. Pairs without one are unlimited.
+    pub max_position: HashMap<String, Decimal>,
+    /// Quote value of a single order
+    pub max_order_notional: Option<Decimal>,
+    pub max_orders_per_minute: Option<u32>,
+    /// Loss in the market's quote currency since the start of the UTC day that activates the
+    /// kill switch
+    pub daily_loss_limit: Option<Decimal>,
+}
+
+/// Price grid for the grid strategy. Bounds left unset are placed GRID_RANGE_PERCENT either
+/// side of the mid price when the grid starts.
+#[derive(Debug, Clone)]
+pub struct GridConfig {
+    pub lower_price: Option<Decimal>,
+    pub upper_price: Option<Decimal>,
+    /// Number of prices in the grid, bounds included
+    pub levels: usize,
+    pub range_percent: Decimal,
+}
+
+impl GridConfig {
+    pub fn from_env() -> Self {
+        dotenv::dotenv().ok();
+        GridConfig {
+            lower_price: env_optional("GRID_LOWER_PRICE"),
+            upper_price: env_optional("GRID_UPPER_PRICE"),
+            // a grid needs at least a lower and an upper price
+            levels: env_value_where("GRID_LEVELS", "10", |levels: &usize| *levels >= 2),
+            range_percent: env_value("GRID_RANGE_PERCENT", "5"),
+        }
+    }
+}
+
+/// Quoting for the market making strategy, spreads and thresholds are in basis points of the
+/// mid price
+#[derive(Debug, Clone)]
+pub struct MarketMakingConfig {
+    /// Distance of each quote from the mid in a quiet market
+    pub half_spread_bps: Decimal,
+    /// ATRs of the 1m candles added to the half spread
+    pub volatility_multiplier: Decimal,
+    /// Share of the available quote and base balances offered on each side
+    pub order_percent: Decimal,
+    /// How far, in half spreads, a fully one-sided inventory shifts both quotes
+    pub inventory_skew: Decimal,
+    /// Mid price move that amends the quotes
+    pub refresh_bps: Decimal,
+}
+
+impl MarketMakingConfig {
+    pub fn from_env() -> Self {
+        dotenv::dotenv().ok();
+        MarketMakingConfig {
+            half_spread_bps: env_value("MM_HALF_SPREAD_BPS", "20"),
+            volatility_multiplier: env_value("MM_VOLATILITY_MULTIPLIER", "1"),
+            order_percent: env_value("MM_ORDER_PERCENT", "10"),
+            inventory_skew: env_value("MM_INVENTORY_SKEW", "1"),
+            refresh_bps: env_value("MM_REFRESH_BPS", "10"),
+        }
+    }
+}
+
+/// Triangle, thresholds and sizing of the triangular arbitrage scanner
+#[derive(Debug, Clone)]
+pub struct ArbitrageConfig {
+    /// The three pairs of the triangle, the market is one of them
+    pub pairs: Vec<String>,
+    /// Currency each round trip starts and ends in, sizes and profits are in it
+    pub start_currency: String,
+    /// Taker fee charged on every leg
+    pub fee_rate: Decimal,
+    /// Fee adjusted round trip profit an opportunity needs to be logged or executed
+    pub min_profit_bps: Decimal,
+    /// Largest round trip in the start currency, unlimited when unset
+    pub max_amount: Option<Decimal>,
+    /// Places the three legs of an opportunity instead of only logging it
+    pub execute: bool,
+}
+
+impl ArbitrageConfig {
+    pub fn from_env() -> Self {
+        dotenv::dotenv().ok();
+        let pairs: Vec<String> = env_value::<String>("ARB_PAIRS", "BTCZAR,BTCUSDC,USDCZAR")
+            .split(',')
+            .map(|pair| pair.trim().to_uppercase())
+            .filter(|pair| !pair.is_empty())
+            .collect();
+        if pairs.len() != 3 {
+            panic!("Invalid ARB_PAIRS, a triangle needs three pairs");
+        }
+        ArbitrageConfig {
+            pairs,
+            start_currency: env_value::<String>("ARB_START_CURRENCY", "ZAR").to_uppercase(),
+            fee_rate: env_value("ARB_FEE_RATE", "0.001"),
+            min_profit_bps: env_value("ARB_MIN_PROFIT_BPS", "10"),
+            max_amount: env_optional("ARB_MAX_AMOUNT"),
+            execute: env_value("ARB_EXECUTE", "false"),
+        }
+    }
+}
+
+/// Swing detection of the break of structure strategy
+#[derive(Debug, Clone)]
+pub struct BosConfig {
+    pub timeframe: Timeframe,
+    /// Candles either side of a swing that must be lower, for a high, or higher, for a low
+    pub swing_width: usize,
+    /// Closed candles scanned for swings and breaks
+    pub lookback: usize,
+}
+
+impl BosConfig {
+    pub fn from_env() -> Self {
+        dotenv::dotenv().ok();
+        BosConfig {
+            timeframe: env_value("BOS_TIMEFRAME", "1m"),
+            swing_width: env_value_where("BOS_SWING_WIDTH", "3", |width: &usize| *width > 0),
+            lookback: env_value("BOS_LOOKBACK", "60"),
+        }
+    }
+}
+
+/// Bands, stop and sizing of the Bollinger mean reversion strategy
+#[derive(Debug, Clone)]
+pub struct BollingerConfig {
+    pub timeframe: Timeframe,
+    pub period: usize,
+    pub standard_deviations: Decimal,
+    /// How far below the entry, as a percentage, a position is stopped out
+    pub stop_percent: Decimal,
+    /// Share of the available quote balance spent on an entry
+    pub order_percent: Decimal,
+}
+
+impl BollingerConfig {
+    pub fn from_env() -> Self {
+        dotenv::dotenv().ok();
+        BollingerConfig {
+            timeframe: env_value("BB_TIMEFRAME", "1m"),
+            period: env_value_where("BB_PERIOD", "20", |period: &usize| *period > 0),
+            standard_deviations: env_value("BB_STD_DEVS", "2"),
+            stop_percent: env_value("BB_STOP_PERCENT", "2"),
+            order_percent: env_value("BB_ORDER_PERCENT", "100"),
+        }
+    }
+}
+
+/// Schedule and sizing of the dollar-cost averaging strategy
+#[derive(Debug, Clone)]
+pub struct DcaConfig {
+    /// Quote currency spent on each scheduled buy
+    pub quote_amount: Decimal,
+    pub interval_minutes: i64,
+    /// Timeframe and period of the moving average that marks a dip, a period of 0 turns the
+    /// dip multiplier off
+    pub ma_timeframe: Timeframe,
+    pub ma_period: usize,
+    /// Scales the quote amount of a buy made below the moving average
+    pub dip_multiplier: Decimal,
+}
+
+impl DcaConfig {
+    pub fn from_env() -> Self {
+        dotenv::dotenv().ok();
+        DcaConfig {
+            quote_amount: env_value("DCA_QUOTE_AMOUNT", "100"),
+            interval_minutes: env_value_where("DCA_INTERVAL_MINUTES", "1440", |minutes: &i64| {
+                *minutes > 0
+            }),
+            ma_timeframe: env_value("DCA_MA_TIMEFRAME", "1h"),
+            ma_period: env_value("DCA_MA_PERIOD", "20"),
+            dip_multiplier: env_value("DCA_DIP_MULTIPLIER", "1"),
+        }
+    }
+}
+
+/// PINGs keep the WebSockets alive, a connection that stops answering them is reopened
+#[derive(Debug, Clone)]
+pub struct HeartbeatConfig {
+    pub ping_interval: Duration,
+    /// How long without a PONG before the connection is considered dead
+    pub pong_timeout: Duration,
+}
+
+/// An indicator attached to the market's candles on top of the strategy's own, logged as each
+/// candle of its timeframe closes
+#[derive(Debug, Clone)]
+pub struct IndicatorConfig {
+    pub timeframe: Timeframe,
+    /// The indicator as configured, e.g. `rsi:14`
+    pub name: String,
+    pub indicator: Indicator,
+}
+
+/// `timeframe:indicator`, e.g. `5m:rsi:14`
+impl FromStr for IndicatorConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (timeframe, name) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Indicator: {} needs a timeframe, e.g. 5m:rsi:14", s))?;
+        Ok(IndicatorConfig {
+            timeframe: timeframe.parse()?,
+            name: name.to_string(),
+            indicator: name.parse()?,
+        })
+    }
+}
+
 pub struct Config {
     pub api_key: String,
     pub api_secret: String,
     pub market: String,
     pub strategy: String,
+    pub mode: Mode,
+    pub backtest: BacktestConfig,
+    pub paper: PaperConfig,
+    pub risk: RiskConfig,
+    pub shutdown: ShutdownConfig,
+    pub heartbeat: HeartbeatConfig,
+    pub indicators: Vec<IndicatorConfig>,
 }
 
 pub struct DotEnvConfigProvider(Config);
@@ -19,12 +341,116 @@ impl DotEnvConfigProvider {
         let api_secret = env::var("API_SECRET").expect("Missing API_SECRET");
         let market = env::var("MARKET").expect("Missing MARKET");
         let strategy = env::var("STRATEGY").expect("Missing STRATEGY");
-        
+        let mode = env::var("MODE")
+            .unwrap_or(String::from("live"))
+            .parse::<Mode>()
+            .expect("Invalid MODE");
+
+        let backtest = BacktestConfig {
+            file: env::var("BACKTEST_FILE").ok(),
+            start_time: env::var("BACKTEST_START").ok(),
+            end_time: env::var("BACKTEST_END").ok(),
+            period_seconds: env::var("BACKTEST_PERIOD_SECONDS").unwrap_or(String::from("60")),
+            starting_quote_balance: env::var("BACKTEST_QUOTE_BALANCE")
+                .unwrap_or(String::from("10000"))
+                .parse::<Decimal>()
+                .expect("Invalid BACKTEST_QUOTE_BALANCE"),
+            fee_rate: env::var("BACKTEST_FEE_RATE")
+                .unwrap_or(String::from("0.001"))
+                .parse::<Decimal>()
+                .expect("Invalid BACKTEST_FEE_RATE"),
+        };
+
+        let paper = PaperConfig {
+            quote_balance: env::var("PAPER_QUOTE_BALANCE")
+                .unwrap_or(String::from("10000"))
+                .parse::<Decimal>()
+                .expect("Invalid PAPER_QUOTE_BALANCE"),
+            base_balance: env::var("PAPER_BASE_BALANCE")
+                .unwrap_or(String::from("0"))
+                .parse::<Decimal>()
+                .expect("Invalid PAPER_BASE_BALANCE"),
+            fee_rate: env::var("PAPER_FEE_RATE")
+                .unwrap_or(String::from("0.001"))
+                .parse::<Decimal>()
+                .expect("Invalid PAPER_FEE_RATE"),
+        };
+
+        let risk = RiskConfig {
+            max_position: env::var("RISK_MAX_POSITION")
+                .unwrap_or_default()
+                .split(',')
+                .map(|limit| limit.trim())
+                .filter(|limit| !limit.is_empty())
+                .map(|limit| {
+                    limit
+                        .split_once(':')
+                        .and_then(|(pair, max)| {
+                            Some((pair.trim().to_uppercase(), max.trim().parse().ok()?))
+                        })
+                        .expect("Invalid RISK_MAX_POSITION")
+                })
+                .collect(),
+            max_order_notional: env::var("RISK_MAX_ORDER_NOTIONAL").ok().map(|v| {
+                v.parse::<Decimal>()
+                    .expect("Invalid RISK_MAX_ORDER_NOTIONAL")
+            }),
+            max_orders_per_minute: env::var("RISK_MAX_ORDERS_PER_MINUTE").ok().map(|v| {
+                v.parse::<u32>()
+                    .expect("Invalid RISK_MAX_ORDERS_PER_MINUTE")
+            }),
+            daily_loss_limit: env::var("RISK_DAILY_LOSS_LIMIT").ok().map(|v| {
+                v.parse::<Decimal>()
+                    .expect("Invalid RISK_DAILY_LOSS_LIMIT")
+            }),
+        };
+
+        let shutdown = ShutdownConfig {
+            policy: env::var("SHUTDOWN_POLICY")
+                .unwrap_or(String::from("cancel"))
+                .parse::<ShutdownPolicy>()
+                .expect("Invalid SHUTDOWN_POLICY"),
+            state_file: env::var("STATE_FILE").unwrap_or(String::from("rusty_bot_state.json")),
+        };
+
+        let heartbeat = HeartbeatConfig {
+            ping_interval: Duration::from_secs(
+                env::var("WS_PING_INTERVAL_SECONDS")
+                    .unwrap_or(String::from("10"))
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|seconds| *seconds > 0)
+                    .expect("Invalid WS_PING_INTERVAL_SECONDS"),
+            ),
+            pong_timeout: Duration::from_secs(
+                env::var("WS_PONG_TIMEOUT_SECONDS")
+                    .unwrap_or(String::from("30"))
+                    .parse::<u64>()
+                    .expect("Invalid WS_PONG_TIMEOUT_SECONDS"),
+            ),
+        };
+
+        let indicators = env::var("INDICATORS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|indicator| indicator.trim())
+            .filter(|indicator| !indicator.is_empty())
+            .map(|indicator| indicator.parse::<IndicatorConfig>())
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Invalid INDICATORS");
+
         let config = Config {
              api_key,
              api_secret,
              market,
-             strategy
+             strategy,
+             mode,
+             backtest,
+             paper,
+             risk,
+             shutdown,
+             heartbeat,
+             indicators,
         };
 
         DotEnvConfigProvider(config)
@@ -41,4 +467,4 @@ impl Default for DotEnvConfigProvider {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
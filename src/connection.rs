@@ -0,0 +1,168 @@
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use colored::Colorize;
+use futures_util::{SinkExt, StreamExt};
+use http::Uri;
+use log::{error, warn};
+use serde_json::Value;
+use tokio::sync::watch;
+use tokio::time::{sleep, Instant};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+use tungstenite::http;
+
+use crate::config::HeartbeatConfig;
+use crate::strategies::break_of_structure::helper::create_ws_request;
+use crate::strategies::{SharedStrategy, StrategyContext};
+use crate::{create_ping_thread, handle_ws_incoming_messages};
+
+const VALR_WS_URL: &str = "wss://api.valr.com";
+const INITIAL_RECONNECT_DELAY_SECONDS: u64 = 1;
+const MAX_RECONNECT_DELAY_SECONDS: u64 = 60;
+
+pub struct WsSubscription {
+    /// Name shown in the logs, e.g. "Trade   WS"
+    pub name: String,
+    pub subscription_type: &'static str,
+    pub path: &'static str,
+    /// Replayed after every (re)connection
+    pub subscribe_message: Option<Value>,
+    pub heartbeat: HeartbeatConfig,
+}
+
+/// When the last PONG arrived, recorded by the reader and checked by the ping task
+#[derive(Debug, Clone)]
+pub struct LastPong(Arc<Mutex<Instant>>);
+
+impl LastPong {
+    pub fn new(at: Instant) -> Self {
+        LastPong(Arc::new(Mutex::new(at)))
+    }
+
+    pub fn record(&self, at: Instant) {
+        *self.0.lock().unwrap() = at;
+    }
+
+    pub fn is_overdue(&self, now: Instant, pong_timeout: Duration) -> bool {
+        now.saturating_duration_since(*self.0.lock().unwrap()) > pong_timeout
+    }
+}
+
+/// Exponential backoff: 1s, 2s, 4s ... capped at a minute
+pub fn reconnect_delay(attempt: u32) -> Duration {
+    let seconds = 2u64
+        .checked_pow(attempt)
+        .map(|factor| INITIAL_RECONNECT_DELAY_SECONDS.saturating_mul(factor))
+        .unwrap_or(MAX_RECONNECT_DELAY_SECONDS)
+        .min(MAX_RECONNECT_DELAY_SECONDS);
+    Duration::from_secs(seconds)
+}
+
+/// Keeps a WebSocket connected until shutdown. Every connection is signed with a fresh
+/// timestamp and resubscribed; when it closes, errors or stops answering PINGs it is reopened
+/// with backoff.
+pub async fn supervise_connection(
+    subscription: WsSubscription,
+    api_key: String,
+    api_secret: String,
+    strategy: SharedStrategy,
+    strategy_context: StrategyContext,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let url = format!("{}{}", VALR_WS_URL, subscription.path);
+    let mut attempt: u32 = 0;
+    let mut reconnects: u32 = 0;
+
+    while !*shutdown.borrow() {
+        let request = create_ws_request(
+            Uri::from_str(&url).unwrap(),
+            &api_key,
+            &api_secret,
+            subscription.path,
+            "GET",
+            None,
+        );
+        match connect_async(request).await {
+            Ok((ws_stream, _)) => {
+                if reconnects > 0 {
+                    println!(
+                        "{}| {} {} (reconnect #{})",
+                        Utc::now().to_rfc3339().blue(),
+                        subscription.name.bold().green(),
+                        "Reconnected".yellow(),
+                        reconnects
+                    );
+                }
+                attempt = 0;
+                let (mut write, read) = ws_stream.split();
+                let subscribed = match &subscription.subscribe_message {
+                    None => true,
+                    Some(message) => match write.send(Message::from(message.to_string())).await {
+                        Ok(_) => true,
+                        Err(e) => {
+                            error!("{} failed to subscribe: {}", subscription.name, e);
+                            false
+                        }
+                    },
+                };
+                if subscribed {
+                    let last_pong = LastPong::new(Instant::now());
+                    let mut ping_handle = create_ping_thread(
+                        write,
+                        subscription.name.clone(),
+                        subscription.heartbeat.clone(),
+                        last_pong.clone(),
+                        shutdown.clone(),
+                    );
+                    tokio::select! {
+                        _ = handle_ws_incoming_messages(
+                            read,
+                            strategy.clone(),
+                            subscription.subscription_type,
+                            strategy_context.clone(),
+                            last_pong,
+                        ) => ping_handle.abort(),
+                        // the ping task stops when the pongs do, dropping the read half
+                        _ = &mut ping_handle => {}
+                        // the ping task owns the write half and sends the close frame
+                        _ = shutdown.changed() => {
+                            let _ = ping_handle.await;
+                        }
+                    }
+                }
+                if *shutdown.borrow() {
+                    break;
+                }
+                warn!("{} connection closed", subscription.name);
+            }
+            Err(e) => {
+                error!("Error connecting to {}: {}", subscription.name, e);
+            }
+        }
+
+        let delay = reconnect_delay(attempt);
+        attempt = attempt.saturating_add(1);
+        reconnects += 1;
+        println!(
+            "{}| {} {} in {}s (attempt {})",
+            Utc::now().to_rfc3339().blue(),
+            subscription.name.bold().green(),
+            "Reconnecting".red(),
+            delay.as_secs(),
+            attempt
+        );
+        tokio::select! {
+            _ = sleep(delay) => {},
+            _ = shutdown.changed() => {},
+        }
+    }
+    println!(
+        "{}| {} {}",
+        Utc::now().to_rfc3339().blue(),
+        subscription.name.bold().green(),
+        "Closed".yellow()
+    );
+}
@@ -1,45 +1,10 @@
-use std::sync::Arc;
 use std::time::SystemTime;
 
 use hmac::{Hmac, KeyInit, Mac};
 use http::Uri;
 use reqwest::RequestBuilder;
 use sha2::Sha512;
-use tokio::sync::RwLock;
 use tungstenite::client::IntoClientRequest;
-use crate::rusty_bot_models::{BalanceUpdate, CurrencyPair, MarkPriceBucket};
-
-use crate::strategies::break_of_structure;
-
-pub async fn execute_strategy(
-    strategy: &str,
-    bucket_prices: Vec<MarkPriceBucket>,
-    asks: &Arc<RwLock<Vec<Vec<String>>>>,
-    bids: &Arc<RwLock<Vec<Vec<String>>>>,
-    balances: Arc<RwLock<Vec<BalanceUpdate>>>,
-    currency_pair: CurrencyPair
-) {
-    match strategy {
-        "break_of_structure" => {
-            break_of_structure::test_for_break_of_structure(bucket_prices, &asks, &bids, balances, currency_pair).await;
-        }
-        _ => {
-            println!("Strategy {} not supported", strategy)
-        }
-    }
-}
-
-pub fn strip_slashes(s: &str) -> Option<String> {
-    let mut n = String::new();
-    let mut chars = s.chars();
-    while let Some(c) = chars.next() {
-        n.push(match c {
-            '\\' => chars.next()?,
-            c => c,
-        });
-    }
-    Some(n)
-}
 
 pub fn create_ws_request(
     url: Uri,
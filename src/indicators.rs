@@ -0,0 +1,541 @@
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use chrono::DateTime;
+use rust_decimal::{Decimal, MathematicalOps};
+
+use crate::rusty_bot_models::MarkPriceBucket;
+
+/// Upper, middle and lower lines of Bollinger Bands or a Donchian channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bands {
+    pub upper: Decimal,
+    pub middle: Decimal,
+    pub lower: Decimal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MacdValue {
+    pub macd: Decimal,
+    pub signal: Decimal,
+    pub histogram: Decimal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndicatorValue {
+    Single(Decimal),
+    Bands(Bands),
+    Macd(MacdValue),
+}
+
+impl IndicatorValue {
+    pub fn single(&self) -> Option<Decimal> {
+        match self {
+            IndicatorValue::Single(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn bands(&self) -> Option<Bands> {
+        match self {
+            IndicatorValue::Bands(bands) => Some(*bands),
+            _ => None,
+        }
+    }
+}
+
+impl Display for IndicatorValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IndicatorValue::Single(value) => write!(f, "{}", value.round_dp(8)),
+            IndicatorValue::Bands(bands) => write!(
+                f,
+                "upper {} middle {} lower {}",
+                bands.upper.round_dp(8),
+                bands.middle.round_dp(8),
+                bands.lower.round_dp(8)
+            ),
+            IndicatorValue::Macd(macd) => write!(
+                f,
+                "macd {} signal {} histogram {}",
+                macd.macd.round_dp(8),
+                macd.signal.round_dp(8),
+                macd.histogram.round_dp(8)
+            ),
+        }
+    }
+}
+
+/// Simple moving average of the closes
+#[derive(Debug, Clone)]
+pub struct Sma {
+    period: usize,
+    window: VecDeque<Decimal>,
+    sum: Decimal,
+}
+
+impl Sma {
+    pub fn new(period: usize) -> Self {
+        Sma {
+            period: period.max(1),
+            window: VecDeque::new(),
+            sum: Decimal::ZERO,
+        }
+    }
+
+    pub fn update_value(&mut self, value: Decimal) -> Option<Decimal> {
+        self.window.push_back(value);
+        self.sum += value;
+        if self.window.len() > self.period {
+            self.sum -= self.window.pop_front().unwrap();
+        }
+        self.value()
+    }
+
+    pub fn value(&self) -> Option<Decimal> {
+        (self.window.len() == self.period).then(|| self.sum / Decimal::from(self.period))
+    }
+}
+
+/// Exponential moving average of the closes, seeded with the SMA of the first period
+#[derive(Debug, Clone)]
+pub struct Ema {
+    multiplier: Decimal,
+    seed: Sma,
+    value: Option<Decimal>,
+}
+
+impl Ema {
+    pub fn new(period: usize) -> Self {
+        let period = period.max(1);
+        Ema {
+            multiplier: Decimal::TWO / Decimal::from(period + 1),
+            seed: Sma::new(period),
+            value: None,
+        }
+    }
+
+    pub fn update_value(&mut self, value: Decimal) -> Option<Decimal> {
+        self.value = match self.value {
+            Some(ema) => Some((value - ema) * self.multiplier + ema),
+            None => self.seed.update_value(value),
+        };
+        self.value
+    }
+
+    pub fn value(&self) -> Option<Decimal> {
+        self.value
+    }
+}
+
+/// Wilder's relative strength index, 0 to 100
+#[derive(Debug, Clone)]
+pub struct Rsi {
+    period: usize,
+    previous_close: Option<Decimal>,
+    changes: usize,
+    average_gain: Decimal,
+    average_loss: Decimal,
+}
+
+impl Rsi {
+    pub fn new(period: usize) -> Self {
+        Rsi {
+            period: period.max(1),
+            previous_close: None,
+            changes: 0,
+            average_gain: Decimal::ZERO,
+            average_loss: Decimal::ZERO,
+        }
+    }
+
+    pub fn update_value(&mut self, close: Decimal) -> Option<Decimal> {
+        let previous_close = self.previous_close.replace(close)?;
+        let change = close - previous_close;
+        let gain = change.max(Decimal::ZERO);
+        let loss = (-change).max(Decimal::ZERO);
+        let period = Decimal::from(self.period);
+        self.changes += 1;
+        if self.changes <= self.period {
+            // the first averages are plain means of the first period's changes
+            self.average_gain += gain / period;
+            self.average_loss += loss / period;
+        } else {
+            self.average_gain = (self.average_gain * (period - Decimal::ONE) + gain) / period;
+            self.average_loss = (self.average_loss * (period - Decimal::ONE) + loss) / period;
+        }
+        self.value()
+    }
+
+    pub fn value(&self) -> Option<Decimal> {
+        if self.changes < self.period {
+            return None;
+        }
+        if self.average_loss.is_zero() {
+            return Some(if self.average_gain.is_zero() {
+                Decimal::from(50)
+            } else {
+                Decimal::ONE_HUNDRED
+            });
+        }
+        let relative_strength = self.average_gain / self.average_loss;
+        Some(Decimal::ONE_HUNDRED - Decimal::ONE_HUNDRED / (Decimal::ONE + relative_strength))
+    }
+}
+
+/// Wilder's average true range
+#[derive(Debug, Clone)]
+pub struct Atr {
+    period: usize,
+    previous_close: Option<Decimal>,
+    seed: Sma,
+    value: Option<Decimal>,
+}
+
+impl Atr {
+    pub fn new(period: usize) -> Self {
+        let period = period.max(1);
+        Atr {
+            period,
+            previous_close: None,
+            seed: Sma::new(period),
+            value: None,
+        }
+    }
+
+    pub fn update(&mut self, candle: &MarkPriceBucket) -> Option<Decimal> {
+        let range = candle.high - candle.low;
+        let true_range = match self.previous_close.replace(candle.close) {
+            Some(previous_close) => range
+                .max((candle.high - previous_close).abs())
+                .max((candle.low - previous_close).abs()),
+            None => range,
+        };
+        let period = Decimal::from(self.period);
+        self.value = match self.value {
+            Some(atr) => Some((atr * (period - Decimal::ONE) + true_range) / period),
+            None => self.seed.update_value(true_range),
+        };
+        self.value
+    }
+
+    pub fn value(&self) -> Option<Decimal> {
+        self.value
+    }
+}
+
+/// SMA of the closes with bands a number of population standard deviations either side
+#[derive(Debug, Clone)]
+pub struct BollingerBands {
+    standard_deviations: Decimal,
+    sma: Sma,
+}
+
+impl BollingerBands {
+    pub fn new(period: usize, standard_deviations: Decimal) -> Self {
+        BollingerBands {
+            standard_deviations,
+            sma: Sma::new(period),
+        }
+    }
+
+    pub fn update_value(&mut self, close: Decimal) -> Option<Bands> {
+        self.sma.update_value(close);
+        self.value()
+    }
+
+    pub fn value(&self) -> Option<Bands> {
+        let middle = self.sma.value()?;
+        let variance = self
+            .sma
+            .window
+            .iter()
+            .map(|close| (*close - middle) * (*close - middle))
+            .sum::<Decimal>()
+            / Decimal::from(self.sma.window.len());
+        let width = variance.sqrt()? * self.standard_deviations;
+        Some(Bands {
+            upper: middle + width,
+            middle,
+            lower: middle - width,
+        })
+    }
+}
+
+/// Difference between a fast and a slow EMA of the closes, with an EMA of that as the signal
+#[derive(Debug, Clone)]
+pub struct Macd {
+    fast: Ema,
+    slow: Ema,
+    signal: Ema,
+}
+
+impl Macd {
+    pub fn new(fast_period: usize, slow_period: usize, signal_period: usize) -> Self {
+        Macd {
+            fast: Ema::new(fast_period),
+            slow: Ema::new(slow_period),
+            signal: Ema::new(signal_period),
+        }
+    }
+
+    pub fn update_value(&mut self, close: Decimal) -> Option<MacdValue> {
+        let fast = self.fast.update_value(close);
+        let slow = self.slow.update_value(close);
+        if let (Some(fast), Some(slow)) = (fast, slow) {
+            self.signal.update_value(fast - slow);
+        }
+        self.value()
+    }
+
+    pub fn value(&self) -> Option<MacdValue> {
+        let macd = self.fast.value()? - self.slow.value()?;
+        let signal = self.signal.value()?;
+        Some(MacdValue {
+            macd,
+            signal,
+            histogram: macd - signal,
+        })
+    }
+}
+
+/// Volume weighted average of the typical price (high + low + close) / 3 since the start of
+/// the UTC day. None while the day has no volume, mark price buckets carry none.
+#[derive(Debug, Clone, Default)]
+pub struct Vwap {
+    day: Option<String>,
+    price_volume: Decimal,
+    volume: Decimal,
+}
+
+impl Vwap {
+    pub fn new() -> Self {
+        Vwap::default()
+    }
+
+    pub fn update(&mut self, candle: &MarkPriceBucket) -> Option<Decimal> {
+        let day = DateTime::parse_from_rfc3339(&candle.start_time)
+            .map(|start| start.date_naive().to_string())
+            .ok();
+        if day != self.day {
+            self.day = day;
+            self.price_volume = Decimal::ZERO;
+            self.volume = Decimal::ZERO;
+        }
+        let typical_price = (candle.high + candle.low + candle.close) / Decimal::from(3);
+        self.price_volume += typical_price * candle.volume;
+        self.volume += candle.volume;
+        self.value()
+    }
+
+    pub fn value(&self) -> Option<Decimal> {
+        (self.volume > Decimal::ZERO).then(|| self.price_volume / self.volume)
+    }
+}
+
+/// Highest high and lowest low of the period, the middle is halfway between
+#[derive(Debug, Clone)]
+pub struct Donchian {
+    period: usize,
+    window: VecDeque<(Decimal, Decimal)>,
+}
+
+impl Donchian {
+    pub fn new(period: usize) -> Self {
+        Donchian {
+            period: period.max(1),
+            window: VecDeque::new(),
+        }
+    }
+
+    pub fn update(&mut self, candle: &MarkPriceBucket) -> Option<Bands> {
+        self.window.push_back((candle.high, candle.low));
+        if self.window.len() > self.period {
+            self.window.pop_front();
+        }
+        self.value()
+    }
+
+    pub fn value(&self) -> Option<Bands> {
+        if self.window.len() < self.period {
+            return None;
+        }
+        let upper = self.window.iter().map(|(high, _)| *high).max()?;
+        let lower = self.window.iter().map(|(_, low)| *low).min()?;
+        Some(Bands {
+            upper,
+            middle: (upper + lower) / Decimal::TWO,
+            lower,
+        })
+    }
+}
+
+/// Any of the indicators, so they can be attached to a candle series by name
+#[derive(Debug, Clone)]
+pub enum Indicator {
+    Sma(Sma),
+    Ema(Ema),
+    Rsi(Rsi),
+    Atr(Atr),
+    BollingerBands(BollingerBands),
+    Macd(Macd),
+    Vwap(Vwap),
+    Donchian(Donchian),
+}
+
+impl Indicator {
+    /// Feeds the next closed candle
+    pub fn update(&mut self, candle: &MarkPriceBucket) -> Option<IndicatorValue> {
+        match self {
+            Indicator::Sma(sma) => {
+                sma.update_value(candle.close);
+            }
+            Indicator::Ema(ema) => {
+                ema.update_value(candle.close);
+            }
+            Indicator::Rsi(rsi) => {
+                rsi.update_value(candle.close);
+            }
+            Indicator::Atr(atr) => {
+                atr.update(candle);
+            }
+            Indicator::BollingerBands(bollinger_bands) => {
+                bollinger_bands.update_value(candle.close);
+            }
+            Indicator::Macd(macd) => {
+                macd.update_value(candle.close);
+            }
+            Indicator::Vwap(vwap) => {
+                vwap.update(candle);
+            }
+            Indicator::Donchian(donchian) => {
+                donchian.update(candle);
+            }
+        }
+        self.value()
+    }
+
+    /// None until the indicator has seen enough candles
+    pub fn value(&self) -> Option<IndicatorValue> {
+        match self {
+            Indicator::Sma(sma) => sma.value().map(IndicatorValue::Single),
+            Indicator::Ema(ema) => ema.value().map(IndicatorValue::Single),
+            Indicator::Rsi(rsi) => rsi.value().map(IndicatorValue::Single),
+            Indicator::Atr(atr) => atr.value().map(IndicatorValue::Single),
+            Indicator::BollingerBands(bollinger_bands) => {
+                bollinger_bands.value().map(IndicatorValue::Bands)
+            }
+            Indicator::Macd(macd) => macd.value().map(IndicatorValue::Macd),
+            Indicator::Vwap(vwap) => vwap.value().map(IndicatorValue::Single),
+            Indicator::Donchian(donchian) => donchian.value().map(IndicatorValue::Bands),
+        }
+    }
+}
+
+/// `name:parameters` with the periods in candles, e.g. `rsi:14`, `bollinger:20:2`,
+/// `macd:12:26:9` or `vwap`
+impl FromStr for Indicator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let name = parts.next().unwrap_or_default().to_lowercase();
+        let parameters: Vec<&str> = parts.collect();
+        let period = |i: usize| {
+            parameters
+                .get(i)
+                .and_then(|p| p.parse::<usize>().ok())
+                .filter(|p| *p > 0)
+        };
+        let indicator = match (name.as_str(), parameters.len()) {
+            ("sma", 1) => period(0).map(|p| Indicator::Sma(Sma::new(p))),
+            ("ema", 1) => period(0).map(|p| Indicator::Ema(Ema::new(p))),
+            ("rsi", 1) => period(0).map(|p| Indicator::Rsi(Rsi::new(p))),
+            ("atr", 1) => period(0).map(|p| Indicator::Atr(Atr::new(p))),
+            ("donchian", 1) => period(0).map(|p| Indicator::Donchian(Donchian::new(p))),
+            ("bollinger", 2) => {
+                period(0)
+                    .zip(parameters[1].parse::<Decimal>().ok())
+                    .map(|(p, deviations)| {
+                        Indicator::BollingerBands(BollingerBands::new(p, deviations))
+                    })
+            }
+            ("macd", 3) => match (period(0), period(1), period(2)) {
+                (Some(fast), Some(slow), Some(signal)) => {
+                    Some(Indicator::Macd(Macd::new(fast, slow, signal)))
+                }
+                _ => None,
+            },
+            ("vwap", 0) => Some(Indicator::Vwap(Vwap::new())),
+            _ => None,
+        };
+        indicator.ok_or_else(|| {
+            format!(
+                "Indicator: {} not supported, use sma, ema, rsi, atr or donchian:<period>, \
+                 bollinger:<period>:<deviations>, macd:<fast>:<slow>:<signal> or vwap",
+                s
+            )
+        })
+    }
+}
+
+/// An indicator attached to a candle series. Closed candles are fed once and the forming candle
+/// is applied to a copy of the indicator's state, so an update costs the candles that closed
+/// since the last one plus the forming one, not the whole history.
+#[derive(Debug, Clone)]
+pub struct AttachedIndicator {
+    pub name: String,
+    initial: Indicator,
+    closed: Indicator,
+    current: Indicator,
+    last_closed_start: Option<String>,
+}
+
+impl AttachedIndicator {
+    pub fn new(name: &str, indicator: Indicator) -> Self {
+        AttachedIndicator {
+            name: name.to_string(),
+            initial: indicator.clone(),
+            closed: indicator.clone(),
+            current: indicator,
+            last_closed_start: None,
+        }
+    }
+
+    /// Starts over, for when the series is replaced
+    pub fn reset(&mut self) {
+        self.closed = self.initial.clone();
+        self.current = self.initial.clone();
+        self.last_closed_start = None;
+    }
+
+    /// Catches up with the series, every candle but the last has closed. The candles already
+    /// fed are skipped with a binary search on the start time, the series is oldest first.
+    pub fn sync(&mut self, candles: &[MarkPriceBucket]) {
+        let Some((forming, closed)) = candles.split_last() else {
+            return;
+        };
+        let new_closed = match &self.last_closed_start {
+            Some(last) => &closed[closed.partition_point(|c| c.start_time <= *last)..],
+            None => closed,
+        };
+        for candle in new_closed {
+            self.closed.update(candle);
+        }
+        if let Some(candle) = new_closed.last() {
+            self.last_closed_start = Some(candle.start_time.clone());
+        }
+        self.current.clone_from(&self.closed);
+        self.current.update(forming);
+    }
+
+    pub fn value(&self) -> Option<IndicatorValue> {
+        self.current.value()
+    }
+
+    /// Value as of the last closed candle
+    pub fn closed_value(&self) -> Option<IndicatorValue> {
+        self.closed.value()
+    }
+}
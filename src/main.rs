@@ -1,48 +1,87 @@
 #![allow(unused_variables)]
 
+mod backtest;
+mod candles;
 mod config;
+mod indicators;
+mod connection;
+mod order_book;
+mod order_manager;
+mod orders;
+mod paper;
+mod position;
+mod risk;
 mod rusty_bot_models;
+mod shutdown;
+mod simulation;
+mod sizing;
+mod smc;
 mod strategies;
 mod tests;
 
-use crate::config::{ConfigProvider, DotEnvConfigProvider};
+use crate::candles::{CandleEngine, Timeframe};
+use crate::indicators::IndicatorValue;
+use crate::config::{
+    Config, ConfigProvider, DotEnvConfigProvider, HeartbeatConfig, Mode, ShutdownPolicy,
+};
+use crate::connection::{supervise_connection, LastPong, WsSubscription};
+use crate::order_book::{fetch_full_order_book, FullOrderBook, OrderBook};
+use crate::order_manager::{record_account_trade, OrderManager};
+use crate::orders::{Credentials, LiveOrderExecutor, OrderExecutor};
+use crate::paper::PaperOrderExecutor;
+use crate::position::PositionTracker;
+use crate::risk::{RiskManagedExecutor, RiskManager};
 use crate::rusty_bot_models::{CurrencyPair, WsMessage};
-use crate::strategies::break_of_structure::helper::{
-    create_http_request, create_ws_request, execute_strategy, strip_slashes,
+use crate::shutdown::{
+    persist_state, wait_for_kill_switch_signal, wait_for_shutdown_signal, BotState,
+};
+use crate::simulation::SimulatedAccount;
+use crate::strategies::{
+    available_strategies, create_candle_engine, create_strategy, SharedStrategy, StrategyContext,
 };
-use chrono::{DateTime, Duration, Utc};
+use chrono::{Duration, SecondsFormat, Utc};
 use colored::Colorize;
 use convert_case::{Case, Casing};
 use futures_util::future::try_join_all;
 use futures_util::stream::{SplitSink, SplitStream};
 use futures_util::{SinkExt, StreamExt};
-use http::Uri;
 use lazy_static::lazy_static;
 use log::{error, warn};
 use reqwest::Error;
 use rusty_bot_models::{
-    AggregatedOrderBookUpdate, BalanceUpdate, DepthOrderBookSnapshot, MarkPriceBucket, Order,
-    OrderBookData, TradePriceBucketUpdate,
+    AccountTrade, BalanceUpdate, DepthOrderBookSnapshot, FailedCancelOrder, MarkPriceBucket, Order,
+    OrderBookData, OrderProcessed, OrderStatusUpdate, Trade, TradePriceBucketUpdate,
 };
 use serde_json::json;
-use std::mem::replace;
-use std::str::FromStr;
+use std::collections::HashMap;
 use std::string::String;
 use std::sync::Arc;
 use tokio::net::TcpStream;
-use tokio::sync::RwLock;
-use tokio::task::{JoinHandle, yield_now};
-use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
-use tungstenite::http;
+use tokio::sync::{watch, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Instant, MissedTickBehavior};
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
 
-const FIVE_MINUTE_BUCKET_SECONDS: &str = "300";
+/// Candles of history loaded per timeframe at startup
+const HISTORY_CANDLES: i64 = 60;
+const SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
 
 lazy_static! {
-    static ref BUCKET_PRICES: Arc<RwLock<Vec<MarkPriceBucket>>> = Arc::new(RwLock::new(vec![]));
-    static ref BIDS: Arc<RwLock<Vec<Vec<String>>>> = Arc::new(RwLock::new(vec![]));
-    static ref ASKS: Arc<RwLock<Vec<Vec<String>>>> = Arc::new(RwLock::new(vec![]));
-    static ref ORDERS: Arc<RwLock<Vec<Order>>> = Arc::new(RwLock::new(vec![]));
+    static ref CANDLES: Arc<RwLock<CandleEngine>> =
+        Arc::new(RwLock::new(CandleEngine::default()));
+    static ref ORDER_BOOK: Arc<RwLock<OrderBook>> = Arc::new(RwLock::new(OrderBook::default()));
+    static ref ORDERS: Arc<RwLock<OrderManager>> = Arc::new(RwLock::new(OrderManager::default()));
     static ref BALANCES: Arc<RwLock<Vec<BalanceUpdate>>> = Arc::new(RwLock::new(vec![]));
+    static ref ACCOUNT_TRADES: Arc<RwLock<Vec<AccountTrade>>> = Arc::new(RwLock::new(vec![]));
+    static ref POSITIONS: Arc<RwLock<PositionTracker>> =
+        Arc::new(RwLock::new(PositionTracker::default()));
+    static ref FULL_ORDER_BOOK: Arc<RwLock<FullOrderBook>> =
+        Arc::new(RwLock::new(FullOrderBook::default()));
+    /// Pairs besides the market the strategy follows the top of book of
+    static ref FOLLOWED_PAIRS: Arc<RwLock<Vec<CurrencyPair>>> = Arc::new(RwLock::new(vec![]));
+    /// Latest book of every followed pair, by symbol
+    static ref PAIR_BOOKS: Arc<RwLock<HashMap<String, OrderBook>>> =
+        Arc::new(RwLock::new(HashMap::new()));
 }
 
 #[tokio::main]
@@ -51,104 +90,268 @@ async fn main() {
     let env_config_provider = DotEnvConfigProvider::new();
     let config = env_config_provider.get_config();
     env_logger::init();
-    let current_date_time = Utc::now().naive_utc();
-    let one_hour_ago_date_time = current_date_time - Duration::hours(1);
-    let currency_pair = get_currency_pair(config.market.clone()).await;
+    let strategy: SharedStrategy = match create_strategy(&config.strategy) {
+        None => panic!(
+            "Strategy: {} not supported, available strategies: {}",
+            config.strategy,
+            available_strategies().join(", ")
+        ),
+        Some(_strategy) => Arc::new(RwLock::new(_strategy)),
+    };
+    let mut symbols = vec![config.market.clone()];
+    symbols.extend(
+        strategy
+            .read()
+            .await
+            .pairs()
+            .into_iter()
+            .filter(|symbol| *symbol != config.market),
+    );
+    let mut currency_pairs = get_currency_pairs(&symbols).await;
+    let currency_pair = currency_pairs.remove(0);
     println!("{:?}", currency_pair);
-    get_historical_sixty_second_mark_price_buckets_for_pair(
-        &config.market,
-        one_hour_ago_date_time.to_string(),
-        current_date_time.to_string(),
-    )
-    .await
-    .expect("Error getting historical mark price buckets");
-    // get_open_orders_for_pair(&config.api_key, &config.api_secret, &config.market).await.expect("Error getting open orders");
+    *FOLLOWED_PAIRS.write().await = currency_pairs;
+    if config.mode == Mode::Backtest {
+        let bucket_prices = backtest::load_bucket_prices(&config.backtest, &config.market).await;
+        let mut strategy_writer = strategy.write().await;
+        let report = backtest::run_backtest(
+            strategy_writer.as_mut(),
+            currency_pair.clone(),
+            bucket_prices,
+            config.backtest.starting_quote_balance,
+            config.backtest.fee_rate,
+        )
+        .await;
+        report.print(&currency_pair);
+        return;
+    }
+    let executor: Arc<dyn OrderExecutor> = match config.mode {
+        Mode::Paper => Arc::new(
+            PaperOrderExecutor::new(
+                SimulatedAccount::new(
+                    currency_pair.clone(),
+                    config.paper.quote_balance,
+                    config.paper.base_balance,
+                    config.paper.fee_rate,
+                ),
+                ORDER_BOOK.clone(),
+                BALANCES.clone(),
+                ORDERS.clone(),
+                POSITIONS.clone(),
+            )
+            .await,
+        ),
+        _ => Arc::new(LiveOrderExecutor::new(Credentials {
+            api_key: config.api_key.clone(),
+            api_secret: config.api_secret.clone(),
+        })),
+    };
+    let mut traded_pairs = vec![currency_pair.clone()];
+    traded_pairs.extend(FOLLOWED_PAIRS.read().await.iter().cloned());
+    let risk_managed_executor = Arc::new(RiskManagedExecutor::new(
+        executor,
+        RiskManager::new(config.risk.clone()),
+        traded_pairs,
+        ORDER_BOOK.clone(),
+        PAIR_BOOKS.clone(),
+        ORDERS.clone(),
+        POSITIONS.clone(),
+        BALANCES.clone(),
+    ));
+    risk_managed_executor.roll_day().await;
+    let day_rollover_executor = risk_managed_executor.clone();
+    tokio::spawn(async move { day_rollover_executor.run_day_rollover().await });
+    let shutdown_executor = risk_managed_executor.clone();
+    let kill_switch_executor = risk_managed_executor.clone();
+    tokio::spawn(async move {
+        wait_for_kill_switch_signal().await;
+        kill_switch_executor.activate_kill_switch().await;
+    });
+    let executor: Arc<dyn OrderExecutor> = risk_managed_executor;
+    let strategy_context = StrategyContext {
+        currency_pair: currency_pair.clone(),
+        order_book: ORDER_BOOK.clone(),
+        balances: BALANCES.clone(),
+        orders: ORDERS.clone(),
+        positions: POSITIONS.clone(),
+        candles: CANDLES.clone(),
+        executor,
+    };
+    let mut candle_engine = create_candle_engine(strategy.read().await.as_ref());
+    for indicator in &config.indicators {
+        candle_engine.attach_indicator(
+            indicator.timeframe,
+            &indicator.name,
+            indicator.indicator.clone(),
+        );
+    }
+    let timeframes = candle_engine.timeframes();
+    *CANDLES.write().await = candle_engine;
+    seed_candles(&config.market, &timeframes)
+        .await
+        .expect("Error getting historical trade buckets");
 
+    let (shutdown_sender, shutdown_receiver) = watch::channel(false);
     let mut handles = vec![];
     let mut trade_update_read_handles = subscribe_to_trade_updates(
         &config.api_key,
         &config.api_secret,
-        &currency_pair,
-        config.strategy.clone(),
+        strategy.clone(),
+        strategy_context.clone(),
+        config.heartbeat.clone(),
+        shutdown_receiver.clone(),
     )
     .await;
-    let mut account_handlers = subscribe_to_account_updates(
-        &config.api_key,
-        &config.api_secret,
-        &currency_pair,
-        config.strategy.clone(),
+    handles.append(&mut trade_update_read_handles);
+    // paper trading publishes its own balances and orders in place of the account's
+    if config.mode != Mode::Paper {
+        let mut account_handlers = subscribe_to_account_updates(
+            &config.api_key,
+            &config.api_secret,
+            strategy.clone(),
+            strategy_context.clone(),
+            config.heartbeat.clone(),
+            shutdown_receiver.clone(),
+        )
+        .await;
+        handles.append(&mut account_handlers);
+    }
+
+    wait_for_shutdown_signal().await;
+    shutdown(
+        config,
+        strategy,
+        &shutdown_executor,
+        shutdown_sender,
+        handles,
     )
     .await;
-    handles.append(&mut trade_update_read_handles);
-    handles.append(&mut account_handlers);
+}
 
-    try_join_all(handles.into_iter())
-        .await
-        .expect("Failure joining tasks");
+/// Stops the strategy, cancels the open orders on every traded pair when the policy asks for
+/// it, closes the sockets and writes the state to the STATE_FILE. Orders on the pairs that were
+/// cancelled are left out of the state.
+async fn shutdown(
+    config: &Config,
+    strategy: SharedStrategy,
+    executor: &RiskManagedExecutor,
+    shutdown_sender: watch::Sender<bool>,
+    handles: Vec<JoinHandle<()>>,
+) {
+    println!(
+        "{}| {} policy: {:?}",
+        Utc::now().to_rfc3339().blue(),
+        "Shutting down".on_red(),
+        config.shutdown.policy
+    );
+    // waits for the running hook to finish and keeps new ones from starting
+    let strategy_writer = strategy.write().await;
+
+    if config.shutdown.policy == ShutdownPolicy::CancelOrders {
+        let cancelled = executor.cancel_all_traded_orders().await;
+        let mut orders_writer = ORDERS.write().await;
+        for currency_pair in &cancelled {
+            orders_writer.forget_pair(currency_pair);
+        }
+        drop(orders_writer);
+    } else if ORDERS.read().await.has_open_orders() {
+        warn!("Leaving open orders on the exchange, shutdown policy is keep");
+    }
+
+    let _ = shutdown_sender.send(true);
+    match tokio::time::timeout(SHUTDOWN_TIMEOUT, try_join_all(handles)).await {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => error!("Connection task failed during shutdown: {}", e),
+        Err(_) => warn!("Connections did not close within {:?}", SHUTDOWN_TIMEOUT),
+    }
+
+    let state = BotState::new(
+        &*POSITIONS.read().await,
+        &*ORDERS.read().await,
+        &ACCOUNT_TRADES.read().await,
+    );
+    match persist_state(&config.shutdown.state_file, &state) {
+        Ok(_) => println!(
+            "{}| State saved to {}",
+            Utc::now().to_rfc3339().blue(),
+            config.shutdown.state_file.green()
+        ),
+        Err(e) => error!(
+            "Error saving state to {}: {}",
+            config.shutdown.state_file, e
+        ),
+    }
+    drop(strategy_writer);
 }
 
 async fn subscribe_to_account_updates(
     api_key: &str,
     api_secret: &str,
-    currency_pair: &CurrencyPair,
-    strategy: String,
+    strategy: SharedStrategy,
+    strategy_context: StrategyContext,
+    heartbeat: HeartbeatConfig,
+    shutdown: watch::Receiver<bool>,
 ) -> Vec<JoinHandle<()>> {
-    let url = Uri::from_str("wss://api.valr.com/ws/account");
-
-    let request = create_ws_request(
-        url.unwrap(),
-        api_key,
-        api_secret,
-        "/ws/account",
-        "GET",
-        None,
-    );
-    let (ws_stream, _) = connect_async(request)
-        .await
-        .expect("Error connecting to Account WebSocket");
-    let (write, read) = ws_stream.split();
-
-    let account_handle = tokio::spawn(handle_ws_incoming_messages(
-        read,
+    let subscription = WsSubscription {
+        name: String::from("Account WS"),
+        subscription_type: "account",
+        path: "/ws/account",
+        subscribe_message: None,
+        heartbeat,
+    };
+    let account_handle = tokio::spawn(supervise_connection(
+        subscription,
+        api_key.to_string(),
+        api_secret.to_string(),
         strategy,
-        "account",
-        currency_pair.clone(),
+        strategy_context,
+        shutdown,
     ));
-    let ping_handle = create_ping_thread(write, Utc::now(), String::from("Account WS"));
 
-    vec![account_handle, ping_handle]
+    vec![account_handle]
 }
 
 async fn subscribe_to_trade_updates(
     api_key: &str,
     api_secret: &str,
-    currency_pair: &CurrencyPair,
-    strategy: String,
+    strategy: SharedStrategy,
+    strategy_context: StrategyContext,
+    heartbeat: HeartbeatConfig,
+    shutdown: watch::Receiver<bool>,
 ) -> Vec<JoinHandle<()>> {
-    let url = Uri::from_str("wss://api.valr.com/ws/trade");
+    let currency_pair = &strategy_context.currency_pair;
+    let mut book_pairs = vec![currency_pair.symbol.clone()];
+    book_pairs.extend(
+        FOLLOWED_PAIRS
+            .read()
+            .await
+            .iter()
+            .map(|followed| followed.symbol.clone()),
+    );
     let message = json!(
         {
         "type": "SUBSCRIBE",
         "subscriptions": [
             {
-                "event": "NEW_TRADE_BUCKET",
-                "pairs": [format!("{}", currency_pair.symbol)]
+                "event": "OB_L1_D10_SNAPSHOT",
+                "pairs": book_pairs
             },
             {
-                "event": "OB_L1_D10_SNAPSHOT",
+                "event": "NEW_TRADE",
                 "pairs": [format!("{}", currency_pair.symbol)]
             },
             {
-                "event": "NEW_TRADE"
+                "event": "NEW_TRADE_BUCKET",
+                "pairs": [format!("{}", currency_pair.symbol)]
             },
             {
                 "event": "ORDER_STATUS_UPDATE"
+            },
+            {
+                "event": "FULL_ORDERBOOK_UPDATE",
+                "pairs": [format!("{}", currency_pair.symbol)]
             }
             // {
-            //     "event": "FULL_ORDERBOOK_UPDATE",
-            //     "pairs": [format!("{}", currency_pair)]
-            // },
-            // {
             //     "event": "AGGREGATED_ORDERBOOK_UPDATE",
             //     "pairs": [format!("{}", currency_pair)]
             // },
@@ -156,68 +359,85 @@ async fn subscribe_to_trade_updates(
         ]
     });
 
-    let request = create_ws_request(url.unwrap(), api_key, api_secret, "/ws/trade", "GET", None);
-    let (ws_stream, _) = connect_async(request)
-        .await
-        .expect("Error connecting to Trade WebSocket");
-
-    let (mut write, read) = ws_stream.split();
-    let subscribe_handle = tokio::spawn(handle_ws_incoming_messages(
-        read,
+    let subscription = WsSubscription {
+        name: String::from("Trade   WS"),
+        subscription_type: "trade",
+        path: "/ws/trade",
+        subscribe_message: Some(message),
+        heartbeat,
+    };
+    let subscribe_handle = tokio::spawn(supervise_connection(
+        subscription,
+        api_key.to_string(),
+        api_secret.to_string(),
         strategy,
-        "trade",
-        currency_pair.clone(),
+        strategy_context,
+        shutdown,
     ));
 
-    write
-        .send(Message::from(message.to_string()))
-        .await
-        .expect("Failed to send message");
-
-    let ping_handle = create_ping_thread(write, Utc::now(), String::from("Trade   WS"));
-    vec![subscribe_handle, ping_handle]
+    vec![subscribe_handle]
 }
 
+/// Sends a PING every interval and stops when no PONG came back within the timeout, the
+/// connection is then treated as dead. Sends a close frame and stops once shutdown is signalled.
 fn create_ping_thread(
     mut write: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
-    mut current_time: DateTime<Utc>,
     name: String,
+    heartbeat: HeartbeatConfig,
+    last_pong: LastPong,
+    mut shutdown: watch::Receiver<bool>,
 ) -> JoinHandle<()> {
     tokio::spawn(async move {
+        let mut ping_interval = interval(heartbeat.ping_interval);
+        ping_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // the first tick completes immediately
+        ping_interval.tick().await;
         loop {
-            let now = Utc::now();
-            let time_delta = now.signed_duration_since(current_time);
-
-            if time_delta.num_seconds() >= 10 {
-                current_time = Utc::now();
-                let ping_message = json!({
-                    "type": "PING"
-                });
-
-                match write.send(Message::from(ping_message.to_string())).await {
-                    Ok(i) => {
-                        println!(
-                            "{}| {} Ping {}",
-                            current_time.to_rfc3339().blue(),
-                            name.bold().green(),
-                            "Sent".yellow()
-                        );
-                    }
-                    Err(e) => {
-                        warn!("Error sending ping: {}", e.to_string())
+            tokio::select! {
+                _ = ping_interval.tick() => {}
+                _ = shutdown.changed() => {
+                    if let Err(e) = write.send(Message::Close(None)).await {
+                        warn!("Error closing {}: {}", name, e);
                     }
+                    return;
+                }
+            }
+            if last_pong.is_overdue(Instant::now(), heartbeat.pong_timeout) {
+                warn!(
+                    "{} has not answered a ping in {}s, reconnecting",
+                    name,
+                    heartbeat.pong_timeout.as_secs()
+                );
+                return;
+            }
+
+            let ping_message = json!({
+                "type": "PING"
+            });
+            match write.send(Message::from(ping_message.to_string())).await {
+                Ok(_) => {
+                    println!(
+                        "{}| {} Ping {}",
+                        Utc::now().to_rfc3339().blue(),
+                        name.bold().green(),
+                        "Sent".yellow()
+                    );
+                }
+                Err(e) => {
+                    warn!("Error sending ping on {}: {}", name, e);
+                    return;
                 }
-            };
-            yield_now().await
+            }
         }
     })
 }
 
 async fn handle_ws_incoming_messages(
     mut read: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
-    strategy: String,
+    strategy: SharedStrategy,
     subscription_type: &str,
-    currency_pair: CurrencyPair,
+    strategy_context: StrategyContext,
+    last_pong: LastPong,
 ) {
     while let Some(message) = read.next().await {
         match message {
@@ -227,19 +447,29 @@ async fn handle_ws_incoming_messages(
                 match ws_message {
                     Ok(serialized) => match serialized {
                         WsMessage::BalanceUpdate(balance_update) => {
-                            handle_balance_update(*balance_update).await
+                            handle_balance_update(*balance_update.clone()).await;
+                            strategy
+                                .write()
+                                .await
+                                .on_balance(&balance_update, &strategy_context)
+                                .await
                         }
                         WsMessage::OpenOrdersUpdate(order_update) => {
-                            handle_order_update(order_update).await
+                            handle_order_update(order_update).await;
+                            let orders = ORDERS.read().await.open_orders().to_vec();
+                            strategy
+                                .write()
+                                .await
+                                .on_order_update(&orders, &strategy_context)
+                                .await
                         }
                         WsMessage::NewTradeBucket(trade_price_bucket_update) => {
-                            handle_trade_price_bucket_update(
-                                *trade_price_bucket_update,
-                                strategy.clone(),
-                                currency_pair.clone(),
-                                BALANCES.clone(),
+                            handle_trade_bucket(
+                                &trade_price_bucket_update,
+                                &strategy,
+                                &strategy_context,
                             )
-                            .await;
+                            .await
                         }
                         WsMessage::OrderbookLvOneDepthOneSnapshot(ob) => {
                             println!(
@@ -248,9 +478,96 @@ async fn handle_ws_incoming_messages(
                                 text.green()
                             )
                         }
+                        WsMessage::OrderbookLvOneDepthTenSnapshot(ob)
+                            if !ob.currency_pair.is_empty()
+                                && ob.currency_pair != strategy_context.currency_pair.symbol =>
+                        {
+                            let followed = FOLLOWED_PAIRS
+                                .read()
+                                .await
+                                .iter()
+                                .find(|followed| followed.symbol == ob.currency_pair)
+                                .cloned();
+                            if let Some(followed) = followed {
+                                let order_book = OrderBook::from_snapshot(ob.data);
+                                PAIR_BOOKS
+                                    .write()
+                                    .await
+                                    .insert(followed.symbol.clone(), order_book.clone());
+                                strategy
+                                    .write()
+                                    .await
+                                    .on_pair_book(&followed, &order_book, &strategy_context)
+                                    .await
+                            }
+                        }
                         WsMessage::OrderbookLvOneDepthTenSnapshot(ob) => {
-                            handle_orderbook_level_one_depth_ten_snapshot_update(*ob).await
+                            handle_orderbook_level_one_depth_ten_snapshot_update(ob.data).await;
+                            let mut strategy_writer = strategy.write().await;
+                            if strategy_context.executor.on_book_update().await {
+                                let balances = BALANCES.read().await.clone();
+                                for balance_update in &balances {
+                                    strategy_writer
+                                        .on_balance(balance_update, &strategy_context)
+                                        .await;
+                                }
+                                let orders = ORDERS.read().await.open_orders().to_vec();
+                                strategy_writer
+                                    .on_order_update(&orders, &strategy_context)
+                                    .await;
+                            }
+                            for fill in strategy_context.executor.take_fills().await {
+                                strategy_writer
+                                    .on_account_trade(&fill, &strategy_context)
+                                    .await;
+                            }
+                            strategy_writer.on_book(&strategy_context).await
+                        }
+                        WsMessage::FullOrderbookSnapshot(ob) => {
+                            handle_orderbook_snapshot(*ob).await
+                        }
+                        WsMessage::FullOrderbookUpdate(ob) => {
+                            handle_full_orderbook_update(*ob, &strategy_context.currency_pair.symbol)
+                                .await
+                        }
+                        WsMessage::OrderProcessed(order_processed) => {
+                            handle_order_processed(*order_processed).await;
+                            let orders = ORDERS.read().await.open_orders().to_vec();
+                            strategy
+                                .write()
+                                .await
+                                .on_order_update(&orders, &strategy_context)
+                                .await
                         }
+                        WsMessage::OrderStatusUpdate(order_status_update) => {
+                            handle_order_status_update(*order_status_update.clone()).await;
+                            let orders = ORDERS.read().await.open_orders().to_vec();
+                            let mut strategy_writer = strategy.write().await;
+                            strategy_writer
+                                .on_order_update(&orders, &strategy_context)
+                                .await;
+                            strategy_writer
+                                .on_order_status(&order_status_update, &strategy_context)
+                                .await
+                        }
+                        WsMessage::FailedCancelOrder(failed_cancel_order) => {
+                            handle_failed_cancel_order(*failed_cancel_order)
+                        }
+                        WsMessage::NewAccountTrade(account_trade) => {
+                            let is_new = handle_account_trade(
+                                *account_trade.clone(),
+                                &strategy_context.currency_pair,
+                            )
+                            .await;
+                            if is_new {
+                                strategy
+                                    .write()
+                                    .await
+                                    .on_account_trade(&account_trade, &strategy_context)
+                                    .await
+                            }
+                        }
+                        WsMessage::NewTrade(trade) => handle_trade(&trade),
                         WsMessage::Subscribed => {
                             println!(
                                 "{}| Subscribed {}",
@@ -270,6 +587,7 @@ async fn handle_ws_incoming_messages(
                             )
                         }
                         WsMessage::Pong => {
+                            last_pong.record(Instant::now());
                             println!(
                                 "{}| {} {} message Pong",
                                 current_time.to_rfc3339().blue(),
@@ -290,11 +608,18 @@ async fn handle_ws_incoming_messages(
                     }
                 }
             }
+            Ok(Message::Close(frame)) => {
+                warn!("{} WebSocket closed by VALR: {:?}", subscription_type, frame);
+                break;
+            }
             Ok(t) => error!(
                 "Unexpected Message during the WebSocket communication: {}",
                 t
             ), // Ignore non-Text messages
-            Err(e) => error!("Error during the Trade WebSocket communication: {}", e),
+            Err(e) => {
+                error!("Error during the {} WebSocket communication: {}", subscription_type, e);
+                break;
+            }
         }
     }
 }
@@ -302,167 +627,234 @@ async fn handle_ws_incoming_messages(
 async fn handle_orderbook_level_one_depth_ten_snapshot_update(
     orderbook_data: DepthOrderBookSnapshot,
 ) {
-    // Access parsed fields from the struct
-    // println!("Last Change: {}", orderbook_data.last_change);
-    // Iterate over Asks and Bids
-    let mut asks_writer = ASKS.write().await;
-    let mut bids_writer = BIDS.write().await;
-    asks_writer.clear();
-    bids_writer.clear();
-
-    for ask in orderbook_data.asks {
-        // println!("Ask Price: {}, quantity: {}", ask[0], ask[1]);
-        asks_writer.push(ask);
-    }
-
-    for bid in orderbook_data.bids {
-        // println!("Bid Price: {}, quantity: {}", bid[0], bid[1]);
-        bids_writer.push(bid);
-    }
-    drop(asks_writer);
-    drop(bids_writer);
+    let order_book = OrderBook::from_snapshot(orderbook_data);
+    let mut order_book_writer = ORDER_BOOK.write().await;
+    *order_book_writer = order_book;
+    drop(order_book_writer);
 }
 
-async fn handle_trade_price_bucket_update(
-    trade_price_bucket_update: TradePriceBucketUpdate,
-    strategy: String,
-    currency_pair: CurrencyPair,
-    balances: Arc<RwLock<Vec<BalanceUpdate>>>,
+/// Rolls the 1m trade bucket into the candles and calls the strategy with every timeframe it
+/// changed. Awaited in the read loop so the strategy sees the buckets in the order they arrive,
+/// repeated updates of the forming bucket are merged by the candle engine.
+async fn handle_trade_bucket(
+    trade_price_bucket_update: &TradePriceBucketUpdate,
+    strategy: &SharedStrategy,
+    strategy_context: &StrategyContext,
 ) {
-    if trade_price_bucket_update.bucket_period_in_seconds != 60 {
-        //300
+    let mut candles_writer = CANDLES.write().await;
+    let forming: Vec<(Timeframe, Option<String>)> = candles_writer
+        .timeframes()
+        .into_iter()
+        .map(|timeframe| {
+            let start = candles_writer.candles(timeframe).last();
+            (timeframe, start.map(|c| c.start_time.clone()))
+        })
+        .collect();
+    let updated: Vec<(Timeframe, Vec<MarkPriceBucket>)> = candles_writer
+        .apply_bucket(&trade_price_bucket_update.to_candle())
+        .into_iter()
+        .map(|timeframe| (timeframe, candles_writer.candles(timeframe).to_vec()))
+        .collect();
+    // a candle closed when the timeframe's forming candle is no longer the last one
+    let closed_indicator_values: Vec<(Timeframe, String, IndicatorValue)> = updated
+        .iter()
+        .filter(|(timeframe, candles)| {
+            let forming_start = forming
+                .iter()
+                .find(|(forming_timeframe, _)| forming_timeframe == timeframe)
+                .and_then(|(_, start)| start.as_ref());
+            let last_start = candles.last().map(|c| &c.start_time);
+            forming_start.is_some() && forming_start != last_start
+        })
+        .flat_map(|(timeframe, _)| {
+            candles_writer
+                .closed_indicator_values(*timeframe)
+                .into_iter()
+                .map(|(name, value)| (*timeframe, name.to_string(), value))
+        })
+        .collect();
+    let smc_events = candles_writer.take_smc_events();
+    drop(candles_writer);
+    if updated.is_empty() {
         return;
     }
-    let up_arrow: String = String::from_utf16(&[0x2B06]).unwrap();
-    let down_arrow: String = String::from_utf16(&[0x2B07]).unwrap();
-    let circle: String = String::from_utf16(&[0x23FA]).unwrap();
-
-    let c_lock = BUCKET_PRICES.clone();
-
-    tokio::spawn(async move {
-        // While main has an active read lock, we acquire one too.
-        let bpr = BUCKET_PRICES.read().await;
-        let position = {
-            bpr.iter()
-                .position(|b| b.start_time == trade_price_bucket_update.start_time)
-        };
-
-        let last_position = bpr.iter().last().unwrap();
-
-        let close_direction = if last_position.close < trade_price_bucket_update.close {
-            up_arrow.green()
-        } else if last_position.close > trade_price_bucket_update.close {
-            down_arrow.red()
-        } else {
-            circle.white()
-        };
-
-        let high_direction = if last_position.high < trade_price_bucket_update.high {
-            up_arrow.green()
-        } else if last_position.high > trade_price_bucket_update.high {
-            down_arrow.red()
-        } else {
-            circle.white()
-        };
-
-        let low_direction = if last_position.low < trade_price_bucket_update.low {
-            up_arrow.green()
-        } else if last_position.low > trade_price_bucket_update.low {
-            down_arrow.red()
-        } else {
-            circle.white()
-        };
-
+    for (timeframe, name, value) in closed_indicator_values {
         println!(
-            "{} for {} received. CLOSE: {}{} , HIGH: {}{} , LOW: {}{} , start_time: {}",
-            "Trade Price Bucket Update".on_bright_blue(),
-            trade_price_bucket_update.currency_pair_symbol.green(),
-            trade_price_bucket_update.close.to_string().yellow(),
-            close_direction,
-            trade_price_bucket_update.high.to_string().yellow(),
-            high_direction,
-            trade_price_bucket_update.low.to_string().yellow(),
-            low_direction,
-            trade_price_bucket_update.start_time.blue()
+            "{}| {} {} on {}: {}",
+            Utc::now().to_rfc3339().blue(),
+            "INDICATOR".bright_blue(),
+            name,
+            timeframe.to_string().green(),
+            value
         );
+    }
 
-        drop(bpr);
-        let mpb = create_mark_price_bucket(trade_price_bucket_update);
-        let mut bpw = BUCKET_PRICES.write().await;
-
-        match position {
-            None => {
-                bpw.push(mpb);
-                drop(bpw);
-            }
-            Some(_) => {
-                let _ = replace(&mut bpw[position.unwrap()], mpb);
-            }
+    let mut strategy_writer = strategy.write().await;
+    for (timeframe, candles) in &updated {
+        strategy_writer
+            .on_candle(*timeframe, candles, strategy_context)
+            .await;
+    }
+    for (timeframe, event) in smc_events {
+        println!(
+            "{}| {} {:?} {} on {} candle {}",
+            Utc::now().to_rfc3339().blue(),
+            "SMC".bright_blue(),
+            event.direction(),
+            event.name(),
+            timeframe.to_string().green(),
+            event.index()
+        );
+        if let Some((_, candles)) = updated.iter().find(|(updated, _)| *updated == timeframe) {
+            strategy_writer
+                .on_smc_event(timeframe, &event, candles, strategy_context)
+                .await;
         }
-    })
-    .await
-    .expect("The spawned task has panicked");
-    tokio::spawn(async move {
-        // While main has an active read lock, we acquire one too.
-        // let bpr = &c_lock.read().await;
-        let bpr = BUCKET_PRICES.read().await;
-
-        execute_strategy(
-            strategy.clone().as_str(),
-            bpr.to_vec(),
-            &ASKS,
-            &BIDS,
-            balances,
-            currency_pair,
-        )
-        .await;
-    });
+    }
 }
 
-fn create_mark_price_bucket(trade_price_bucket_update: TradePriceBucketUpdate) -> MarkPriceBucket {
-    MarkPriceBucket {
-        currency_pair_symbol: trade_price_bucket_update.currency_pair_symbol,
-        bucket_period_in_seconds: trade_price_bucket_update.bucket_period_in_seconds,
-        start_time: trade_price_bucket_update.start_time,
-        open: trade_price_bucket_update.open,
-        high: trade_price_bucket_update.high,
-        low: trade_price_bucket_update.low,
-        close: trade_price_bucket_update.close,
-    }
+async fn handle_order_update(orders: Vec<Order>) {
+    println!(
+        "{}| {} {} open orders",
+        Utc::now().to_rfc3339().blue(),
+        "OPEN ORDERS UPDATE".bright_blue(),
+        orders.len()
+    );
+    let mut orders_writer = ORDERS.write().await;
+    orders_writer.replace_open_orders(orders);
+    drop(orders_writer);
 }
 
-fn handle_aggregated_orderbook_update(aggregated_orderbook_update: AggregatedOrderBookUpdate) {
-    for ask in aggregated_orderbook_update.asks {
+async fn handle_order_processed(order_processed: OrderProcessed) {
+    if order_processed.success {
         println!(
-            "Ask Price: {}, quantity: {}, side: {}",
-            ask.price, ask.quantity, ask.side
+            "{}| Order {} {}",
+            Utc::now().to_rfc3339().blue(),
+            order_processed.order_id.bright_blue(),
+            "processed".green()
         );
-    }
-
-    for bid in aggregated_orderbook_update.bids {
-        println!(
-            "Bid Price: {}, quantity: {}, side: {}",
-            bid.price, bid.quantity, bid.side
+    } else {
+        error!(
+            "Order {} failed: {}",
+            order_processed.order_id,
+            order_processed.failure_reason.clone().unwrap_or_default()
         );
     }
+    let mut orders_writer = ORDERS.write().await;
+    orders_writer.apply_order_processed(&order_processed);
+    drop(orders_writer);
 }
 
-async fn handle_order_update(orders: Vec<Order>) {
-    println!();
-    println!("OPEN ORDERS UPDATE: {:?}", orders);
-    let mut orders_writer = ORDERS.write().await;
-    for ask in orders {
-        orders_writer.push(ask);
+async fn handle_order_status_update(order_status_update: OrderStatusUpdate) {
+    println!(
+        "{}| {} {} order {} {}, remaining: {} of {}",
+        Utc::now().to_rfc3339().blue(),
+        order_status_update.currency_pair.green(),
+        order_status_update.order_side.to_string().yellow(),
+        order_status_update.order_id.bright_blue(),
+        order_status_update.order_status_type.to_string().bold(),
+        order_status_update.remaining_quantity,
+        order_status_update.original_quantity
+    );
+    if let Some(reason) = order_status_update
+        .failed_reason
+        .as_ref()
+        .filter(|reason| !reason.is_empty())
+    {
+        error!("Order {} failed: {}", order_status_update.order_id, reason);
     }
+    let mut orders_writer = ORDERS.write().await;
+    orders_writer.apply_status_update(&order_status_update);
     drop(orders_writer);
-    println!();
+}
+
+fn handle_failed_cancel_order(failed_cancel_order: FailedCancelOrder) {
+    error!(
+        "Failed to cancel order {}: {}",
+        failed_cancel_order.order_id, failed_cancel_order.message
+    );
+}
+
+/// Returns false for a trade that was already recorded. The position is tracked for the market
+/// and the followed pairs, whose quote currency and book are known.
+async fn handle_account_trade(account_trade: AccountTrade, market: &CurrencyPair) -> bool {
+    println!(
+        "{}| {} {} FILL of order {} at price: {} and quantity: {}",
+        Utc::now().to_rfc3339().blue(),
+        account_trade.currency_pair.green(),
+        account_trade.side.to_string().yellow(),
+        account_trade.order_id.bright_blue(),
+        account_trade.price,
+        account_trade.quantity
+    );
+    let mut account_trades_writer = ACCOUNT_TRADES.write().await;
+    let is_new = record_account_trade(&mut account_trades_writer, account_trade.clone());
+    drop(account_trades_writer);
+    if !is_new {
+        return false;
+    }
+
+    let (currency_pair, order_book) = if account_trade.currency_pair == market.symbol {
+        (Some(market.clone()), Some(ORDER_BOOK.read().await.clone()))
+    } else {
+        let currency_pair = FOLLOWED_PAIRS
+            .read()
+            .await
+            .iter()
+            .find(|followed| followed.symbol == account_trade.currency_pair)
+            .cloned();
+        let order_book = PAIR_BOOKS
+            .read()
+            .await
+            .get(&account_trade.currency_pair)
+            .cloned();
+        (currency_pair, order_book)
+    };
+    let Some(currency_pair) = currency_pair else {
+        warn!(
+            "Not tracking the position of {}, the bot does not trade it",
+            account_trade.currency_pair
+        );
+        return true;
+    };
+
+    let mut positions_writer = POSITIONS.write().await;
+    let position =
+        positions_writer.apply_account_trade(&account_trade, &currency_pair.quote_currency);
+    let unrealized_pnl = order_book.and_then(|order_book| position.unrealized_pnl(&order_book));
+    println!(
+        "{}| {} position: {} at average: {}, realized P&L: {}, fees: {}, unrealized P&L: {}",
+        Utc::now().to_rfc3339().blue(),
+        position.currency_pair.green(),
+        position.quantity.to_string().yellow(),
+        position.average_entry_price,
+        position.realized_pnl.to_string().bright_blue(),
+        position.fees_paid,
+        unrealized_pnl
+            .map(|pnl| pnl.to_string())
+            .unwrap_or(String::from("-"))
+            .bright_blue()
+    );
+    drop(positions_writer);
+    true
+}
+
+fn handle_trade(trade: &Trade) {
+    println!(
+        "{}| {} trade {} at price: {} and quantity: {}",
+        trade.traded_at.blue(),
+        trade.currency_pair.green(),
+        trade.taker_side.to_string().yellow(),
+        trade.price,
+        trade.quantity
+    );
 }
 
 async fn handle_balance_update(balance_update: BalanceUpdate) {
     println!(
         "{}: {}",
         balance_update.currency.symbol.bright_green(),
-        balance_update.available.bright_blue()
+        balance_update.available.to_string().bright_blue()
     );
 
     let balances_reader = BALANCES.read().await;
@@ -485,58 +877,115 @@ async fn handle_balance_update(balance_update: BalanceUpdate) {
     drop(balances_writer);
 }
 
-fn handle_orderbook_snapshot(orderbook_data: OrderBookData) {
-    // Access parsed fields from the struct
-    println!("Last Change: {}", orderbook_data.last_change);
-    println!("Sequence Number: {}", orderbook_data.sn);
-    println!("Checksum: {}", orderbook_data.checksum);
-    // Iterate over Asks and Bids
-    for ask in orderbook_data.asks {
-        println!("Ask Price: {}", ask.price);
-        for order in ask.orders {
-            println!(
-                "  Order ID: {}, Quantity: {}",
-                order.order_id, order.quantity
-            );
-        }
+async fn handle_orderbook_snapshot(orderbook_data: OrderBookData) {
+    let sequence_number = orderbook_data.sn;
+    let mut full_order_book_writer = FULL_ORDER_BOOK.write().await;
+    match full_order_book_writer.apply_snapshot(orderbook_data) {
+        Ok(_) => println!(
+            "{}| Full order book synced at sequence {}",
+            Utc::now().to_rfc3339().blue(),
+            sequence_number.to_string().green()
+        ),
+        Err(e) => warn!("Full order book snapshot rejected: {}", e),
     }
+    drop(full_order_book_writer);
+}
 
-    for bid in orderbook_data.bids {
-        println!("Bid Price: {}", bid.price);
-        for order in bid.orders {
-            println!(
-                "  Order ID: {}, Quantity: {}",
-                order.order_id, order.quantity
-            );
-        }
+async fn handle_full_orderbook_update(orderbook_data: OrderBookData, currency_pair: &str) {
+    let sequence_number = orderbook_data.sn;
+    let mut full_order_book_writer = FULL_ORDER_BOOK.write().await;
+    let was_synced = full_order_book_writer.is_synced();
+    if let Err(e) = full_order_book_writer.apply_update(orderbook_data) {
+        warn!("Full order book out of sync: {}", e);
+    }
+    // a resynced book is only trusted once an update's checksum matched it
+    if !was_synced && full_order_book_writer.is_synced() {
+        println!(
+            "{}| Full order book back in sync at sequence {}",
+            Utc::now().to_rfc3339().blue(),
+            sequence_number.to_string().green()
+        );
     }
+    let should_resync = full_order_book_writer.should_resync();
+    drop(full_order_book_writer);
+    if !should_resync {
+        return;
+    }
+    println!(
+        "{}| Resyncing full order book for {}",
+        Utc::now().to_rfc3339().blue(),
+        currency_pair.green()
+    );
+    // the book is fetched off the read loop and swapped in with the updates received meanwhile
+    let currency_pair = currency_pair.to_string();
+    tokio::spawn(async move {
+        match fetch_full_order_book(&currency_pair).await {
+            Ok(Ok(full_order_book)) => {
+                if let Err(e) = FULL_ORDER_BOOK.write().await.resync_from(full_order_book) {
+                    warn!("Full order book out of sync after resync: {}", e);
+                }
+            }
+            Ok(Err(e)) => warn!("Full order book resync rejected: {}", e),
+            Err(e) => error!("Error resyncing full order book: {}", e),
+        }
+    });
 }
 
-async fn get_historical_sixty_second_mark_price_buckets_for_pair(
+/// Loads the last HISTORY_CANDLES trade buckets of every timeframe the strategy trades on or has
+/// indicators on, so the history is built from the same trades as the live candles
+async fn seed_candles(
     currency_pair: &String,
-    start_time: String,
-    end_time: String,
+    timeframes: &[Timeframe],
 ) -> Result<(), reqwest::Error> {
-    let request_url = format!("https://api.valr.com/v1/public/{}/markprice/buckets?startTime={}&endTime={}&periodSeconds={}", currency_pair, start_time, end_time, FIVE_MINUTE_BUCKET_SECONDS);
-    let client = reqwest::Client::new();
-    let response = client.get(request_url).send().await?;
-    let mark_price_buckets: Vec<MarkPriceBucket> = response.json().await?;
-    let mut bpw = BUCKET_PRICES.write().await;
-    for mark_price_bucket in mark_price_buckets {
-        bpw.push(mark_price_bucket)
+    let end_time = Utc::now();
+    let mut candles_writer = CANDLES.write().await;
+    for timeframe in timeframes {
+        let start_time = end_time - Duration::seconds(timeframe.seconds() * HISTORY_CANDLES);
+        let trade_buckets = fetch_trade_buckets(
+            currency_pair,
+            &start_time.to_rfc3339_opts(SecondsFormat::Secs, true),
+            &end_time.to_rfc3339_opts(SecondsFormat::Secs, true),
+            &timeframe.history_period_seconds().to_string(),
+        )
+        .await?;
+        candles_writer.seed(*timeframe, trade_buckets);
+        println!(
+            "{}| {} {} candles loaded",
+            Utc::now().to_rfc3339().blue(),
+            candles_writer.candles(*timeframe).len(),
+            timeframe.to_string().green()
+        );
     }
-    drop(bpw);
-    tokio::spawn(async move {
-        let bpr = BUCKET_PRICES.read().await;
-        println!("{:?}", bpr);
-        drop(bpr);
-    })
-    .await
-    .expect("The spawned task to read Bucket prices has panicked");
     Ok(())
 }
 
-async fn get_currency_pair(currency_pair: String) -> CurrencyPair {
+/// OHLC and volume of the pair's trades, in the same shape as the mark price buckets
+async fn fetch_trade_buckets(
+    currency_pair: &String,
+    start_time: &str,
+    end_time: &str,
+    period_seconds: &str,
+) -> Result<Vec<MarkPriceBucket>, reqwest::Error> {
+    let request_url = format!("https://api.valr.com/v1/public/{}/buckets?startTime={}&endTime={}&periodSeconds={}", currency_pair, start_time, end_time, period_seconds);
+    let client = reqwest::Client::new();
+    let response = client.get(request_url).send().await?;
+    response.json().await
+}
+
+async fn fetch_mark_price_buckets(
+    currency_pair: &String,
+    start_time: &str,
+    end_time: &str,
+    period_seconds: &str,
+) -> Result<Vec<MarkPriceBucket>, reqwest::Error> {
+    let request_url = format!("https://api.valr.com/v1/public/{}/markprice/buckets?startTime={}&endTime={}&periodSeconds={}", currency_pair, start_time, end_time, period_seconds);
+    let client = reqwest::Client::new();
+    let response = client.get(request_url).send().await?;
+    response.json().await
+}
+
+/// Looks every symbol up in a single download of the pairs, in the same order
+async fn get_currency_pairs(symbols: &[String]) -> Vec<CurrencyPair> {
     let request_url = String::from("https://api.valr.com/v1/public/pairs");
     let client = reqwest::Client::new();
     let response = client.get(request_url).send().await;
@@ -545,58 +994,26 @@ async fn get_currency_pair(currency_pair: String) -> CurrencyPair {
         Ok(_response) => {
             let pairs: Result<Vec<CurrencyPair>, Error> = _response.json().await;
             match pairs {
-                Ok(_pairs) => {
-                    let pair = _pairs.iter().clone().find(|p| p.symbol.eq(&currency_pair));
-                    match pair {
-                        None => {
-                            panic!("Currency pair: {} cannot be found", currency_pair)
+                Ok(_pairs) => symbols
+                    .iter()
+                    .map(|currency_pair| {
+                        let pair = _pairs.iter().find(|p| p.symbol.eq(currency_pair));
+                        match pair {
+                            None => {
+                                panic!("Currency pair: {} cannot be found", currency_pair)
+                            }
+                            Some(_pair) => _pair.clone(),
                         }
-                        Some(_pair) => _pair.clone() ,
-                    }
-                }
+                    })
+                    .collect(),
                 Err(_e) => {
-                    panic!("Currency pair: {} cannot be found: {}", currency_pair, _e)
+                    panic!("Currency pairs: {} cannot be found: {}", symbols.join(", "), _e)
                 }
             }
         }
         Err(_e) => {
-            panic!("Currency pair: {} cannot be found: {}", currency_pair, _e)
+            panic!("Currency pairs: {} cannot be found: {}", symbols.join(", "), _e)
         }
     }
 }
 
-//Not necessary because the first subscription always returns all the open orders
-//but leaving as an example
-async fn get_open_orders_for_pair(
-    api_key: &str,
-    api_secret: &str,
-    currency_pair: &String,
-) -> Result<(), reqwest::Error> {
-    let request_url = String::from("https://api.valr.com/v1/orders/open");
-    let response = create_http_request(
-        request_url,
-        api_key,
-        api_secret,
-        "/v1/orders/open",
-        "GET",
-        None,
-    )
-    .send()
-    .await;
-
-    let orders: Vec<Order> = response.unwrap().json().await?;
-    let mut orders_writer = ORDERS.write().await;
-    orders
-        .iter()
-        .filter(|o| o.currency_pair.eq(currency_pair))
-        .for_each(|o| {
-            println!("{:?}", o);
-            orders_writer.push(o.clone());
-        });
-    // for order in orders {
-    //     println!("{:?}", order);
-    //     orders_writer.push(order);
-    // }
-    drop(orders_writer);
-    Ok(())
-}
@@ -0,0 +1,396 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use chrono::DateTime;
+use log::warn;
+use rust_decimal::Decimal;
+
+use crate::rusty_bot_models::{
+    DepthOrderBookSnapshot, FullOrderBookEntry, FullOrderBookResponse, OrderBookData, OrderLevel,
+    OrderLevelOrder, OrderSide,
+};
+
+/// Number of levels per side VALR includes in the FULL_ORDERBOOK_UPDATE checksum
+const CHECKSUM_DEPTH: usize = 25;
+const RESYNC_INTERVAL: Duration = Duration::from_secs(5);
+/// Updates kept while the book is out of sync, to replay on top of a REST resync
+const MAX_PENDING_UPDATES: usize = 1000;
+
+#[derive(Debug, PartialEq)]
+pub enum OrderBookError {
+    NotSynced,
+    SequenceGap { expected: u64, received: u64 },
+    ChecksumMismatch { expected: u64, calculated: u64 },
+    InvalidPrice(String),
+}
+
+impl Display for OrderBookError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderBookError::NotSynced => write!(f, "order book has no snapshot yet"),
+            OrderBookError::SequenceGap { expected, received } => write!(
+                f,
+                "sequence gap, expected {} but received {}",
+                expected, received
+            ),
+            OrderBookError::ChecksumMismatch {
+                expected,
+                calculated,
+            } => write!(
+                f,
+                "checksum mismatch, expected {} but calculated {}",
+                expected, calculated
+            ),
+            OrderBookError::InvalidPrice(price) => write!(f, "invalid price {}", price),
+        }
+    }
+}
+
+/// Local L3 book built from FULL_ORDERBOOK_SNAPSHOT and FULL_ORDERBOOK_UPDATE messages.
+/// Every level keeps the individual orders resting at that price in queue order.
+#[derive(Debug, Default, Clone)]
+pub struct FullOrderBook {
+    pub asks: BTreeMap<Decimal, OrderLevel>,
+    pub bids: BTreeMap<Decimal, OrderLevel>,
+    pub sequence_number: Option<u64>,
+    pub last_change: u64,
+    /// False for a book rebuilt over REST until an update's checksum has matched it
+    verified: bool,
+    /// Updates that arrived while out of sync, oldest first
+    pending: Vec<OrderBookData>,
+    last_resync_attempt: Option<Instant>,
+}
+
+impl FullOrderBook {
+    /// Follows the sequence and matches the last checksum
+    pub fn is_synced(&self) -> bool {
+        self.sequence_number.is_some() && self.verified
+    }
+
+    /// Limits REST resyncs while the book stays out of sequence
+    pub fn should_resync(&mut self) -> bool {
+        if self.sequence_number.is_some() {
+            return false;
+        }
+        let due = self
+            .last_resync_attempt
+            .map(|attempt| attempt.elapsed() >= RESYNC_INTERVAL)
+            .unwrap_or(true);
+        if due {
+            self.last_resync_attempt = Some(Instant::now());
+        }
+        due
+    }
+
+    pub fn apply_snapshot(&mut self, snapshot: OrderBookData) -> Result<(), OrderBookError> {
+        self.asks.clear();
+        self.bids.clear();
+        self.sequence_number = None;
+        apply_levels(&mut self.asks, snapshot.asks)?;
+        apply_levels(&mut self.bids, snapshot.bids)?;
+        self.verify_checksum(snapshot.checksum)?;
+        self.sequence_number = Some(snapshot.sn);
+        self.last_change = snapshot.last_change;
+        self.verified = true;
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Each level in an update replaces the orders at that price, an empty level removes it.
+    /// Updates older than the book are ignored. Updates that cannot be applied, after a gap or
+    /// checksum failure, are kept to replay on the next resync.
+    pub fn apply_update(&mut self, update: OrderBookData) -> Result<(), OrderBookError> {
+        let Some(sequence_number) = self.sequence_number else {
+            self.keep_pending(update);
+            return Err(OrderBookError::NotSynced);
+        };
+        if update.sn <= sequence_number {
+            return Ok(());
+        }
+        if update.sn != sequence_number + 1 {
+            let received = update.sn;
+            self.sequence_number = None;
+            self.keep_pending(update);
+            return Err(OrderBookError::SequenceGap {
+                expected: sequence_number + 1,
+                received,
+            });
+        }
+        let result = apply_levels(&mut self.asks, update.asks.clone())
+            .and_then(|_| apply_levels(&mut self.bids, update.bids.clone()))
+            .and_then(|_| self.verify_checksum(update.checksum));
+        match result {
+            Ok(_) => {
+                self.sequence_number = Some(update.sn);
+                self.last_change = update.last_change;
+                self.verified = true;
+                Ok(())
+            }
+            Err(e) => {
+                self.sequence_number = None;
+                self.keep_pending(update);
+                Err(e)
+            }
+        }
+    }
+
+    fn keep_pending(&mut self, update: OrderBookData) {
+        if self.pending.len() == MAX_PENDING_UPDATES {
+            self.pending.remove(0);
+        }
+        self.pending.push(update);
+    }
+
+    /// Swaps in a book fetched over REST and replays the updates that arrived while it was
+    /// fetched. Ignored when a snapshot brought the book past it in the meantime. The book
+    /// counts as synced again once an update's checksum matches.
+    pub fn resync_from(&mut self, rebuilt: FullOrderBook) -> Result<(), OrderBookError> {
+        if let (Some(current), Some(fetched)) = (self.sequence_number, rebuilt.sequence_number) {
+            if current >= fetched {
+                return Ok(());
+            }
+        }
+        let pending = std::mem::take(&mut self.pending);
+        self.asks = rebuilt.asks;
+        self.bids = rebuilt.bids;
+        self.sequence_number = rebuilt.sequence_number;
+        self.last_change = rebuilt.last_change;
+        self.verified = rebuilt.verified;
+        let mut result = Ok(());
+        for update in pending {
+            if let Err(e) = self.apply_update(update) {
+                result = Err(e);
+            }
+        }
+        result
+    }
+
+    /// Book as returned by the full order book endpoint, verified when the response carries
+    /// a checksum that matches
+    pub fn from_response(response: FullOrderBookResponse) -> Result<Self, OrderBookError> {
+        let mut full_order_book = FullOrderBook::default();
+        for (side, entries) in [
+            (&mut full_order_book.asks, response.asks),
+            (&mut full_order_book.bids, response.bids),
+        ] {
+            let mut entries: Vec<FullOrderBookEntry> = entries;
+            entries.sort_by_key(|e| e.position_at_price);
+            for entry in entries {
+                let Ok(price) = Decimal::from_str(&entry.price) else {
+                    continue;
+                };
+                side.entry(price)
+                    .or_insert_with(|| OrderLevel {
+                        price: entry.price.clone(),
+                        orders: vec![],
+                    })
+                    .orders
+                    .push(OrderLevelOrder {
+                        order_id: entry.id,
+                        quantity: entry.quantity,
+                    });
+            }
+        }
+        if let Some(checksum) = response.checksum {
+            full_order_book.verify_checksum(checksum)?;
+            full_order_book.verified = true;
+        }
+        full_order_book.last_change = response
+            .last_change
+            .and_then(|last_change| DateTime::parse_from_rfc3339(&last_change).ok())
+            .map(|last_change| last_change.timestamp_millis() as u64)
+            .unwrap_or_default();
+        full_order_book.sequence_number = Some(response.sn);
+        Ok(full_order_book)
+    }
+
+    /// Aggregated price and quantity per level, best ask first
+    pub fn ask_levels(&self) -> Vec<(Decimal, Decimal)> {
+        self.asks
+            .iter()
+            .map(|(price, level)| (*price, level_quantity(level)))
+            .collect()
+    }
+
+    /// Aggregated price and quantity per level, best bid first
+    pub fn bid_levels(&self) -> Vec<(Decimal, Decimal)> {
+        self.bids
+            .iter()
+            .rev()
+            .map(|(price, level)| (*price, level_quantity(level)))
+            .collect()
+    }
+
+    /// CRC32 of the top bids and asks alternating as `bid_price:bid_quantity:ask_price:ask_quantity:...`
+    pub fn checksum(&self) -> u64 {
+        let asks = self.ask_levels();
+        let bids = self.bid_levels();
+        let mut parts = vec![];
+        for i in 0..CHECKSUM_DEPTH {
+            if let Some((price, quantity)) = bids.get(i) {
+                parts.push(price.normalize().to_string());
+                parts.push(quantity.normalize().to_string());
+            }
+            if let Some((price, quantity)) = asks.get(i) {
+                parts.push(price.normalize().to_string());
+                parts.push(quantity.normalize().to_string());
+            }
+        }
+        crc32fast::hash(parts.join(":").as_bytes()) as u64
+    }
+
+    fn verify_checksum(&self, expected: u64) -> Result<(), OrderBookError> {
+        let calculated = self.checksum();
+        if calculated == expected {
+            Ok(())
+        } else {
+            Err(OrderBookError::ChecksumMismatch {
+                expected,
+                calculated,
+            })
+        }
+    }
+}
+
+fn apply_levels(
+    side: &mut BTreeMap<Decimal, OrderLevel>,
+    levels: Vec<OrderLevel>,
+) -> Result<(), OrderBookError> {
+    for level in levels {
+        let price = Decimal::from_str(&level.price)
+            .map_err(|_| OrderBookError::InvalidPrice(level.price.clone()))?;
+        if level.orders.is_empty() {
+            side.remove(&price);
+        } else {
+            side.insert(price, level);
+        }
+    }
+    Ok(())
+}
+
+fn level_quantity(level: &OrderLevel) -> Decimal {
+    level
+        .orders
+        .iter()
+        .filter_map(|o| Decimal::from_str(&o.quantity).ok())
+        .sum()
+}
+
+/// Fetches the full book over REST so a book that fell out of sequence can be rebuilt
+pub async fn fetch_full_order_book(
+    currency_pair: &str,
+) -> Result<Result<FullOrderBook, OrderBookError>, reqwest::Error> {
+    let request_url = format!(
+        "https://api.valr.com/v1/public/{}/orderbook/full",
+        currency_pair
+    );
+    let response = reqwest::Client::new()
+        .get(request_url)
+        .send()
+        .await?
+        .error_for_status()?;
+    let full_order_book_response: FullOrderBookResponse = response.json().await?;
+    Ok(FullOrderBook::from_response(full_order_book_response))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceLevel {
+    pub price: Decimal,
+    pub quantity: Decimal,
+}
+
+/// Aggregated book with asks sorted from the lowest and bids from the highest price
+#[derive(Debug, Default, Clone)]
+pub struct OrderBook {
+    pub asks: Vec<PriceLevel>,
+    pub bids: Vec<PriceLevel>,
+}
+
+impl OrderBook {
+    /// Levels that are not a valid `[price, quantity]` pair are skipped and logged
+    pub fn from_snapshot(snapshot: DepthOrderBookSnapshot) -> Self {
+        let mut asks = parse_levels(snapshot.asks);
+        let mut bids = parse_levels(snapshot.bids);
+        asks.sort_by_key(|level| level.price);
+        bids.sort_by_key(|level| std::cmp::Reverse(level.price));
+        OrderBook { asks, bids }
+    }
+
+    pub fn best_ask(&self) -> Option<&PriceLevel> {
+        self.asks.first()
+    }
+
+    pub fn best_bid(&self) -> Option<&PriceLevel> {
+        self.bids.first()
+    }
+
+    pub fn mid_price(&self) -> Option<Decimal> {
+        Some((self.best_ask()?.price + self.best_bid()?.price) / Decimal::TWO)
+    }
+
+    pub fn spread(&self) -> Option<Decimal> {
+        Some(self.best_ask()?.price - self.best_bid()?.price)
+    }
+
+    /// Levels a taker order on `side` trades against
+    pub fn opposite_levels(&self, side: OrderSide) -> &[PriceLevel] {
+        match side {
+            OrderSide::Buy => &self.asks,
+            OrderSide::Sell => &self.bids,
+        }
+    }
+
+    /// Quantity resting on the bid or ask side at exactly this price
+    pub fn depth_at_price(&self, side: OrderSide, price: Decimal) -> Decimal {
+        let levels = match side {
+            OrderSide::Buy => &self.bids,
+            OrderSide::Sell => &self.asks,
+        };
+        levels
+            .iter()
+            .find(|level| level.price == price)
+            .map(|level| level.quantity)
+            .unwrap_or(Decimal::ZERO)
+    }
+
+    /// Average price a taker order for `quantity` would fill at, None when the book is too thin
+    pub fn volume_weighted_fill_price(
+        &self,
+        side: OrderSide,
+        quantity: Decimal,
+    ) -> Option<Decimal> {
+        if quantity <= Decimal::ZERO {
+            return None;
+        }
+        let mut remaining = quantity;
+        let mut notional = Decimal::ZERO;
+        for level in self.opposite_levels(side) {
+            let taken = level.quantity.min(remaining);
+            notional += taken * level.price;
+            remaining -= taken;
+            if remaining.is_zero() {
+                return Some(notional / quantity);
+            }
+        }
+        None
+    }
+}
+
+fn parse_levels(levels: Vec<Vec<String>>) -> Vec<PriceLevel> {
+    levels
+        .into_iter()
+        .filter_map(|level| {
+            let price = level.first().and_then(|p| Decimal::from_str(p).ok());
+            let quantity = level.get(1).and_then(|q| Decimal::from_str(q).ok());
+            match (price, quantity) {
+                (Some(price), Some(quantity)) => Some(PriceLevel { price, quantity }),
+                _ => {
+                    warn!("Skipping malformed order book level: {:?}", level);
+                    None
+                }
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,146 @@
+use log::warn;
+use rust_decimal::Decimal;
+
+use crate::rusty_bot_models::{
+    AccountTrade, Order, OrderProcessed, OrderSide, OrderStatus, OrderStatusUpdate, TimeInForce,
+};
+
+/// The account's open orders. OPEN_ORDERS_UPDATE replaces the whole set, ORDER_STATUS_UPDATE
+/// and ORDER_PROCESSED move single orders through their lifecycle in between.
+#[derive(Debug, Default, Clone)]
+pub struct OrderManager {
+    orders: Vec<Order>,
+}
+
+impl OrderManager {
+    pub fn open_orders(&self) -> &[Order] {
+        &self.orders
+    }
+
+    pub fn get(&self, order_id: &str) -> Option<&Order> {
+        self.orders.iter().find(|o| o.order_id == order_id)
+    }
+
+    pub fn has_open_orders(&self) -> bool {
+        !self.orders.is_empty()
+    }
+
+    pub fn open_orders_for(&self, currency_pair: &str, side: OrderSide) -> Vec<&Order> {
+        self.orders
+            .iter()
+            .filter(|o| o.currency_pair == currency_pair && o.side == side)
+            .collect()
+    }
+
+    /// Base quantity still waiting to fill on one side of the pair
+    pub fn open_quantity(&self, currency_pair: &str, side: OrderSide) -> Decimal {
+        self.open_orders_for(currency_pair, side)
+            .iter()
+            .map(|o| o.remaining_quantity.unwrap_or(o.original_quantity))
+            .sum()
+    }
+
+    /// Tracks an order we just sent until VALR reports on it
+    pub fn record_placed(&mut self, order: Order) {
+        if self.get(&order.order_id).is_none() {
+            self.orders.push(order);
+        }
+    }
+
+    /// OPEN_ORDERS_UPDATE lists every open order, anything missing from it has closed
+    pub fn replace_open_orders(&mut self, orders: Vec<Order>) {
+        self.orders = orders
+            .into_iter()
+            .filter(|o| !o.status.is_closed())
+            .collect();
+    }
+
+    /// Returns false when the update was ignored because the order already moved past it.
+    /// A modified order is kept as placed or partially filled from its remaining quantity.
+    pub fn apply_status_update(&mut self, update: &OrderStatusUpdate) -> bool {
+        let status = match update.order_status_type {
+            OrderStatus::Modified if update.remaining_quantity < update.original_quantity => {
+                OrderStatus::PartiallyFilled
+            }
+            OrderStatus::Modified => OrderStatus::Placed,
+            status => status,
+        };
+        let position = self
+            .orders
+            .iter()
+            .position(|o| o.order_id == update.order_id);
+        if let Some(order) = position.map(|i| &self.orders[i]) {
+            if !order.status.can_transition_to(status) {
+                warn!(
+                    "Ignoring {} update for order {} that is already {}",
+                    status, order.order_id, order.status
+                );
+                return false;
+            }
+        }
+        if status.is_closed() {
+            if let Some(i) = position {
+                self.orders.remove(i);
+            }
+            return true;
+        }
+
+        let filled_percentage = if update.original_quantity > Decimal::ZERO {
+            ((update.original_quantity - update.remaining_quantity) / update.original_quantity
+                * Decimal::ONE_HUNDRED)
+                .round_dp(2)
+        } else {
+            Decimal::ZERO
+        };
+        match position {
+            Some(i) => {
+                let order = &mut self.orders[i];
+                order.status = status;
+                order.remaining_quantity = Some(update.remaining_quantity);
+                order.original_quantity = update.original_quantity;
+                order.filled_percentage = filled_percentage;
+                order.updated_at = update.order_updated_at.clone();
+                if let Some(price) = update.original_price {
+                    order.price = price;
+                }
+            }
+            None => self.orders.push(Order {
+                order_id: update.order_id.clone(),
+                side: update.order_side,
+                remaining_quantity: Some(update.remaining_quantity),
+                price: update.original_price.unwrap_or(Decimal::ZERO),
+                currency_pair: update.currency_pair.clone(),
+                created_at: update.order_created_at.clone(),
+                original_quantity: update.original_quantity,
+                filled_percentage,
+                updated_at: update.order_updated_at.clone(),
+                status,
+                r#type: update.order_type,
+                time_in_force: TimeInForce::GoodTillCancelled,
+                allow_margin: false,
+            }),
+        }
+        true
+    }
+
+    /// Drops the pair's orders once they were all cancelled
+    pub fn forget_pair(&mut self, currency_pair: &str) {
+        self.orders.retain(|o| o.currency_pair != currency_pair);
+    }
+
+    /// Orders VALR failed to process never rest on the book
+    pub fn apply_order_processed(&mut self, processed: &OrderProcessed) {
+        if !processed.success {
+            self.orders.retain(|o| o.order_id != processed.order_id);
+        }
+    }
+}
+
+/// Returns false when the trade was already recorded
+pub fn record_account_trade(trades: &mut Vec<AccountTrade>, trade: AccountTrade) -> bool {
+    if trades.iter().any(|t| t.id == trade.id) {
+        return false;
+    }
+    trades.push(trade);
+    true
+}
@@ -0,0 +1,471 @@
+use std::fmt::{Display, Formatter};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use colored::Colorize;
+use log::error;
+use rust_decimal::Decimal;
+
+use crate::rusty_bot_models::{
+    AccountTrade, CancelOrderRequest, CurrencyPair, LimitOrderRequest, MarketOrderRequest,
+    ModifyOrderRequest, Order, OrderResponse, OrderSide, OrderStatus, OrderType, TimeInForce,
+};
+use crate::sizing::{size_limit_order, size_market_order, SizingError};
+use crate::strategies::break_of_structure::helper::create_http_request;
+use crate::ORDERS;
+
+const VALR_API_URL: &str = "https://api.valr.com";
+const LIMIT_ORDER_PATH: &str = "/v1/orders/limit";
+const MARKET_ORDER_PATH: &str = "/v1/orders/market";
+const ORDERS_PATH: &str = "/v1/orders";
+const CANCEL_ORDER_PATH: &str = "/v1/orders/order";
+const MODIFY_ORDER_PATH: &str = "/v1/orders/modify";
+
+#[derive(Debug)]
+pub enum OrderError {
+    Http(reqwest::Error),
+    Rejected(String),
+}
+
+impl Display for OrderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderError::Http(e) => write!(f, "{}", e),
+            OrderError::Rejected(reason) => write!(f, "Order rejected: {}", reason),
+        }
+    }
+}
+
+impl From<reqwest::Error> for OrderError {
+    fn from(e: reqwest::Error) -> Self {
+        OrderError::Http(e)
+    }
+}
+
+impl From<SizingError> for OrderError {
+    fn from(e: SizingError) -> Self {
+        OrderError::Rejected(e.to_string())
+    }
+}
+
+/// Where strategy orders end up: VALR itself or one of the simulated exchanges
+#[async_trait]
+pub trait OrderExecutor: Send + Sync {
+    async fn place_limit_order(
+        &self,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<String, OrderError>;
+
+    /// A limit order that is rejected instead of taking liquidity when it would cross the
+    /// book. Executors without a book to check against place a plain limit order.
+    async fn place_post_only_order(
+        &self,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<String, OrderError> {
+        self.place_limit_order(side, price, quantity, currency_pair)
+            .await
+    }
+
+    /// A limit order that fills in full right away or not at all, VALR fails or cancels it
+    /// otherwise. Executors without a book to fill it against reject it.
+    async fn place_fill_or_kill_order(
+        &self,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<String, OrderError> {
+        Err(OrderError::Rejected(format!(
+            "fill-or-kill {} order on {} is not supported",
+            side, currency_pair.symbol
+        )))
+    }
+
+    async fn place_market_order(
+        &self,
+        side: OrderSide,
+        amount: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<String, OrderError>;
+
+    async fn cancel_order(
+        &self,
+        order_id: &str,
+        currency_pair: &CurrencyPair,
+    ) -> Result<(), OrderError>;
+
+    async fn cancel_all_orders(&self, currency_pair: &CurrencyPair) -> Result<(), OrderError>;
+
+    /// Moves a resting limit order to a new price and total quantity, keeping its place in
+    /// the lifecycle. The side is needed to round the new price to tick.
+    async fn modify_order(
+        &self,
+        order_id: &str,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<(), OrderError>;
+
+    /// Called after every order book snapshot, returns true when resting orders were filled
+    async fn on_book_update(&self) -> bool {
+        false
+    }
+
+    /// Simulated fills since the last call, VALR reports real ones as NEW_ACCOUNT_TRADE
+    async fn take_fills(&self) -> Vec<AccountTrade> {
+        vec![]
+    }
+}
+
+/// API key and secret that sign every order request, loaded once at startup
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub api_key: String,
+    pub api_secret: String,
+}
+
+pub struct LiveOrderExecutor {
+    credentials: Credentials,
+}
+
+impl LiveOrderExecutor {
+    pub fn new(credentials: Credentials) -> Self {
+        LiveOrderExecutor { credentials }
+    }
+}
+
+#[async_trait]
+impl OrderExecutor for LiveOrderExecutor {
+    async fn place_limit_order(
+        &self,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<String, OrderError> {
+        let sized = size_limit_order(side, price, quantity, currency_pair)?;
+        Ok(place_limit_order(
+            &self.credentials,
+            side,
+            sized.price,
+            sized.quantity,
+            currency_pair,
+            false,
+            TimeInForce::GoodTillCancelled,
+        )
+        .await?)
+    }
+
+    async fn place_post_only_order(
+        &self,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<String, OrderError> {
+        let sized = size_limit_order(side, price, quantity, currency_pair)?;
+        Ok(place_limit_order(
+            &self.credentials,
+            side,
+            sized.price,
+            sized.quantity,
+            currency_pair,
+            true,
+            TimeInForce::GoodTillCancelled,
+        )
+        .await?)
+    }
+
+    async fn place_fill_or_kill_order(
+        &self,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<String, OrderError> {
+        let sized = size_limit_order(side, price, quantity, currency_pair)?;
+        Ok(place_limit_order(
+            &self.credentials,
+            side,
+            sized.price,
+            sized.quantity,
+            currency_pair,
+            false,
+            TimeInForce::FillOrKill,
+        )
+        .await?)
+    }
+
+    async fn place_market_order(
+        &self,
+        side: OrderSide,
+        amount: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<String, OrderError> {
+        let amount = size_market_order(side, amount, currency_pair)?;
+        Ok(place_market_order(&self.credentials, side, amount, currency_pair).await?)
+    }
+
+    async fn cancel_order(
+        &self,
+        order_id: &str,
+        currency_pair: &CurrencyPair,
+    ) -> Result<(), OrderError> {
+        Ok(cancel_order(&self.credentials, order_id, currency_pair).await?)
+    }
+
+    async fn cancel_all_orders(&self, currency_pair: &CurrencyPair) -> Result<(), OrderError> {
+        Ok(cancel_all_orders(&self.credentials, currency_pair).await?)
+    }
+
+    async fn modify_order(
+        &self,
+        order_id: &str,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<(), OrderError> {
+        let sized = size_limit_order(side, price, quantity, currency_pair)?;
+        Ok(modify_order(
+            &self.credentials,
+            order_id,
+            sized.price,
+            sized.quantity,
+            currency_pair,
+        )
+        .await?)
+    }
+}
+
+/// Post-only orders that would cross the book are failed by VALR, which reports it in an
+/// ORDER_STATUS_UPDATE
+pub async fn place_limit_order(
+    credentials: &Credentials,
+    side: OrderSide,
+    price: Decimal,
+    quantity: Decimal,
+    currency_pair: &CurrencyPair,
+    post_only: bool,
+    time_in_force: TimeInForce,
+) -> Result<String, reqwest::Error> {
+    let request = LimitOrderRequest {
+        side,
+        quantity: quantity.round_dp(8).to_string(),
+        price: price.to_string(),
+        pair: currency_pair.symbol.clone(),
+        post_only,
+        customer_order_id: create_customer_order_id(),
+        time_in_force: time_in_force.to_string(),
+    };
+    let body = serde_json::to_string(&request).expect("Failed to serialize limit order");
+    let order_id = send_order(credentials, LIMIT_ORDER_PATH, body).await?;
+
+    println!(
+        "{}| {} {} LIMIT order {} placed at price: {} and quantity: {}",
+        Utc::now().to_rfc3339().blue(),
+        request.pair.green(),
+        side.to_string().yellow(),
+        order_id.bright_blue(),
+        request.price,
+        request.quantity
+    );
+    record_order(
+        &order_id,
+        side,
+        price,
+        quantity.round_dp(8),
+        if post_only {
+            OrderType::PostOnlyLimit
+        } else {
+            OrderType::Limit
+        },
+        time_in_force,
+        &request.pair,
+    )
+    .await;
+    Ok(order_id)
+}
+
+/// Buys are sized in the quote currency and sells in the base currency, as VALR expects
+pub async fn place_market_order(
+    credentials: &Credentials,
+    side: OrderSide,
+    amount: Decimal,
+    currency_pair: &CurrencyPair,
+) -> Result<String, reqwest::Error> {
+    let amount = amount.round_dp(8);
+    let (base_amount, quote_amount) = if side == OrderSide::Buy {
+        (None, Some(amount.to_string()))
+    } else {
+        (Some(amount.to_string()), None)
+    };
+    let request = MarketOrderRequest {
+        side,
+        base_amount,
+        quote_amount,
+        pair: currency_pair.symbol.clone(),
+        customer_order_id: create_customer_order_id(),
+    };
+    let body = serde_json::to_string(&request).expect("Failed to serialize market order");
+    let order_id = send_order(credentials, MARKET_ORDER_PATH, body).await?;
+
+    println!(
+        "{}| {} {} MARKET order {} placed for amount: {}",
+        Utc::now().to_rfc3339().blue(),
+        request.pair.green(),
+        side.to_string().yellow(),
+        order_id.bright_blue(),
+        amount
+    );
+    record_order(
+        &order_id,
+        side,
+        Decimal::ZERO,
+        amount,
+        OrderType::Market,
+        TimeInForce::GoodTillCancelled,
+        &request.pair,
+    )
+    .await;
+    Ok(order_id)
+}
+
+pub async fn cancel_order(
+    credentials: &Credentials,
+    order_id: &str,
+    currency_pair: &CurrencyPair,
+) -> Result<(), reqwest::Error> {
+    let request = CancelOrderRequest {
+        order_id: order_id.to_string(),
+        pair: currency_pair.symbol.clone(),
+    };
+    let body = serde_json::to_string(&request).expect("Failed to serialize cancel order");
+    send_request(credentials, CANCEL_ORDER_PATH, "DELETE", body).await?;
+    println!(
+        "{}| {} order {} cancel requested",
+        Utc::now().to_rfc3339().blue(),
+        request.pair.green(),
+        order_id.bright_blue()
+    );
+    Ok(())
+}
+
+/// VALR confirms every cancellation with an ORDER_STATUS_UPDATE
+pub async fn cancel_all_orders(
+    credentials: &Credentials,
+    currency_pair: &CurrencyPair,
+) -> Result<(), reqwest::Error> {
+    let path = format!("{}/{}", ORDERS_PATH, currency_pair.symbol);
+    send_request(credentials, &path, "DELETE", String::new()).await?;
+    println!(
+        "{}| {} all open orders cancel requested",
+        Utc::now().to_rfc3339().blue(),
+        currency_pair.symbol.green()
+    );
+    Ok(())
+}
+
+pub async fn modify_order(
+    credentials: &Credentials,
+    order_id: &str,
+    price: Decimal,
+    quantity: Decimal,
+    currency_pair: &CurrencyPair,
+) -> Result<(), reqwest::Error> {
+    let request = ModifyOrderRequest {
+        order_id: order_id.to_string(),
+        pair: currency_pair.symbol.clone(),
+        modify_match_strategy: String::from("CANCEL_ORIGINAL"),
+        new_price: price.to_string(),
+        new_total_quantity: quantity.to_string(),
+    };
+    let body = serde_json::to_string(&request).expect("Failed to serialize modify order");
+    send_request(credentials, MODIFY_ORDER_PATH, "PUT", body).await?;
+    println!(
+        "{}| {} order {} modify requested to price: {} and quantity: {}",
+        Utc::now().to_rfc3339().blue(),
+        request.pair.green(),
+        order_id.bright_blue(),
+        request.new_price,
+        request.new_total_quantity
+    );
+    Ok(())
+}
+
+async fn send_request(
+    credentials: &Credentials,
+    path: &str,
+    verb: &str,
+    body: String,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let response = create_http_request(
+        format!("{}{}", VALR_API_URL, path),
+        &credentials.api_key,
+        &credentials.api_secret,
+        path,
+        verb,
+        Option::from(body),
+    )
+    .header("Content-Type", "application/json")
+    .send()
+    .await?;
+
+    response.error_for_status().map_err(|e| {
+        error!("{} request to {} failed: {}", verb, path, e);
+        e
+    })
+}
+
+async fn send_order(
+    credentials: &Credentials,
+    path: &str,
+    body: String,
+) -> Result<String, reqwest::Error> {
+    let response = send_request(credentials, path, "POST", body).await?;
+    let order_response = response.json::<OrderResponse>().await?;
+    Ok(order_response.id)
+}
+
+/// VALR only confirms the order asynchronously, so the order is tracked as placed until the
+/// next ORDER_STATUS_UPDATE or OPEN_ORDERS_UPDATE arrives
+async fn record_order(
+    order_id: &str,
+    side: OrderSide,
+    price: Decimal,
+    quantity: Decimal,
+    order_type: OrderType,
+    time_in_force: TimeInForce,
+    pair: &str,
+) {
+    let now = Utc::now().to_rfc3339();
+    let order = Order {
+        order_id: order_id.to_string(),
+        side,
+        remaining_quantity: Some(quantity),
+        price,
+        currency_pair: pair.to_string(),
+        created_at: now.clone(),
+        original_quantity: quantity,
+        filled_percentage: Decimal::ZERO,
+        updated_at: now,
+        status: OrderStatus::Placed,
+        r#type: order_type,
+        time_in_force,
+        allow_margin: false,
+    };
+    let mut orders_writer = ORDERS.write().await;
+    orders_writer.record_placed(order);
+    drop(orders_writer);
+}
+
+fn create_customer_order_id() -> String {
+    format!("rusty-bot-{}", Utc::now().timestamp_micros())
+}
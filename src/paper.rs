@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use colored::Colorize;
+use rust_decimal::Decimal;
+use tokio::sync::{Mutex, RwLock};
+
+use crate::order_book::{OrderBook, PriceLevel};
+use crate::order_manager::OrderManager;
+use crate::orders::{OrderError, OrderExecutor};
+use crate::position::PositionTracker;
+use crate::rusty_bot_models::{AccountTrade, BalanceUpdate, CurrencyPair, OrderSide};
+use crate::simulation::{SimulatedAccount, SimulatedOrder, SimulatedOrderExecutor};
+
+/// Forward-tests strategies on the live order book. Orders are matched locally against the
+/// OB_L1_D10_SNAPSHOT levels and the simulated balances and orders are published in place of
+/// the account's.
+pub struct PaperOrderExecutor {
+    simulated: SimulatedOrderExecutor,
+    order_book: Arc<RwLock<OrderBook>>,
+    taken: Mutex<TakenLiquidity>,
+    balances: Arc<RwLock<Vec<BalanceUpdate>>>,
+    orders: Arc<RwLock<OrderManager>>,
+    positions: Arc<RwLock<PositionTracker>>,
+    fills: Mutex<Vec<AccountTrade>>,
+}
+
+impl PaperOrderExecutor {
+    pub async fn new(
+        account: SimulatedAccount,
+        order_book: Arc<RwLock<OrderBook>>,
+        balances: Arc<RwLock<Vec<BalanceUpdate>>>,
+        orders: Arc<RwLock<OrderManager>>,
+        positions: Arc<RwLock<PositionTracker>>,
+    ) -> Self {
+        let paper_order_executor = PaperOrderExecutor {
+            simulated: SimulatedOrderExecutor {
+                account: Arc::new(Mutex::new(account)),
+            },
+            order_book,
+            taken: Mutex::new(TakenLiquidity::default()),
+            balances,
+            orders,
+            positions,
+            fills: Mutex::new(vec![]),
+        };
+        paper_order_executor.publish().await;
+        paper_order_executor
+    }
+
+    async fn match_book(&self) -> bool {
+        let order_book_reader = self.order_book.read().await;
+        let mut taken = self.taken.lock().await;
+        taken.refresh(&order_book_reader);
+        let mut account = self.simulated.account.lock().await;
+        account.current_time = Utc::now().to_rfc3339();
+        // what is offered to one order is out of reach of the next in the same pass
+        let mut offered = taken.clone();
+        let trades = account.match_orders(|order| {
+            let fill = fill_from_book(order, &order_book_reader, &offered);
+            if let Some((_, quantity)) = fill {
+                offered.take(order.side, &order_book_reader, quantity);
+            }
+            fill
+        });
+        for trade in &trades {
+            taken.take(trade.side, &order_book_reader, trade.quantity);
+        }
+        let currency_pair = account.currency_pair.clone();
+        drop(account);
+        drop(taken);
+        drop(order_book_reader);
+
+        let mut positions_writer = self.positions.write().await;
+        for trade in &trades {
+            positions_writer.apply_simulated_trade(&currency_pair.symbol, trade);
+        }
+        drop(positions_writer);
+        self.fills.lock().await.extend(
+            trades
+                .iter()
+                .map(|trade| trade.to_account_trade(&currency_pair)),
+        );
+
+        for trade in &trades {
+            println!(
+                "{}| {} {} {} filled {} at {} fee: {}",
+                trade.traded_at.blue(),
+                "PAPER".on_bright_blue(),
+                trade.order_id,
+                trade.side.to_string().yellow(),
+                trade.quantity,
+                trade.price.to_string().yellow(),
+                trade.fee
+            );
+        }
+        self.publish().await;
+        !trades.is_empty()
+    }
+
+    async fn publish(&self) {
+        let account = self.simulated.account.lock().await;
+        *self.balances.write().await = account.balance_updates();
+        self.orders
+            .write()
+            .await
+            .replace_open_orders(account.orders());
+    }
+}
+
+#[async_trait]
+impl OrderExecutor for PaperOrderExecutor {
+    async fn place_limit_order(
+        &self,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<String, OrderError> {
+        self.simulated.account.lock().await.current_time = Utc::now().to_rfc3339();
+        let order_id = self
+            .simulated
+            .place_limit_order(side, price, quantity, currency_pair)
+            .await?;
+        self.match_book().await;
+        Ok(order_id)
+    }
+
+    async fn place_post_only_order(
+        &self,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<String, OrderError> {
+        let order_book_reader = self.order_book.read().await;
+        let crosses =
+            order_book_reader
+                .opposite_levels(side)
+                .first()
+                .is_some_and(|level| match side {
+                    OrderSide::Buy => price >= level.price,
+                    OrderSide::Sell => price <= level.price,
+                });
+        drop(order_book_reader);
+        if crosses {
+            return Err(OrderError::Rejected(format!(
+                "post-only {} at {} would cross the book",
+                side, price
+            )));
+        }
+        self.place_limit_order(side, price, quantity, currency_pair)
+            .await
+    }
+
+    /// Placed only when the levels still on offer can fill the whole quantity at the price
+    async fn place_fill_or_kill_order(
+        &self,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<String, OrderError> {
+        let order_book_reader = self.order_book.read().await;
+        let mut taken = self.taken.lock().await;
+        taken.refresh(&order_book_reader);
+        let order = SimulatedOrder {
+            order_id: String::new(),
+            side,
+            price: Some(price),
+            quantity,
+            created_at: String::new(),
+        };
+        let fillable = fill_from_book(&order, &order_book_reader, &taken)
+            .map_or(Decimal::ZERO, |(_, quantity)| quantity);
+        drop(taken);
+        drop(order_book_reader);
+        if fillable < quantity {
+            return Err(OrderError::Rejected(format!(
+                "fill-or-kill {} of {} at {} killed, only {} on offer",
+                side, quantity, price, fillable
+            )));
+        }
+        self.place_limit_order(side, price, quantity, currency_pair)
+            .await
+    }
+
+    async fn place_market_order(
+        &self,
+        side: OrderSide,
+        amount: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<String, OrderError> {
+        self.simulated.account.lock().await.current_time = Utc::now().to_rfc3339();
+        let order_id = self
+            .simulated
+            .place_market_order(side, amount, currency_pair)
+            .await?;
+        self.match_book().await;
+        Ok(order_id)
+    }
+
+    async fn cancel_order(
+        &self,
+        order_id: &str,
+        currency_pair: &CurrencyPair,
+    ) -> Result<(), OrderError> {
+        self.simulated.cancel_order(order_id, currency_pair).await?;
+        self.publish().await;
+        Ok(())
+    }
+
+    async fn cancel_all_orders(&self, currency_pair: &CurrencyPair) -> Result<(), OrderError> {
+        self.simulated.cancel_all_orders(currency_pair).await?;
+        self.publish().await;
+        Ok(())
+    }
+
+    /// A new price can cross the book, so the order is matched again right away
+    async fn modify_order(
+        &self,
+        order_id: &str,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<(), OrderError> {
+        self.simulated
+            .modify_order(order_id, side, price, quantity, currency_pair)
+            .await?;
+        self.match_book().await;
+        Ok(())
+    }
+
+    async fn on_book_update(&self) -> bool {
+        self.match_book().await
+    }
+
+    async fn take_fills(&self) -> Vec<AccountTrade> {
+        std::mem::take(&mut *self.fills.lock().await)
+    }
+}
+
+/// Quantity paper fills took from each displayed level. A snapshot only shows the book as it
+/// was before the simulated fills, so what was taken stays out of reach until the level is
+/// gone or shows less than was taken, only volume added since is offered again.
+#[derive(Debug, Clone, Default)]
+pub struct TakenLiquidity {
+    asks: HashMap<Decimal, Decimal>,
+    bids: HashMap<Decimal, Decimal>,
+}
+
+impl TakenLiquidity {
+    fn levels(&self, side: OrderSide) -> &HashMap<Decimal, Decimal> {
+        match side {
+            OrderSide::Buy => &self.asks,
+            OrderSide::Sell => &self.bids,
+        }
+    }
+
+    fn levels_mut(&mut self, side: OrderSide) -> &mut HashMap<Decimal, Decimal> {
+        match side {
+            OrderSide::Buy => &mut self.asks,
+            OrderSide::Sell => &mut self.bids,
+        }
+    }
+
+    /// Forgets levels that left the book and caps the rest at what is displayed now
+    pub fn refresh(&mut self, order_book: &OrderBook) {
+        // a buy takes from the asks, where sells rest
+        for (side, resting_side) in [
+            (OrderSide::Buy, OrderSide::Sell),
+            (OrderSide::Sell, OrderSide::Buy),
+        ] {
+            self.levels_mut(side).retain(|price, taken| {
+                let displayed = order_book.depth_at_price(resting_side, *price);
+                *taken = (*taken).min(displayed);
+                displayed > Decimal::ZERO
+            });
+        }
+    }
+
+    /// Quantity of the level an order on `side` can still fill against
+    pub fn remaining(&self, side: OrderSide, level: &PriceLevel) -> Decimal {
+        let taken = self
+            .levels(side)
+            .get(&level.price)
+            .copied()
+            .unwrap_or(Decimal::ZERO);
+        (level.quantity - taken).max(Decimal::ZERO)
+    }
+
+    /// Takes the quantity from the best levels down, the way `fill_from_book` walks them
+    pub fn take(&mut self, side: OrderSide, order_book: &OrderBook, quantity: Decimal) {
+        let mut left = quantity;
+        for level in order_book.opposite_levels(side) {
+            if left <= Decimal::ZERO {
+                break;
+            }
+            let taken = self.remaining(side, level).min(left);
+            if taken > Decimal::ZERO {
+                *self
+                    .levels_mut(side)
+                    .entry(level.price)
+                    .or_insert(Decimal::ZERO) += taken;
+                left -= taken;
+            }
+        }
+    }
+}
+
+/// Walks the opposite side of the book for as long as it crosses the order's price and
+/// returns the volume weighted fill price and the quantity still available there
+pub fn fill_from_book(
+    order: &SimulatedOrder,
+    order_book: &OrderBook,
+    taken: &TakenLiquidity,
+) -> Option<(Decimal, Decimal)> {
+    let side = order.side;
+    let is_buy = side == OrderSide::Buy;
+    let mut quantity = Decimal::ZERO;
+    let mut notional = Decimal::ZERO;
+
+    for level in order_book.opposite_levels(side) {
+        let price = level.price;
+        let level_quantity = taken.remaining(side, level);
+        if price <= Decimal::ZERO {
+            continue;
+        }
+        let crosses = match order.price {
+            None => true,
+            Some(limit) if is_buy => price <= limit,
+            Some(limit) => price >= limit,
+        };
+        if !crosses {
+            break;
+        }
+        let wanted = if is_buy && order.price.is_none() {
+            (order.quantity - notional) / price
+        } else {
+            order.quantity - quantity
+        };
+        if wanted <= Decimal::ZERO {
+            break;
+        }
+        let taken = level_quantity.min(wanted);
+        quantity += taken;
+        notional += taken * price;
+    }
+
+    if quantity > Decimal::ZERO {
+        Some((notional / quantity, quantity))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::order_book::OrderBook;
+use crate::rusty_bot_models::{AccountTrade, OrderSide};
+use crate::simulation::SimulatedTrade;
+
+/// Net position in one pair. The quantity is negative when more was sold than bought.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Position {
+    pub currency_pair: String,
+    pub quantity: Decimal,
+    pub average_entry_price: Decimal,
+    /// Before fees
+    pub realized_pnl: Decimal,
+    /// In the quote currency
+    pub fees_paid: Decimal,
+}
+
+impl Position {
+    pub fn new(currency_pair: &str) -> Self {
+        Position {
+            currency_pair: currency_pair.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Fills that add to the position move the average entry price, fills against it realize
+    /// P&L at the current average. A fill larger than the position flips it at the fill price.
+    pub fn apply_fill(
+        &mut self,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        fee_in_quote: Decimal,
+    ) {
+        let signed_quantity = match side {
+            OrderSide::Buy => quantity,
+            OrderSide::Sell => -quantity,
+        };
+        self.fees_paid += fee_in_quote;
+
+        if self.quantity.is_zero()
+            || self.quantity.is_sign_positive() == signed_quantity.is_sign_positive()
+        {
+            let total = self.quantity.abs() + quantity;
+            if !total.is_zero() {
+                self.average_entry_price =
+                    (self.average_entry_price * self.quantity.abs() + price * quantity) / total;
+            }
+            self.quantity += signed_quantity;
+            return;
+        }
+
+        let closed = self.quantity.abs().min(quantity);
+        let direction = if self.quantity.is_sign_positive() {
+            Decimal::ONE
+        } else {
+            Decimal::NEGATIVE_ONE
+        };
+        self.realized_pnl += (price - self.average_entry_price) * closed * direction;
+        let flipped = quantity > self.quantity.abs();
+        self.quantity += signed_quantity;
+        if self.quantity.is_zero() {
+            self.average_entry_price = Decimal::ZERO;
+        } else if flipped {
+            self.average_entry_price = price;
+        }
+    }
+
+    pub fn unrealized_pnl_at(&self, mark_price: Decimal) -> Decimal {
+        (mark_price - self.average_entry_price) * self.quantity
+    }
+
+    /// Marks longs at the best bid and shorts at the best ask, the price the position would
+    /// close at. None when the book has no price to mark against.
+    pub fn unrealized_pnl(&self, order_book: &OrderBook) -> Option<Decimal> {
+        if self.quantity.is_zero() {
+            return Some(Decimal::ZERO);
+        }
+        let mark_price = if self.quantity.is_sign_positive() {
+            order_book.best_bid()
+        } else {
+            order_book.best_ask()
+        }?
+        .price;
+        Some(self.unrealized_pnl_at(mark_price))
+    }
+
+    /// Realized P&L after fees
+    pub fn net_realized_pnl(&self) -> Decimal {
+        self.realized_pnl - self.fees_paid
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct PositionTracker {
+    positions: HashMap<String, Position>,
+}
+
+impl PositionTracker {
+    pub fn position(&self, currency_pair: &str) -> Option<&Position> {
+        self.positions.get(currency_pair)
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = &Position> {
+        self.positions.values()
+    }
+
+    /// Starts the pair's position from base held before the bot traded it, entered at `price`.
+    /// Returns false when the pair already has a position.
+    pub fn seed(&mut self, currency_pair: &str, quantity: Decimal, price: Decimal) -> bool {
+        if self.positions.contains_key(currency_pair) {
+            return false;
+        }
+        self.positions.insert(
+            currency_pair.to_string(),
+            Position {
+                quantity,
+                average_entry_price: price,
+                ..Position::new(currency_pair)
+            },
+        );
+        true
+    }
+
+    pub fn apply_fill(
+        &mut self,
+        currency_pair: &str,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        fee_in_quote: Decimal,
+    ) -> &Position {
+        let position = self
+            .positions
+            .entry(currency_pair.to_string())
+            .or_insert_with(|| Position::new(currency_pair));
+        position.apply_fill(side, price, quantity, fee_in_quote);
+        position
+    }
+
+    /// VALR charges the fee in either currency of the pair, base currency fees are converted
+    /// at the trade price
+    pub fn apply_account_trade(&mut self, trade: &AccountTrade, quote_currency: &str) -> &Position {
+        let fee = trade.fee.unwrap_or(Decimal::ZERO);
+        let fee_in_quote = match &trade.fee_currency {
+            Some(fee_currency) if fee_currency != quote_currency => fee * trade.price,
+            _ => fee,
+        };
+        self.apply_fill(
+            &trade.currency_pair,
+            trade.side,
+            trade.price,
+            trade.quantity,
+            fee_in_quote,
+        )
+    }
+
+    /// Simulated fees are always charged in the quote currency
+    pub fn apply_simulated_trade(
+        &mut self,
+        currency_pair: &str,
+        trade: &SimulatedTrade,
+    ) -> &Position {
+        self.apply_fill(
+            currency_pair,
+            trade.side,
+            trade.price,
+            trade.quantity,
+            trade.fee,
+        )
+    }
+}
@@ -0,0 +1,579 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Days, Duration, NaiveDate, NaiveTime, Utc};
+use colored::Colorize;
+use log::{error, warn};
+use rust_decimal::Decimal;
+use tokio::sync::{Mutex, RwLock};
+
+use crate::config::RiskConfig;
+use crate::order_book::OrderBook;
+use crate::order_manager::OrderManager;
+use crate::orders::{OrderError, OrderExecutor};
+use crate::position::PositionTracker;
+use crate::rusty_bot_models::{AccountTrade, BalanceUpdate, CurrencyPair, OrderSide};
+
+#[derive(Debug, PartialEq)]
+pub enum RiskViolation {
+    KillSwitchActive,
+    MaxPositionSize { position: Decimal, max: Decimal },
+    MaxOrderNotional { notional: Decimal, max: Decimal },
+    MaxOrdersPerMinute { max: u32 },
+    DailyLossLimit { loss: Decimal, max: Decimal },
+    NoMarketPrice,
+    NoOrderBook { currency_pair: String },
+}
+
+impl Display for RiskViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RiskViolation::KillSwitchActive => write!(f, "kill switch is active"),
+            RiskViolation::MaxPositionSize { position, max } => write!(
+                f,
+                "max position size: position would be {} with a limit of {}",
+                position, max
+            ),
+            RiskViolation::MaxOrderNotional { notional, max } => write!(
+                f,
+                "max order notional: order is worth {} with a limit of {}",
+                notional, max
+            ),
+            RiskViolation::MaxOrdersPerMinute { max } => {
+                write!(f, "max orders per minute: {} orders already sent", max)
+            }
+            RiskViolation::DailyLossLimit { loss, max } => write!(
+                f,
+                "daily loss limit: lost {} today with a limit of {}",
+                loss, max
+            ),
+            RiskViolation::NoMarketPrice => {
+                write!(f, "no market price to value the market order")
+            }
+            RiskViolation::NoOrderBook { currency_pair } => {
+                write!(f, "no order book for {}", currency_pair)
+            }
+        }
+    }
+}
+
+/// Pre-trade checks every order has to pass. Tripping the daily loss limit activates the
+/// kill switch, which blocks all further orders until the bot is restarted. The daily loss is
+/// measured from the P&L recorded by `roll_day` at the start of the UTC day.
+#[derive(Debug)]
+pub struct RiskManager {
+    limits: RiskConfig,
+    order_times: VecDeque<DateTime<Utc>>,
+    day: Option<NaiveDate>,
+    day_start_pnl: Decimal,
+    kill_switch: bool,
+}
+
+impl RiskManager {
+    pub fn new(limits: RiskConfig) -> Self {
+        RiskManager {
+            limits,
+            order_times: VecDeque::new(),
+            day: None,
+            day_start_pnl: Decimal::ZERO,
+            kill_switch: false,
+        }
+    }
+
+    pub fn is_killed(&self) -> bool {
+        self.kill_switch
+    }
+
+    pub fn activate_kill_switch(&mut self) {
+        self.kill_switch = true;
+    }
+
+    /// Records `pnl` as the start of the day when `now` falls on a new UTC day, returns false
+    /// when the day already started
+    pub fn roll_day(&mut self, pnl: Decimal, now: DateTime<Utc>) -> bool {
+        if self.day == Some(now.date_naive()) {
+            return false;
+        }
+        self.day = Some(now.date_naive());
+        self.day_start_pnl = pnl;
+        true
+    }
+
+    /// `position` is the base quantity held including open orders on the same side, `pnl` the
+    /// total P&L after fees. The order counts against the rate limit only when it passes.
+    #[allow(clippy::too_many_arguments)]
+    pub fn check_order(
+        &mut self,
+        currency_pair: &str,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        position: Decimal,
+        pnl: Decimal,
+        now: DateTime<Utc>,
+    ) -> Result<(), RiskViolation> {
+        if self.kill_switch {
+            return Err(RiskViolation::KillSwitchActive);
+        }
+
+        if let Some(max) = self.limits.daily_loss_limit {
+            let loss = self.day_start_pnl - pnl;
+            if loss >= max {
+                self.kill_switch = true;
+                return Err(RiskViolation::DailyLossLimit { loss, max });
+            }
+        }
+
+        if let Some(max) = self.limits.max_order_notional {
+            let notional = price * quantity;
+            if notional > max {
+                return Err(RiskViolation::MaxOrderNotional { notional, max });
+            }
+        }
+
+        if let Some(max) = self.limits.max_position.get(currency_pair).copied() {
+            let position_after = match side {
+                OrderSide::Buy => position + quantity,
+                OrderSide::Sell => position - quantity,
+            };
+            if position_after.abs() > max && position_after.abs() > position.abs() {
+                return Err(RiskViolation::MaxPositionSize {
+                    position: position_after,
+                    max,
+                });
+            }
+        }
+
+        if let Some(max) = self.limits.max_orders_per_minute {
+            while self
+                .order_times
+                .front()
+                .is_some_and(|sent_at| now - *sent_at >= Duration::minutes(1))
+            {
+                self.order_times.pop_front();
+            }
+            if self.order_times.len() >= max as usize {
+                return Err(RiskViolation::MaxOrdersPerMinute { max });
+            }
+        }
+
+        self.order_times.push_back(now);
+        Ok(())
+    }
+}
+
+/// Sits between the strategies and the executor that sends the orders and blocks the ones
+/// that break a risk rule. Orders are valued against the book of their own pair, the market's
+/// book or one of the followed pairs' books, and blocked when there is none.
+///
+/// The first order on a pair starts its position from the base held before then, so selling
+/// inventory the bot did not buy is not counted as going short. A base currency shared by
+/// several pairs is only held in the first of them to trade.
+pub struct RiskManagedExecutor {
+    inner: Arc<dyn OrderExecutor>,
+    risk_manager: Mutex<RiskManager>,
+    /// The market first, then the followed pairs
+    currency_pairs: Vec<CurrencyPair>,
+    order_book: Arc<RwLock<OrderBook>>,
+    pair_books: Arc<RwLock<HashMap<String, OrderBook>>>,
+    orders: Arc<RwLock<OrderManager>>,
+    positions: Arc<RwLock<PositionTracker>>,
+    balances: Arc<RwLock<Vec<BalanceUpdate>>>,
+    /// Base currencies whose holdings were taken into a position
+    seeded_currencies: Mutex<HashSet<String>>,
+}
+
+impl RiskManagedExecutor {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        inner: Arc<dyn OrderExecutor>,
+        risk_manager: RiskManager,
+        currency_pairs: Vec<CurrencyPair>,
+        order_book: Arc<RwLock<OrderBook>>,
+        pair_books: Arc<RwLock<HashMap<String, OrderBook>>>,
+        orders: Arc<RwLock<OrderManager>>,
+        positions: Arc<RwLock<PositionTracker>>,
+        balances: Arc<RwLock<Vec<BalanceUpdate>>>,
+    ) -> Self {
+        RiskManagedExecutor {
+            inner,
+            risk_manager: Mutex::new(risk_manager),
+            currency_pairs,
+            order_book,
+            pair_books,
+            orders,
+            positions,
+            balances,
+            seeded_currencies: Mutex::new(HashSet::new()),
+        }
+    }
+
+    async fn find_order_book(&self, symbol: &str) -> Option<OrderBook> {
+        let is_market = self
+            .currency_pairs
+            .first()
+            .is_some_and(|market| market.symbol == symbol);
+        if is_market {
+            Some(self.order_book.read().await.clone())
+        } else {
+            self.pair_books.read().await.get(symbol).cloned()
+        }
+    }
+
+    async fn order_book(&self, currency_pair: &CurrencyPair) -> Result<OrderBook, OrderError> {
+        let order_book = self.find_order_book(&currency_pair.symbol).await;
+        order_book.ok_or_else(|| {
+            let violation = RiskViolation::NoOrderBook {
+                currency_pair: currency_pair.symbol.clone(),
+            };
+            warn!(
+                "{} order blocked by risk rule {}",
+                currency_pair.symbol, violation
+            );
+            OrderError::Rejected(violation.to_string())
+        })
+    }
+
+    /// Realized P&L after fees of every position plus the unrealized P&L of those with a book,
+    /// in the market's quote currency. P&L of a pair quoted in another currency is left out
+    /// when no traded pair converts it.
+    async fn total_pnl(&self) -> Decimal {
+        let positions = self.positions.read().await.clone();
+        let mut pnl = Decimal::ZERO;
+        for position in positions.positions() {
+            let unrealized_pnl = self
+                .find_order_book(&position.currency_pair)
+                .await
+                .and_then(|order_book| position.unrealized_pnl(&order_book));
+            let position_pnl =
+                position.net_realized_pnl() + unrealized_pnl.unwrap_or(Decimal::ZERO);
+            let quote_currency = self
+                .currency_pairs
+                .iter()
+                .find(|pair| pair.symbol == position.currency_pair)
+                .map(|pair| pair.quote_currency.as_str());
+            let converted = match quote_currency {
+                Some(quote_currency) => self.to_market_quote(position_pnl, quote_currency).await,
+                None => None,
+            };
+            match converted {
+                Some(converted) => pnl += converted,
+                None => warn!(
+                    "Leaving the P&L of {} out of the daily loss, no traded pair converts it",
+                    position.currency_pair
+                ),
+            }
+        }
+        pnl
+    }
+
+    /// Converts at the mid price of a traded pair between the currency and the market's quote
+    /// currency
+    async fn to_market_quote(&self, amount: Decimal, currency: &str) -> Option<Decimal> {
+        let market_quote = &self.currency_pairs.first()?.quote_currency;
+        if currency == market_quote || amount.is_zero() {
+            return Some(amount);
+        }
+        for pair in &self.currency_pairs {
+            let direct = pair.base_currency == currency && pair.quote_currency == *market_quote;
+            let inverse = pair.base_currency == *market_quote && pair.quote_currency == currency;
+            if !direct && !inverse {
+                continue;
+            }
+            let Some(mid_price) = self
+                .find_order_book(&pair.symbol)
+                .await
+                .and_then(|order_book| order_book.mid_price())
+                .filter(|price| *price > Decimal::ZERO)
+            else {
+                continue;
+            };
+            return Some(if direct {
+                amount * mid_price
+            } else {
+                amount / mid_price
+            });
+        }
+        None
+    }
+
+    /// Starts the daily loss limit's day from the current P&L when the UTC day changed
+    pub async fn roll_day(&self) {
+        let pnl = self.total_pnl().await;
+        if self.risk_manager.lock().await.roll_day(pnl, Utc::now()) {
+            println!(
+                "{}| Risk day started at P&L {}",
+                Utc::now().to_rfc3339().blue(),
+                pnl.to_string().bright_blue()
+            );
+        }
+    }
+
+    /// Rolls the day over at every UTC midnight
+    pub async fn run_day_rollover(&self) {
+        loop {
+            let now = Utc::now();
+            let next_day = (now.date_naive() + Days::new(1))
+                .and_time(NaiveTime::MIN)
+                .and_utc();
+            tokio::time::sleep((next_day - now).to_std().unwrap_or_default()).await;
+            self.roll_day().await;
+        }
+    }
+
+    /// Halts trading and cancels every open order on the traded pairs
+    pub async fn activate_kill_switch(&self) {
+        self.risk_manager.lock().await.activate_kill_switch();
+        self.cancel_after_kill_switch().await;
+    }
+
+    async fn cancel_after_kill_switch(&self) {
+        for currency_pair in &self.currency_pairs {
+            println!(
+                "{}| {} trading halted for {}, cancelling open orders",
+                Utc::now().to_rfc3339().blue(),
+                "KILL SWITCH".on_red(),
+                currency_pair.symbol.green()
+            );
+        }
+        self.cancel_all_traded_orders().await;
+    }
+
+    /// Cancels the open orders on every traded pair, returns the pairs they were cancelled on
+    pub async fn cancel_all_traded_orders(&self) -> Vec<String> {
+        let mut cancelled = vec![];
+        for currency_pair in &self.currency_pairs {
+            match self.inner.cancel_all_orders(currency_pair).await {
+                Ok(_) => cancelled.push(currency_pair.symbol.clone()),
+                Err(e) => error!(
+                    "Failed to cancel open orders on {}: {}",
+                    currency_pair.symbol, e
+                ),
+            }
+        }
+        cancelled
+    }
+
+    /// Enters the base held at the mid price, once the balance of the base currency is known
+    async fn seed_position(&self, currency_pair: &CurrencyPair, order_book: &OrderBook) {
+        let mut seeded_currencies = self.seeded_currencies.lock().await;
+        if seeded_currencies.contains(&currency_pair.base_currency) {
+            return;
+        }
+        let held = self
+            .balances
+            .read()
+            .await
+            .iter()
+            .find(|b| b.currency.symbol == currency_pair.base_currency)
+            .map(|b| b.total);
+        let (Some(held), Some(mid_price)) = (held, order_book.mid_price()) else {
+            return;
+        };
+        seeded_currencies.insert(currency_pair.base_currency.clone());
+        if held > Decimal::ZERO
+            && self
+                .positions
+                .write()
+                .await
+                .seed(&currency_pair.symbol, held, mid_price)
+        {
+            println!(
+                "{}| {} position starts from {} {} held, entered at {}",
+                Utc::now().to_rfc3339().blue(),
+                currency_pair.symbol.green(),
+                held,
+                currency_pair.base_currency,
+                mid_price
+            );
+        }
+    }
+
+    /// `replacing` is the order a modification replaces, its quantity no longer counts
+    /// towards the position
+    async fn check_order(
+        &self,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        currency_pair: &CurrencyPair,
+        replacing: Option<&str>,
+    ) -> Result<(), OrderError> {
+        let order_book = self.order_book(currency_pair).await?;
+        self.seed_position(currency_pair, &order_book).await;
+        let pnl = self.total_pnl().await;
+        let positions_reader = self.positions.read().await;
+        let position = positions_reader.position(&currency_pair.symbol);
+        let orders_reader = self.orders.read().await;
+        let replaced_quantity = replacing
+            .and_then(|order_id| orders_reader.get(order_id))
+            .filter(|order| order.side == side)
+            .map(|order| order.remaining_quantity.unwrap_or(order.original_quantity))
+            .unwrap_or(Decimal::ZERO);
+        let open_quantity =
+            orders_reader.open_quantity(&currency_pair.symbol, side) - replaced_quantity;
+        drop(orders_reader);
+        let position = position.map(|p| p.quantity).unwrap_or(Decimal::ZERO)
+            + match side {
+                OrderSide::Buy => open_quantity,
+                OrderSide::Sell => -open_quantity,
+            };
+        drop(positions_reader);
+
+        let mut risk_manager = self.risk_manager.lock().await;
+        let was_killed = risk_manager.is_killed();
+        let result = risk_manager.check_order(
+            &currency_pair.symbol,
+            side,
+            price,
+            quantity,
+            position,
+            pnl,
+            Utc::now(),
+        );
+        let killed = !was_killed && risk_manager.is_killed();
+        drop(risk_manager);
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(violation) => {
+                warn!(
+                    "{} {} order blocked by risk rule {}",
+                    currency_pair.symbol, side, violation
+                );
+                if killed {
+                    self.cancel_after_kill_switch().await;
+                }
+                Err(OrderError::Rejected(violation.to_string()))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl OrderExecutor for RiskManagedExecutor {
+    async fn place_limit_order(
+        &self,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<String, OrderError> {
+        self.check_order(side, price, quantity, currency_pair, None)
+            .await?;
+        self.inner
+            .place_limit_order(side, price, quantity, currency_pair)
+            .await
+    }
+
+    async fn place_post_only_order(
+        &self,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<String, OrderError> {
+        self.check_order(side, price, quantity, currency_pair, None)
+            .await?;
+        self.inner
+            .place_post_only_order(side, price, quantity, currency_pair)
+            .await
+    }
+
+    async fn place_fill_or_kill_order(
+        &self,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<String, OrderError> {
+        self.check_order(side, price, quantity, currency_pair, None)
+            .await?;
+        self.inner
+            .place_fill_or_kill_order(side, price, quantity, currency_pair)
+            .await
+    }
+
+    /// Market buys are sized in quote, so the quantity is estimated from the asks
+    async fn place_market_order(
+        &self,
+        side: OrderSide,
+        amount: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<String, OrderError> {
+        let order_book = self.order_book(currency_pair).await?;
+        let price = match side {
+            OrderSide::Buy => order_book.best_ask(),
+            OrderSide::Sell => order_book.best_bid(),
+        }
+        .map(|level| level.price)
+        .filter(|price| *price > Decimal::ZERO);
+        let Some(price) = price else {
+            warn!(
+                "{} {} order blocked by risk rule {}",
+                currency_pair.symbol,
+                side,
+                RiskViolation::NoMarketPrice
+            );
+            return Err(OrderError::Rejected(
+                RiskViolation::NoMarketPrice.to_string(),
+            ));
+        };
+        // valued at the price the displayed levels would fill it at, or the best price when
+        // they are too thin
+        let quantity_at_best = match side {
+            OrderSide::Buy => amount / price,
+            OrderSide::Sell => amount,
+        };
+        let price = order_book
+            .volume_weighted_fill_price(side, quantity_at_best)
+            .unwrap_or(price);
+        let quantity = match side {
+            OrderSide::Buy => amount / price,
+            OrderSide::Sell => amount,
+        };
+        self.check_order(side, price, quantity, currency_pair, None)
+            .await?;
+        self.inner
+            .place_market_order(side, amount, currency_pair)
+            .await
+    }
+
+    /// Cancels always go through, they can only reduce risk
+    async fn cancel_order(
+        &self,
+        order_id: &str,
+        currency_pair: &CurrencyPair,
+    ) -> Result<(), OrderError> {
+        self.inner.cancel_order(order_id, currency_pair).await
+    }
+
+    async fn cancel_all_orders(&self, currency_pair: &CurrencyPair) -> Result<(), OrderError> {
+        self.inner.cancel_all_orders(currency_pair).await
+    }
+
+    async fn modify_order(
+        &self,
+        order_id: &str,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<(), OrderError> {
+        self.check_order(side, price, quantity, currency_pair, Some(order_id))
+            .await?;
+        self.inner
+            .modify_order(order_id, side, price, quantity, currency_pair)
+            .await
+    }
+
+    async fn on_book_update(&self) -> bool {
+        self.inner.on_book_update().await
+    }
+
+    async fn take_fills(&self) -> Vec<AccountTrade> {
+        self.inner.take_fills().await
+    }
+}
@@ -1,6 +1,10 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
 use serde::{Deserialize, Deserializer, Serialize};
+use rust_decimal::Decimal;
 use serde_json::Value;
 use serde_with::{DisplayFromStr, serde_as};
 
@@ -15,8 +19,22 @@ pub enum WsMessage {
     NewTradeBucket(Box<TradePriceBucketUpdate>),
     #[serde(rename = "OB_L1_D1_SNAPSHOT", deserialize_with = "ws_deserializer")]
     OrderbookLvOneDepthOneSnapshot(Box<DepthOrderBookSnapshot>),
-    #[serde(rename = "OB_L1_D10_SNAPSHOT", deserialize_with = "ws_deserializer")]
-    OrderbookLvOneDepthTenSnapshot(Box<DepthOrderBookSnapshot>),
+    #[serde(rename = "OB_L1_D10_SNAPSHOT", deserialize_with = "ws_pair_deserializer")]
+    OrderbookLvOneDepthTenSnapshot(Box<PairMessage<DepthOrderBookSnapshot>>),
+    #[serde(rename = "FULL_ORDERBOOK_SNAPSHOT", deserialize_with = "ws_deserializer")]
+    FullOrderbookSnapshot(Box<OrderBookData>),
+    #[serde(rename = "FULL_ORDERBOOK_UPDATE", deserialize_with = "ws_deserializer")]
+    FullOrderbookUpdate(Box<OrderBookData>),
+    #[serde(rename = "ORDER_PROCESSED", deserialize_with = "ws_deserializer")]
+    OrderProcessed(Box<OrderProcessed>),
+    #[serde(rename = "ORDER_STATUS_UPDATE", deserialize_with = "ws_deserializer")]
+    OrderStatusUpdate(Box<OrderStatusUpdate>),
+    #[serde(rename = "FAILED_CANCEL_ORDER", deserialize_with = "ws_deserializer")]
+    FailedCancelOrder(Box<FailedCancelOrder>),
+    #[serde(rename = "NEW_ACCOUNT_TRADE", deserialize_with = "ws_deserializer")]
+    NewAccountTrade(Box<AccountTrade>),
+    #[serde(rename = "NEW_TRADE", deserialize_with = "ws_deserializer")]
+    NewTrade(Box<Trade>),
     #[serde(rename = "AUTHENTICATED")]
     Authenticated,
     #[serde(rename = "SUBSCRIBED")]
@@ -40,6 +58,193 @@ fn ws_deserializer<'de, D, T: Deserialize<'de>>(deserializer: D) -> Result<T, D:
     Ok(d)
 }
 
+/// Data of a message published for several pairs, with the pair it is for
+#[derive(Debug)]
+pub struct PairMessage<T> {
+    pub currency_pair: String,
+    pub data: T,
+}
+
+fn ws_pair_deserializer<'de, D, T: Deserialize<'de>>(
+    deserializer: D,
+) -> Result<Box<PairMessage<T>>, D::Error>
+    where
+        D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct WsMessage<T> {
+        #[serde(rename = "currencyPairSymbol", alias = "ps", default)]
+        currency_pair: String,
+        #[serde(alias = "d")]
+        data: T
+    }
+    let message = WsMessage::deserialize(deserializer)?;
+    Ok(Box::new(PairMessage {
+        currency_pair: message.currency_pair,
+        data: message.data,
+    }))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderSide {
+    #[serde(rename = "BUY", alias = "buy")]
+    Buy,
+    #[serde(rename = "SELL", alias = "sell")]
+    Sell,
+}
+
+impl FromStr for OrderSide {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "BUY" => Ok(OrderSide::Buy),
+            "SELL" => Ok(OrderSide::Sell),
+            _ => Err(format!("Order side: {} not supported", s)),
+        }
+    }
+}
+
+impl Display for OrderSide {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderSide::Buy => write!(f, "BUY"),
+            OrderSide::Sell => write!(f, "SELL"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    Placed,
+    PartiallyFilled,
+    /// The price or quantity of an open order changed, it keeps resting on the book
+    Modified,
+    Filled,
+    Cancelled,
+    Failed,
+}
+
+impl OrderStatus {
+    /// Filled, cancelled and failed orders no longer rest on the book
+    pub fn is_closed(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::Filled | OrderStatus::Cancelled | OrderStatus::Failed
+        )
+    }
+
+    /// Open orders can move to any later state, closed orders never change again
+    pub fn can_transition_to(&self, next: OrderStatus) -> bool {
+        match self {
+            OrderStatus::Placed | OrderStatus::Modified => true,
+            OrderStatus::PartiallyFilled => next != OrderStatus::Placed,
+            OrderStatus::Filled | OrderStatus::Cancelled | OrderStatus::Failed => false,
+        }
+    }
+}
+
+/// VALR reports failures with a reason in the status, e.g. "Instant Order Balance Reserve Failed".
+/// Market orders report "Instant Order Balance Reserved" once accepted and "Instant Order
+/// Completed" once done trading.
+impl FromStr for OrderStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "placed" | "active" | "instant order balance reserved" => Ok(OrderStatus::Placed),
+            "partially filled" => Ok(OrderStatus::PartiallyFilled),
+            "order modified" => Ok(OrderStatus::Modified),
+            "filled" | "instant order completed" => Ok(OrderStatus::Filled),
+            "cancelled" => Ok(OrderStatus::Cancelled),
+            status if status.contains("failed") => Ok(OrderStatus::Failed),
+            _ => Err(format!("Order status: {} not supported", s)),
+        }
+    }
+}
+
+impl Display for OrderStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderStatus::Placed => write!(f, "Placed"),
+            OrderStatus::PartiallyFilled => write!(f, "Partially Filled"),
+            OrderStatus::Modified => write!(f, "Order Modified"),
+            OrderStatus::Filled => write!(f, "Filled"),
+            OrderStatus::Cancelled => write!(f, "Cancelled"),
+            OrderStatus::Failed => write!(f, "Failed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderType {
+    Limit,
+    PostOnlyLimit,
+    Market,
+    Simple,
+    StopLossLimit,
+    TakeProfitLimit,
+}
+
+impl FromStr for OrderType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "limit" => Ok(OrderType::Limit),
+            "limit post-only" | "post-only limit" => Ok(OrderType::PostOnlyLimit),
+            "market" => Ok(OrderType::Market),
+            "simple" => Ok(OrderType::Simple),
+            "stop-loss-limit" | "stop loss limit" => Ok(OrderType::StopLossLimit),
+            "take-profit-limit" | "take profit limit" => Ok(OrderType::TakeProfitLimit),
+            _ => Err(format!("Order type: {} not supported", s)),
+        }
+    }
+}
+
+impl Display for OrderType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderType::Limit => write!(f, "limit"),
+            OrderType::PostOnlyLimit => write!(f, "limit post-only"),
+            OrderType::Market => write!(f, "market"),
+            OrderType::Simple => write!(f, "simple"),
+            OrderType::StopLossLimit => write!(f, "stop-loss-limit"),
+            OrderType::TakeProfitLimit => write!(f, "take-profit-limit"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeInForce {
+    GoodTillCancelled,
+    ImmediateOrCancel,
+    FillOrKill,
+}
+
+impl FromStr for TimeInForce {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "GTC" => Ok(TimeInForce::GoodTillCancelled),
+            "IOC" => Ok(TimeInForce::ImmediateOrCancel),
+            "FOK" => Ok(TimeInForce::FillOrKill),
+            _ => Err(format!("Time in force: {} not supported", s)),
+        }
+    }
+}
+
+impl Display for TimeInForce {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeInForce::GoodTillCancelled => write!(f, "GTC"),
+            TimeInForce::ImmediateOrCancel => write!(f, "IOC"),
+            TimeInForce::FillOrKill => write!(f, "FOK"),
+        }
+    }
+}
+
 #[serde_as]
 #[derive(Deserialize, Clone, Debug, PartialEq, PartialOrd)]
 pub struct MarkPriceBucket {
@@ -50,13 +255,17 @@ pub struct MarkPriceBucket {
     #[serde(rename = "startTime")]
     pub start_time: String,
     #[serde_as(as = "DisplayFromStr")]
-    pub open: f64,
+    pub open: Decimal,
     #[serde_as(as = "DisplayFromStr")]
-    pub high: f64,
+    pub high: Decimal,
     #[serde_as(as = "DisplayFromStr")]
-    pub low: f64,
+    pub low: Decimal,
     #[serde_as(as = "DisplayFromStr")]
-    pub close: f64,
+    pub close: Decimal,
+    /// Base volume, mark price buckets have none
+    #[serde(default)]
+    #[serde_as(as = "DisplayFromStr")]
+    pub volume: Decimal,
 }
 
 
@@ -70,7 +279,7 @@ pub struct WebsocketMessage {
     pub data: Option<Value>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Currency {
     pub symbol: String,
     #[serde(rename = "decimalPlaces")]
@@ -88,6 +297,7 @@ pub struct Currency {
     pub collateral_weight: String,
 }
 
+#[serde_as]
 #[derive(Deserialize, Debug, Clone)]
 pub struct CurrencyPair {
     pub symbol: String,
@@ -99,17 +309,23 @@ pub struct CurrencyPair {
     pub short_name: String,
     pub active: bool,
     #[serde(rename = "minBaseAmount")]
-    min_base_amount: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub min_base_amount: Decimal,
     #[serde(rename = "maxBaseAmount")]
-    max_base_amount: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub max_base_amount: Decimal,
     #[serde(rename = "minQuoteAmount")]
-    min_quote_amount: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub min_quote_amount: Decimal,
     #[serde(rename = "maxQuoteAmount")]
-    max_quote_amount: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub max_quote_amount: Decimal,
     #[serde(rename = "tickSize")]
-    tick_size: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub tick_size: Decimal,
     #[serde(rename = "baseDecimalPlaces")]
-    base_decimal_places:String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub base_decimal_places: u32,
     #[serde(rename = "marginTradingAllowed")]
     margin_trading_allowed: bool,
     #[serde(rename = "currencyPairType")]
@@ -122,24 +338,34 @@ pub struct CurrencyPair {
     auto_close_margin_fraction: Option<String>
 }
 
-#[derive(Deserialize, Debug)]
+#[serde_as]
+#[derive(Deserialize, Debug, Clone)]
 pub struct BalanceUpdate {
     pub currency: Currency,
-    pub available: String,
-    pub reserved: String,
-    pub total: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub available: Decimal,
+    #[serde_as(as = "DisplayFromStr")]
+    pub reserved: Decimal,
+    #[serde_as(as = "DisplayFromStr")]
+    pub total: Decimal,
     #[serde(rename = "updatedAt")]
     pub updated_at: String,
     #[serde(rename = "lendReserved")]
-    pub lend_reserved: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub lend_reserved: Decimal,
     #[serde(rename = "borrowReserved")]
-    pub borrow_reserved: Option<String>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub borrow_reserved: Option<Decimal>,
     #[serde(rename = "borrowedAmount")]
-    pub borrowed_amount: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub borrowed_amount: Decimal,
     #[serde(rename = "totalInReference")]
-    pub total_in_reference: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub total_in_reference: Decimal,
     #[serde(rename = "totalInReferenceWeighted")]
-    pub total_in_reference_weighted: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub total_in_reference_weighted: Decimal,
     #[serde(rename = "referenceCurrency")]
     pub reference_currency: String,
 }
@@ -188,28 +414,43 @@ pub struct TradePriceBucketUpdate {
     #[serde(rename = "startTime")]
     pub start_time: String,
     #[serde_as(as = "DisplayFromStr")]
-    pub open: f64,
+    pub open: Decimal,
     #[serde_as(as = "DisplayFromStr")]
-    pub high: f64,
+    pub high: Decimal,
     #[serde_as(as = "DisplayFromStr")]
-    pub low: f64,
+    pub low: Decimal,
     #[serde_as(as = "DisplayFromStr")]
-    pub close: f64,
+    pub close: Decimal,
     #[serde_as(as = "DisplayFromStr")]
-    pub volume: f64,
+    pub volume: Decimal,
     #[serde(rename = "quoteVolume")]
     #[serde_as(as = "DisplayFromStr")]
-    pub quote_volume: f64,
+    pub quote_volume: Decimal,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl TradePriceBucketUpdate {
+    pub fn to_candle(&self) -> MarkPriceBucket {
+        MarkPriceBucket {
+            currency_pair_symbol: self.currency_pair_symbol.clone(),
+            bucket_period_in_seconds: self.bucket_period_in_seconds,
+            start_time: self.start_time.clone(),
+            open: self.open,
+            high: self.high,
+            low: self.low,
+            close: self.close,
+            volume: self.volume,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct OrderLevelOrder {
     #[serde(rename = "orderId")]
     pub order_id: String,
     pub quantity: String,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct OrderLevel {
     #[serde(rename = "Price")]
     pub price: String,
@@ -225,7 +466,7 @@ pub struct OrderBookUpdate {
     pub data: OrderBookData,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct OrderBookData {
     #[serde(rename = "LastChange")]
     pub last_change: u64,
@@ -239,28 +480,63 @@ pub struct OrderBookData {
     pub checksum: u64,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FullOrderBookEntry {
+    pub side: String,
+    pub quantity: String,
+    pub price: String,
+    #[serde(rename = "currencyPair")]
+    pub currency_pair: String,
+    pub id: String,
+    #[serde(rename = "positionAtPrice")]
+    pub position_at_price: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FullOrderBookResponse {
+    #[serde(rename = "Asks")]
+    pub asks: Vec<FullOrderBookEntry>,
+    #[serde(rename = "Bids")]
+    pub bids: Vec<FullOrderBookEntry>,
+    #[serde(rename = "SequenceNumber")]
+    pub sn: u64,
+    #[serde(rename = "LastChange", default)]
+    pub last_change: Option<String>,
+    #[serde(rename = "Checksum", default)]
+    pub checksum: Option<u64>,
+}
+
+#[serde_as]
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Order {
     #[serde(rename = "orderId")]
     pub order_id: String,
-    pub side: String,
+    pub side: OrderSide,
     #[serde(rename = "remainingQuantity")]
-    pub remaining_quantity: Option<String>,
-    pub price: String,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub remaining_quantity: Option<Decimal>,
+    #[serde_as(as = "DisplayFromStr")]
+    pub price: Decimal,
     #[serde(rename = "currencyPair")]
     pub currency_pair: String,
     #[serde(rename = "createdAt")]
     pub created_at: String,
     #[serde(rename = "originalQuantity")]
-    pub original_quantity: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub original_quantity: Decimal,
     #[serde(rename = "filledPercentage")]
-    pub filled_percentage: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub filled_percentage: Decimal,
     #[serde(rename = "updatedAt")]
     pub updated_at: String,
-    pub status: String,
-    pub r#type: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub status: OrderStatus,
+    #[serde_as(as = "DisplayFromStr")]
+    pub r#type: OrderType,
     #[serde(rename = "timeInForce")]
-    pub time_in_force: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub time_in_force: TimeInForce,
     #[serde(rename = "allowMargin")]
     pub allow_margin: bool,
 }
@@ -269,3 +545,144 @@ pub struct Order {
 pub struct SubAccountResponse {
     pub id: String,
 }
+
+#[derive(Debug, Serialize, Clone)]
+pub struct LimitOrderRequest {
+    pub side: OrderSide,
+    pub quantity: String,
+    pub price: String,
+    pub pair: String,
+    #[serde(rename = "postOnly")]
+    pub post_only: bool,
+    #[serde(rename = "customerOrderId")]
+    pub customer_order_id: String,
+    #[serde(rename = "timeInForce")]
+    pub time_in_force: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct MarketOrderRequest {
+    pub side: OrderSide,
+    #[serde(rename = "baseAmount", skip_serializing_if = "Option::is_none")]
+    pub base_amount: Option<String>,
+    #[serde(rename = "quoteAmount", skip_serializing_if = "Option::is_none")]
+    pub quote_amount: Option<String>,
+    pub pair: String,
+    #[serde(rename = "customerOrderId")]
+    pub customer_order_id: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CancelOrderRequest {
+    #[serde(rename = "orderId")]
+    pub order_id: String,
+    pub pair: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ModifyOrderRequest {
+    #[serde(rename = "orderId")]
+    pub order_id: String,
+    pub pair: String,
+    #[serde(rename = "modifyMatchStrategy")]
+    pub modify_match_strategy: String,
+    #[serde(rename = "newPrice")]
+    pub new_price: String,
+    #[serde(rename = "newTotalQuantity")]
+    pub new_total_quantity: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct OrderResponse {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct OrderProcessed {
+    #[serde(rename = "orderId")]
+    pub order_id: String,
+    pub success: bool,
+    #[serde(rename = "failureReason", default)]
+    pub failure_reason: Option<String>,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize, Clone)]
+pub struct OrderStatusUpdate {
+    #[serde(rename = "orderId")]
+    pub order_id: String,
+    #[serde(rename = "orderStatusType")]
+    #[serde_as(as = "DisplayFromStr")]
+    pub order_status_type: OrderStatus,
+    #[serde(rename = "currencyPair")]
+    pub currency_pair: String,
+    #[serde(rename = "originalPrice")]
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub original_price: Option<Decimal>,
+    #[serde(rename = "remainingQuantity")]
+    #[serde_as(as = "DisplayFromStr")]
+    pub remaining_quantity: Decimal,
+    #[serde(rename = "originalQuantity")]
+    #[serde_as(as = "DisplayFromStr")]
+    pub original_quantity: Decimal,
+    #[serde(rename = "orderSide")]
+    pub order_side: OrderSide,
+    #[serde(rename = "orderType")]
+    #[serde_as(as = "DisplayFromStr")]
+    pub order_type: OrderType,
+    #[serde(rename = "failedReason", default)]
+    pub failed_reason: Option<String>,
+    #[serde(rename = "orderUpdatedAt")]
+    pub order_updated_at: String,
+    #[serde(rename = "orderCreatedAt")]
+    pub order_created_at: String,
+    #[serde(rename = "customerOrderId", default)]
+    pub customer_order_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FailedCancelOrder {
+    #[serde(rename = "orderId")]
+    pub order_id: String,
+    pub message: String,
+}
+
+/// A fill of one of the account's own orders
+#[serde_as]
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AccountTrade {
+    #[serde_as(as = "DisplayFromStr")]
+    pub price: Decimal,
+    #[serde_as(as = "DisplayFromStr")]
+    pub quantity: Decimal,
+    #[serde(rename = "currencyPair")]
+    pub currency_pair: String,
+    #[serde(rename = "tradedAt")]
+    pub traded_at: String,
+    pub side: OrderSide,
+    #[serde(rename = "orderId")]
+    pub order_id: String,
+    pub id: String,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub fee: Option<Decimal>,
+    #[serde(rename = "feeCurrency", default)]
+    pub fee_currency: Option<String>,
+}
+
+/// A public trade on the pair
+#[serde_as]
+#[derive(Debug, Deserialize, Clone)]
+pub struct Trade {
+    #[serde_as(as = "DisplayFromStr")]
+    pub price: Decimal,
+    #[serde_as(as = "DisplayFromStr")]
+    pub quantity: Decimal,
+    #[serde(rename = "currencyPair")]
+    pub currency_pair: String,
+    #[serde(rename = "tradedAt")]
+    pub traded_at: String,
+    #[serde(rename = "takerSide")]
+    pub taker_side: OrderSide,
+}
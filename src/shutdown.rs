@@ -0,0 +1,87 @@
+use std::fs;
+
+use chrono::Utc;
+use log::error;
+use serde::Serialize;
+
+use crate::order_manager::OrderManager;
+use crate::position::{Position, PositionTracker};
+use crate::rusty_bot_models::{AccountTrade, Order};
+
+/// What the bot knew about the account when it stopped
+#[derive(Debug, Serialize)]
+pub struct BotState {
+    #[serde(rename = "savedAt")]
+    pub saved_at: String,
+    pub positions: Vec<Position>,
+    #[serde(rename = "openOrders")]
+    pub open_orders: Vec<Order>,
+    #[serde(rename = "accountTrades")]
+    pub account_trades: Vec<AccountTrade>,
+}
+
+impl BotState {
+    pub fn new(
+        positions: &PositionTracker,
+        orders: &OrderManager,
+        account_trades: &[AccountTrade],
+    ) -> Self {
+        let mut positions: Vec<Position> = positions.positions().cloned().collect();
+        positions.sort_by(|a, b| a.currency_pair.cmp(&b.currency_pair));
+        BotState {
+            saved_at: Utc::now().to_rfc3339(),
+            positions,
+            open_orders: orders.open_orders().to_vec(),
+            account_trades: account_trades.to_vec(),
+        }
+    }
+}
+
+pub fn persist_state(state_file: &str, state: &BotState) -> std::io::Result<()> {
+    let contents = serde_json::to_string_pretty(state)?;
+    fs::write(state_file, contents)
+}
+
+/// Resolves on Ctrl+C, or SIGTERM on unix
+pub async fn wait_for_shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Error listening for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("Error listening for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Resolves on SIGUSR1 on unix, which the operator sends with `kill -USR1 <pid>` to halt
+/// trading without stopping the bot. Never resolves elsewhere.
+pub async fn wait_for_kill_switch_signal() {
+    #[cfg(unix)]
+    match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::user_defined1()) {
+        Ok(mut signal) => {
+            signal.recv().await;
+            return;
+        }
+        Err(e) => error!("Error listening for SIGUSR1: {}", e),
+    }
+    std::future::pending::<()>().await;
+}
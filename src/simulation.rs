@@ -0,0 +1,371 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use tokio::sync::Mutex;
+
+use crate::orders::{OrderError, OrderExecutor};
+use crate::rusty_bot_models::{
+    AccountTrade, BalanceUpdate, Currency, CurrencyPair, MarkPriceBucket, Order, OrderSide,
+    OrderStatus, OrderType, TimeInForce,
+};
+use crate::sizing::{size_limit_order, size_market_order};
+
+#[derive(Debug, Clone)]
+pub struct SimulatedOrder {
+    pub order_id: String,
+    pub side: OrderSide,
+    /// None for market orders
+    pub price: Option<Decimal>,
+    /// Base quantity, or the quote amount for market buys
+    pub quantity: Decimal,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SimulatedTrade {
+    pub id: String,
+    pub order_id: String,
+    pub side: OrderSide,
+    pub price: Decimal,
+    pub quantity: Decimal,
+    pub fee: Decimal,
+    pub traded_at: String,
+}
+
+impl SimulatedTrade {
+    /// The fill as VALR would report it in a NEW_ACCOUNT_TRADE
+    pub fn to_account_trade(&self, currency_pair: &CurrencyPair) -> AccountTrade {
+        AccountTrade {
+            price: self.price,
+            quantity: self.quantity,
+            currency_pair: currency_pair.symbol.clone(),
+            traded_at: self.traded_at.clone(),
+            side: self.side,
+            order_id: self.order_id.clone(),
+            id: self.id.clone(),
+            fee: Some(self.fee),
+            fee_currency: Some(currency_pair.quote_currency.clone()),
+        }
+    }
+}
+
+/// Balances, resting orders and fills of an account that never touches VALR.
+/// Fees are charged in the quote currency for both sides.
+#[derive(Debug)]
+pub struct SimulatedAccount {
+    pub currency_pair: CurrencyPair,
+    pub base_balance: Decimal,
+    pub quote_balance: Decimal,
+    pub fee_rate: Decimal,
+    pub open_orders: Vec<SimulatedOrder>,
+    pub trades: Vec<SimulatedTrade>,
+    pub fees_paid: Decimal,
+    pub current_time: String,
+    next_order_id: u64,
+    next_trade_id: u64,
+}
+
+impl SimulatedAccount {
+    pub fn new(
+        currency_pair: CurrencyPair,
+        quote_balance: Decimal,
+        base_balance: Decimal,
+        fee_rate: Decimal,
+    ) -> Self {
+        SimulatedAccount {
+            currency_pair,
+            base_balance,
+            quote_balance,
+            fee_rate,
+            open_orders: vec![],
+            trades: vec![],
+            fees_paid: Decimal::ZERO,
+            current_time: String::new(),
+            next_order_id: 1,
+            next_trade_id: 1,
+        }
+    }
+
+    pub fn submit_order(
+        &mut self,
+        side: OrderSide,
+        price: Option<Decimal>,
+        quantity: Decimal,
+    ) -> String {
+        let order_id = format!("simulated-{}", self.next_order_id);
+        self.next_order_id += 1;
+        self.open_orders.push(SimulatedOrder {
+            order_id: order_id.clone(),
+            side,
+            price,
+            quantity,
+            created_at: self.current_time.clone(),
+        });
+        order_id
+    }
+
+    /// Returns false when no such order is open
+    pub fn cancel_order(&mut self, order_id: &str) -> bool {
+        let open_orders = self.open_orders.len();
+        self.open_orders.retain(|o| o.order_id != order_id);
+        self.open_orders.len() != open_orders
+    }
+
+    /// Only resting limit orders can be modified, returns false for anything else
+    pub fn modify_order(&mut self, order_id: &str, price: Decimal, quantity: Decimal) -> bool {
+        match self
+            .open_orders
+            .iter_mut()
+            .find(|o| o.order_id == order_id && o.price.is_some())
+        {
+            Some(order) => {
+                order.price = Some(price);
+                order.quantity = quantity;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Fills the open orders that the candle traded through. Market orders fill at the open,
+    /// limit orders at their price or the open when it gapped past them.
+    pub fn match_candle(&mut self, candle: &MarkPriceBucket) -> Vec<SimulatedTrade> {
+        self.current_time = candle.start_time.clone();
+        self.match_orders(|order| match order.price {
+            None => Some((candle.open, Decimal::MAX)),
+            Some(price) if order.side == OrderSide::Buy && candle.low <= price => {
+                Some((price.min(candle.open), Decimal::MAX))
+            }
+            Some(price) if order.side == OrderSide::Sell && candle.high >= price => {
+                Some((price.max(candle.open), Decimal::MAX))
+            }
+            Some(_) => None,
+        })
+    }
+
+    /// `fill` returns the price and the most base quantity an order can fill for, or None to
+    /// leave it resting. Limit orders cut short by that quantity keep resting for the remainder,
+    /// market orders and orders cut short by the balance are done after one fill.
+    pub fn match_orders<F>(&mut self, mut fill: F) -> Vec<SimulatedTrade>
+    where
+        F: FnMut(&SimulatedOrder) -> Option<(Decimal, Decimal)>,
+    {
+        let mut trades = vec![];
+        let mut remaining = vec![];
+        for mut order in std::mem::take(&mut self.open_orders) {
+            match fill(&order) {
+                None => remaining.push(order),
+                Some((price, available_quantity)) => {
+                    let filled_quantity = match self.fill(&order, price, available_quantity) {
+                        None => Decimal::ZERO,
+                        Some(trade) => {
+                            let quantity = trade.quantity;
+                            trades.push(trade);
+                            quantity
+                        }
+                    };
+                    order.quantity -= filled_quantity;
+                    if order.price.is_some()
+                        && filled_quantity == available_quantity
+                        && order.quantity > Decimal::ZERO
+                    {
+                        remaining.push(order);
+                    }
+                }
+            }
+        }
+        self.open_orders = remaining;
+        self.trades.extend(trades.iter().cloned());
+        trades
+    }
+
+    /// Orders larger than the available balance are filled for what the account can afford
+    fn fill(
+        &mut self,
+        order: &SimulatedOrder,
+        price: Decimal,
+        available_quantity: Decimal,
+    ) -> Option<SimulatedTrade> {
+        if price <= Decimal::ZERO {
+            return None;
+        }
+        let requested_quantity = match (order.side, order.price) {
+            (OrderSide::Buy, None) => order.quantity / price,
+            _ => order.quantity,
+        };
+        let quantity = if order.side == OrderSide::Buy {
+            requested_quantity.min(self.quote_balance / (price * (Decimal::ONE + self.fee_rate)))
+        } else {
+            requested_quantity.min(self.base_balance)
+        }
+        .min(available_quantity);
+        if quantity <= Decimal::ZERO {
+            return None;
+        }
+
+        let notional = price * quantity;
+        let fee = notional * self.fee_rate;
+        if order.side == OrderSide::Buy {
+            self.base_balance += quantity;
+            self.quote_balance -= notional + fee;
+        } else {
+            self.base_balance -= quantity;
+            self.quote_balance += notional - fee;
+        }
+        self.fees_paid += fee;
+        let id = format!("simulated-trade-{}", self.next_trade_id);
+        self.next_trade_id += 1;
+
+        Some(SimulatedTrade {
+            id,
+            order_id: order.order_id.clone(),
+            side: order.side,
+            price,
+            quantity,
+            fee,
+            traded_at: self.current_time.clone(),
+        })
+    }
+
+    pub fn equity(&self, mark_price: Decimal) -> Decimal {
+        self.quote_balance + self.base_balance * mark_price
+    }
+
+    pub fn balance_updates(&self) -> Vec<BalanceUpdate> {
+        vec![
+            simulated_balance_update(
+                &self.currency_pair.base_currency,
+                self.base_balance,
+                &self.current_time,
+            ),
+            simulated_balance_update(
+                &self.currency_pair.quote_currency,
+                self.quote_balance,
+                &self.current_time,
+            ),
+        ]
+    }
+
+    pub fn orders(&self) -> Vec<Order> {
+        self.open_orders
+            .iter()
+            .map(|o| Order {
+                order_id: o.order_id.clone(),
+                side: o.side,
+                remaining_quantity: Some(o.quantity),
+                price: o.price.unwrap_or(Decimal::ZERO),
+                currency_pair: self.currency_pair.symbol.clone(),
+                created_at: o.created_at.clone(),
+                original_quantity: o.quantity,
+                filled_percentage: Decimal::ZERO,
+                updated_at: self.current_time.clone(),
+                status: OrderStatus::Placed,
+                r#type: if o.price.is_some() {
+                    OrderType::Limit
+                } else {
+                    OrderType::Market
+                },
+                time_in_force: TimeInForce::GoodTillCancelled,
+                allow_margin: false,
+            })
+            .collect()
+    }
+}
+
+fn simulated_balance_update(symbol: &str, amount: Decimal, updated_at: &str) -> BalanceUpdate {
+    BalanceUpdate {
+        currency: Currency {
+            symbol: symbol.to_string(),
+            decimal_places: 8,
+            is_active: true,
+            short_name: symbol.to_string(),
+            long_name: symbol.to_string(),
+            supported_withdraw_decimal_places: 8,
+            collateral: false,
+            collateral_weight: String::from("0"),
+        },
+        available: amount,
+        reserved: Decimal::ZERO,
+        total: amount,
+        updated_at: updated_at.to_string(),
+        lend_reserved: Decimal::ZERO,
+        borrow_reserved: None,
+        borrowed_amount: Decimal::ZERO,
+        total_in_reference: Decimal::ZERO,
+        total_in_reference_weighted: Decimal::ZERO,
+        reference_currency: String::from("USDC"),
+    }
+}
+
+/// Routes strategy orders into a simulated account instead of VALR
+pub struct SimulatedOrderExecutor {
+    pub account: Arc<Mutex<SimulatedAccount>>,
+}
+
+#[async_trait]
+impl OrderExecutor for SimulatedOrderExecutor {
+    async fn place_limit_order(
+        &self,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<String, OrderError> {
+        let sized = size_limit_order(side, price, quantity, currency_pair)?;
+        let mut account = self.account.lock().await;
+        Ok(account.submit_order(side, Some(sized.price), sized.quantity))
+    }
+
+    async fn place_market_order(
+        &self,
+        side: OrderSide,
+        amount: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<String, OrderError> {
+        let amount = size_market_order(side, amount, currency_pair)?;
+        let mut account = self.account.lock().await;
+        Ok(account.submit_order(side, None, amount))
+    }
+
+    async fn cancel_order(
+        &self,
+        order_id: &str,
+        currency_pair: &CurrencyPair,
+    ) -> Result<(), OrderError> {
+        if self.account.lock().await.cancel_order(order_id) {
+            Ok(())
+        } else {
+            Err(OrderError::Rejected(format!("order {} is not open", order_id)))
+        }
+    }
+
+    async fn cancel_all_orders(&self, currency_pair: &CurrencyPair) -> Result<(), OrderError> {
+        self.account.lock().await.open_orders.clear();
+        Ok(())
+    }
+
+    async fn modify_order(
+        &self,
+        order_id: &str,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        currency_pair: &CurrencyPair,
+    ) -> Result<(), OrderError> {
+        let sized = size_limit_order(side, price, quantity, currency_pair)?;
+        if self
+            .account
+            .lock()
+            .await
+            .modify_order(order_id, sized.price, sized.quantity)
+        {
+            Ok(())
+        } else {
+            Err(OrderError::Rejected(format!(
+                "order {} is not a resting limit order",
+                order_id
+            )))
+        }
+    }
+}
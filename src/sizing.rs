@@ -0,0 +1,158 @@
+use std::fmt::{Display, Formatter};
+
+use log::warn;
+use rust_decimal::{Decimal, RoundingStrategy};
+
+use crate::rusty_bot_models::{CurrencyPair, OrderSide};
+
+#[derive(Debug, PartialEq)]
+pub enum SizingError {
+    InvalidPrice(Decimal),
+    InvalidQuantity(Decimal),
+    BelowMinBaseAmount { quantity: Decimal, min: Decimal },
+    BelowMinQuoteAmount { amount: Decimal, min: Decimal },
+}
+
+impl Display for SizingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SizingError::InvalidPrice(price) => write!(f, "invalid price {}", price),
+            SizingError::InvalidQuantity(quantity) => {
+                write!(f, "invalid quantity {}", quantity)
+            }
+            SizingError::BelowMinBaseAmount { quantity, min } => write!(
+                f,
+                "quantity {} is below the minimum base amount {}",
+                quantity, min
+            ),
+            SizingError::BelowMinQuoteAmount { amount, min } => write!(
+                f,
+                "quote amount {} is below the minimum quote amount {}",
+                amount, min
+            ),
+        }
+    }
+}
+
+/// Limit order price and quantity after applying the pair's trading rules
+#[derive(Debug, PartialEq)]
+pub struct SizedOrder {
+    pub price: Decimal,
+    pub quantity: Decimal,
+}
+
+/// Rounds to the tick size without making the order more aggressive: buys round down, sells up
+pub fn round_to_tick(side: OrderSide, price: Decimal, tick_size: Decimal) -> Decimal {
+    if tick_size <= Decimal::ZERO {
+        return price;
+    }
+    let ticks = price / tick_size;
+    let ticks = if side == OrderSide::Buy {
+        ticks.floor()
+    } else {
+        ticks.ceil()
+    };
+    (ticks * tick_size).normalize()
+}
+
+/// Rounds the price to tick, truncates the quantity to the base decimal places and clamps it to
+/// the maximum base and quote amounts. Orders that end up below a minimum are rejected.
+pub fn size_limit_order(
+    side: OrderSide,
+    price: Decimal,
+    quantity: Decimal,
+    currency_pair: &CurrencyPair,
+) -> Result<SizedOrder, SizingError> {
+    if price <= Decimal::ZERO {
+        return Err(SizingError::InvalidPrice(price));
+    }
+    if quantity <= Decimal::ZERO {
+        return Err(SizingError::InvalidQuantity(quantity));
+    }
+    let price = round_to_tick(side, price, currency_pair.tick_size);
+    if price <= Decimal::ZERO {
+        return Err(SizingError::InvalidPrice(price));
+    }
+
+    let mut sized_quantity = truncate_base(quantity, currency_pair);
+    if sized_quantity > currency_pair.max_base_amount {
+        sized_quantity = currency_pair.max_base_amount;
+    }
+    let max_quantity_for_quote =
+        truncate_base(currency_pair.max_quote_amount / price, currency_pair);
+    if sized_quantity > max_quantity_for_quote {
+        sized_quantity = max_quantity_for_quote;
+    }
+    if sized_quantity != quantity {
+        warn!(
+            "{} {} quantity {} adjusted to {} to fit the pair's trading rules",
+            currency_pair.symbol, side, quantity, sized_quantity
+        );
+    }
+
+    if sized_quantity < currency_pair.min_base_amount {
+        return Err(SizingError::BelowMinBaseAmount {
+            quantity: sized_quantity,
+            min: currency_pair.min_base_amount,
+        });
+    }
+    let quote_amount = price * sized_quantity;
+    if quote_amount < currency_pair.min_quote_amount {
+        return Err(SizingError::BelowMinQuoteAmount {
+            amount: quote_amount,
+            min: currency_pair.min_quote_amount,
+        });
+    }
+    Ok(SizedOrder {
+        price,
+        quantity: sized_quantity,
+    })
+}
+
+/// Buys are sized in the quote currency and sells in the base currency, as VALR expects
+pub fn size_market_order(
+    side: OrderSide,
+    amount: Decimal,
+    currency_pair: &CurrencyPair,
+) -> Result<Decimal, SizingError> {
+    if amount <= Decimal::ZERO {
+        return Err(SizingError::InvalidQuantity(amount));
+    }
+    let (sized_amount, min) = if side == OrderSide::Buy {
+        (
+            amount.min(currency_pair.max_quote_amount),
+            currency_pair.min_quote_amount,
+        )
+    } else {
+        (
+            truncate_base(amount, currency_pair).min(currency_pair.max_base_amount),
+            currency_pair.min_base_amount,
+        )
+    };
+    if sized_amount != amount {
+        warn!(
+            "{} {} amount {} adjusted to {} to fit the pair's trading rules",
+            currency_pair.symbol, side, amount, sized_amount
+        );
+    }
+    if sized_amount < min {
+        return Err(if side == OrderSide::Buy {
+            SizingError::BelowMinQuoteAmount {
+                amount: sized_amount,
+                min,
+            }
+        } else {
+            SizingError::BelowMinBaseAmount {
+                quantity: sized_amount,
+                min,
+            }
+        });
+    }
+    Ok(sized_amount)
+}
+
+fn truncate_base(quantity: Decimal, currency_pair: &CurrencyPair) -> Decimal {
+    quantity
+        .round_dp_with_strategy(currency_pair.base_decimal_places, RoundingStrategy::ToZero)
+        .normalize()
+}
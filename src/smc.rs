@@ -0,0 +1,384 @@
+use rust_decimal::Decimal;
+
+use crate::rusty_bot_models::MarkPriceBucket;
+#[cfg(test)]
+use crate::strategies::break_of_structure::{find_swings, structure_breaks};
+use crate::strategies::break_of_structure::{swings_at, Direction, Swing, SwingKind};
+
+/// Three candles where the first and last do not overlap, leaving a gap the middle candle
+/// moved through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FairValueGap {
+    /// Index of the third candle, which confirms the gap
+    pub index: usize,
+    pub direction: Direction,
+    pub lower: Decimal,
+    pub upper: Decimal,
+}
+
+/// The last opposite candle before the move that broke structure, a bullish block is the last
+/// down candle before a break up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderBlock {
+    /// Index of the candle that broke structure and confirms the block
+    pub index: usize,
+    pub block_index: usize,
+    pub direction: Direction,
+    pub lower: Decimal,
+    pub upper: Decimal,
+}
+
+/// A wick through a swing that closes back inside it. Sweeping a high is bearish, sweeping a
+/// low bullish.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiquiditySweep {
+    pub index: usize,
+    pub direction: Direction,
+    pub swing_index: usize,
+    pub level: Decimal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmcEvent {
+    FairValueGap(FairValueGap),
+    OrderBlock(OrderBlock),
+    LiquiditySweep(LiquiditySweep),
+}
+
+impl SmcEvent {
+    /// Index of the candle that confirmed the event
+    pub fn index(&self) -> usize {
+        match self {
+            SmcEvent::FairValueGap(gap) => gap.index,
+            SmcEvent::OrderBlock(block) => block.index,
+            SmcEvent::LiquiditySweep(sweep) => sweep.index,
+        }
+    }
+
+    pub fn direction(&self) -> Direction {
+        match self {
+            SmcEvent::FairValueGap(gap) => gap.direction,
+            SmcEvent::OrderBlock(block) => block.direction,
+            SmcEvent::LiquiditySweep(sweep) => sweep.direction,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SmcEvent::FairValueGap(_) => "fair value gap",
+            SmcEvent::OrderBlock(_) => "order block",
+            SmcEvent::LiquiditySweep(_) => "liquidity sweep",
+        }
+    }
+}
+
+pub fn fair_value_gaps(candles: &[MarkPriceBucket]) -> Vec<FairValueGap> {
+    candles
+        .windows(3)
+        .enumerate()
+        .filter_map(|(first, window)| {
+            let (before, after) = (&window[0], &window[2]);
+            let (direction, lower, upper) = if before.high < after.low {
+                (Direction::Bullish, before.high, after.low)
+            } else if before.low > after.high {
+                (Direction::Bearish, after.high, before.low)
+            } else {
+                return None;
+            };
+            Some(FairValueGap {
+                index: first + 2,
+                direction,
+                lower,
+                upper,
+            })
+        })
+        .collect()
+}
+
+/// One block per structure break, none when every candle of the move went the same way
+#[cfg(test)]
+pub fn order_blocks(candles: &[MarkPriceBucket], swing_width: usize) -> Vec<OrderBlock> {
+    structure_breaks(candles, swing_width)
+        .into_iter()
+        .filter_map(|structure_break| {
+            let is_opposite = |candle: &MarkPriceBucket| match structure_break.direction {
+                Direction::Bullish => candle.close < candle.open,
+                Direction::Bearish => candle.close > candle.open,
+            };
+            let block_index = (structure_break.swing_index + 1..structure_break.index)
+                .rev()
+                .find(|index| is_opposite(&candles[*index]))?;
+            let block = &candles[block_index];
+            Some(OrderBlock {
+                index: structure_break.index,
+                block_index,
+                direction: structure_break.direction,
+                lower: block.low,
+                upper: block.high,
+            })
+        })
+        .collect()
+}
+
+/// Walks the candles tracking the last confirmed swing high and low like `structure_breaks`.
+/// A swing that is swept or closed through is not swept again.
+#[cfg(test)]
+pub fn liquidity_sweeps(candles: &[MarkPriceBucket], swing_width: usize) -> Vec<LiquiditySweep> {
+    let swings = find_swings(candles, swing_width);
+    let mut swing_high: Option<Swing> = None;
+    let mut swing_low: Option<Swing> = None;
+    let mut sweeps = vec![];
+    for (index, candle) in candles.iter().enumerate() {
+        for swing in swings.iter().filter(|s| s.index + swing_width == index) {
+            match swing.kind {
+                SwingKind::High => swing_high = Some(*swing),
+                SwingKind::Low => swing_low = Some(*swing),
+            }
+        }
+
+        if let Some(high) = swing_high.filter(|high| candle.high > high.price) {
+            swing_high = None;
+            if candle.close <= high.price {
+                sweeps.push(sweep(index, Direction::Bearish, high));
+            }
+        }
+        if let Some(low) = swing_low.filter(|low| candle.low < low.price) {
+            swing_low = None;
+            if candle.close >= low.price {
+                sweeps.push(sweep(index, Direction::Bullish, low));
+            }
+        }
+    }
+    sweeps
+}
+
+fn sweep(index: usize, direction: Direction, swing: Swing) -> LiquiditySweep {
+    LiquiditySweep {
+        index,
+        direction,
+        swing_index: swing.index,
+        level: swing.price,
+    }
+}
+
+/// Every fair value gap, order block and liquidity sweep in the candles, in the order they were
+/// confirmed. Live candles go through `SmcDetector`, which the tests check against this.
+#[cfg(test)]
+pub fn smc_events(candles: &[MarkPriceBucket], swing_width: usize) -> Vec<SmcEvent> {
+    let mut events: Vec<SmcEvent> = fair_value_gaps(candles)
+        .into_iter()
+        .map(SmcEvent::FairValueGap)
+        .chain(
+            order_blocks(candles, swing_width)
+                .into_iter()
+                .map(SmcEvent::OrderBlock),
+        )
+        .chain(
+            liquidity_sweeps(candles, swing_width)
+                .into_iter()
+                .map(SmcEvent::LiquiditySweep),
+        )
+        .collect();
+    events.sort_by_key(|event| event.index());
+    events
+}
+
+/// Reports each event once, as the candle confirming it closes. Called with a timeframe's
+/// candles, the last of which is still forming. The first call only takes in the history, so
+/// events in it are not reported.
+///
+/// Works through the newly closed candles one at a time, keeping the swings still to be broken
+/// or swept and the candles an order block can still be found in, no more than were passed in.
+#[derive(Debug, Clone)]
+pub struct SmcDetector {
+    swing_width: usize,
+    last_closed: Option<String>,
+    window: Vec<MarkPriceBucket>,
+    /// Number of candles seen before the first one in the window
+    first_index: usize,
+    structure_high: Option<Swing>,
+    structure_low: Option<Swing>,
+    sweep_high: Option<Swing>,
+    sweep_low: Option<Swing>,
+}
+
+impl SmcDetector {
+    pub fn new(swing_width: usize) -> Self {
+        SmcDetector {
+            swing_width,
+            last_closed: None,
+            window: vec![],
+            first_index: 0,
+            structure_high: None,
+            structure_low: None,
+            sweep_high: None,
+            sweep_low: None,
+        }
+    }
+
+    /// Forgets the candles seen, the next call takes in the history again
+    pub fn reset(&mut self) {
+        *self = SmcDetector::new(self.swing_width);
+    }
+
+    /// Events confirmed by the candles that closed since the last call, with indexes into
+    /// `candles`. Starts over from `candles` when they no longer reach back to the last call.
+    pub fn new_events(&mut self, candles: &[MarkPriceBucket]) -> Vec<SmcEvent> {
+        let closed = &candles[..candles.len().saturating_sub(1)];
+        let Some(latest) = closed.last() else {
+            return vec![];
+        };
+        let first_new = self
+            .last_closed
+            .as_ref()
+            .and_then(|start| closed.iter().rposition(|c| c.start_time == *start))
+            .map(|index| index + 1);
+        let baseline = first_new.is_none();
+        if baseline {
+            self.reset();
+        }
+        self.last_closed = Some(latest.start_time.clone());
+
+        let mut events = vec![];
+        for candle in &closed[first_new.unwrap_or(0)..] {
+            events.extend(self.push(candle.clone(), closed.len()));
+        }
+        if baseline {
+            return vec![];
+        }
+        // the newest candle seen is the last closed one
+        let offset = self.first_index + self.window.len() - closed.len();
+        events
+            .into_iter()
+            .filter_map(|event| shift(event, offset))
+            .collect()
+    }
+
+    /// Takes in the next closed candle and returns the events it confirmed, indexed from the
+    /// first candle seen. At most `limit` candles are kept.
+    fn push(&mut self, candle: MarkPriceBucket, limit: usize) -> Vec<SmcEvent> {
+        let index = self.first_index + self.window.len();
+        self.window.push(candle);
+        if self.window.len() > limit {
+            let dropped = self.window.len() - limit;
+            self.window.drain(..dropped);
+            self.first_index += dropped;
+            let first_index = self.first_index;
+            for swing in [
+                &mut self.structure_high,
+                &mut self.structure_low,
+                &mut self.sweep_high,
+                &mut self.sweep_low,
+            ] {
+                *swing = swing.filter(|swing| swing.index >= first_index);
+            }
+        }
+        let width = self.swing_width;
+        let position = self.window.len() - 1;
+        let candle = &self.window[position];
+        let mut events = vec![];
+
+        if position >= 2 {
+            events.extend(
+                fair_value_gaps(&self.window[position - 2..])
+                    .into_iter()
+                    .map(|gap| SmcEvent::FairValueGap(FairValueGap { index, ..gap })),
+            );
+        }
+
+        // the candle confirms the swing `width` candles back
+        if position >= width {
+            for swing in swings_at(&self.window, position - width, width) {
+                let swing = Swing {
+                    index: swing.index + self.first_index,
+                    ..swing
+                };
+                match swing.kind {
+                    SwingKind::High => {
+                        self.structure_high = Some(swing);
+                        self.sweep_high = Some(swing);
+                    }
+                    SwingKind::Low => {
+                        self.structure_low = Some(swing);
+                        self.sweep_low = Some(swing);
+                    }
+                }
+            }
+        }
+
+        let broken = match (self.structure_high, self.structure_low) {
+            (Some(high), _) if candle.close > high.price => {
+                self.structure_high = None;
+                Some((Direction::Bullish, high))
+            }
+            (_, Some(low)) if candle.close < low.price => {
+                self.structure_low = None;
+                Some((Direction::Bearish, low))
+            }
+            _ => None,
+        };
+        if let Some((direction, swing)) = broken {
+            let is_opposite = |candle: &MarkPriceBucket| match direction {
+                Direction::Bullish => candle.close < candle.open,
+                Direction::Bearish => candle.close > candle.open,
+            };
+            let block_position = (swing.index + 1 - self.first_index..position)
+                .rev()
+                .find(|position| is_opposite(&self.window[*position]));
+            if let Some(block_position) = block_position {
+                let block = &self.window[block_position];
+                events.push(SmcEvent::OrderBlock(OrderBlock {
+                    index,
+                    block_index: block_position + self.first_index,
+                    direction,
+                    lower: block.low,
+                    upper: block.high,
+                }));
+            }
+        }
+
+        if let Some(high) = self.sweep_high.filter(|high| candle.high > high.price) {
+            self.sweep_high = None;
+            if candle.close <= high.price {
+                events.push(SmcEvent::LiquiditySweep(sweep(
+                    index,
+                    Direction::Bearish,
+                    high,
+                )));
+            }
+        }
+        if let Some(low) = self.sweep_low.filter(|low| candle.low < low.price) {
+            self.sweep_low = None;
+            if candle.close >= low.price {
+                events.push(SmcEvent::LiquiditySweep(sweep(
+                    index,
+                    Direction::Bullish,
+                    low,
+                )));
+            }
+        }
+        events
+    }
+}
+
+/// Moves the event's indexes `offset` candles earlier, None when the swing or block it refers
+/// to is no longer in the candles. Candles that close together are pushed one at a time, so an
+/// event of the first can refer to a candle the later ones pushed out.
+fn shift(event: SmcEvent, offset: usize) -> Option<SmcEvent> {
+    Some(match event {
+        SmcEvent::FairValueGap(gap) => SmcEvent::FairValueGap(FairValueGap {
+            index: gap.index.checked_sub(offset)?,
+            ..gap
+        }),
+        SmcEvent::OrderBlock(block) => SmcEvent::OrderBlock(OrderBlock {
+            index: block.index.checked_sub(offset)?,
+            block_index: block.block_index.checked_sub(offset)?,
+            ..block
+        }),
+        SmcEvent::LiquiditySweep(sweep) => SmcEvent::LiquiditySweep(LiquiditySweep {
+            index: sweep.index.checked_sub(offset)?,
+            swing_index: sweep.swing_index.checked_sub(offset)?,
+            ..sweep
+        }),
+    })
+}
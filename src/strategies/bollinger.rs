@@ -0,0 +1,249 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use colored::Colorize;
+use log::{error, warn};
+use rust_decimal::Decimal;
+
+use crate::candles::Timeframe;
+use crate::config::BollingerConfig;
+use crate::indicators::{Bands, BollingerBands, Indicator};
+use crate::rusty_bot_models::{
+    AccountTrade, MarkPriceBucket, OrderSide, OrderStatus, OrderStatusUpdate,
+};
+use crate::strategies::{available_balance, Strategy, StrategyContext};
+
+const BANDS_INDICATOR: &str = "bollinger";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// The close fell below the lower band
+    Enter,
+    /// The close got back to the middle band
+    TakeProfit,
+    /// The close fell the stop distance below the entry
+    StopLoss,
+}
+
+/// What to do at the close, given the entry price of the open position if there is one
+pub fn mean_reversion_signal(
+    close: Decimal,
+    bands: Bands,
+    entry_price: Option<Decimal>,
+    stop_percent: Decimal,
+) -> Option<Signal> {
+    match entry_price {
+        None if close < bands.lower => Some(Signal::Enter),
+        None => None,
+        Some(entry_price) => {
+            let stop_price = entry_price * (Decimal::ONE - stop_percent / Decimal::ONE_HUNDRED);
+            if close <= stop_price {
+                Some(Signal::StopLoss)
+            } else if close >= bands.middle {
+                Some(Signal::TakeProfit)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// The long opened by an entry order, built up from its fills
+#[derive(Debug)]
+struct Entry {
+    order_id: String,
+    /// Base received, net of fees charged in base
+    quantity: Decimal,
+    notional: Decimal,
+}
+
+impl Entry {
+    /// None until the entry order fills
+    fn price(&self) -> Option<Decimal> {
+        if self.quantity > Decimal::ZERO {
+            Some(self.notional / self.quantity)
+        } else {
+            None
+        }
+    }
+}
+
+/// Buys when the close falls below the lower Bollinger band and sells the base it bought back
+/// at the middle band, or when the close falls the stop distance below the fill price. Long
+/// only, as spot accounts cannot short the upper band. The counterpart to the trend following
+/// BOS strategy.
+pub struct BollingerMeanReversion {
+    config: BollingerConfig,
+    entry: Option<Entry>,
+}
+
+impl BollingerMeanReversion {
+    pub fn new(config: BollingerConfig) -> Self {
+        BollingerMeanReversion {
+            config,
+            entry: None,
+        }
+    }
+
+    async fn enter(&mut self, close: Decimal, context: &StrategyContext) {
+        let pair = &context.currency_pair;
+        let quote_available =
+            available_balance(&context.balances.read().await, &pair.quote_currency);
+        let amount = quote_available * self.config.order_percent / Decimal::ONE_HUNDRED;
+        if amount <= Decimal::ZERO {
+            warn!(
+                "No {} available to enter below the lower band",
+                pair.quote_currency
+            );
+            return;
+        }
+        println!(
+            "{}| {} close {} below the lower band, buying {} {}",
+            Utc::now().to_rfc3339().blue(),
+            "Bollinger".on_bright_blue(),
+            close.to_string().green(),
+            amount,
+            pair.quote_currency
+        );
+        match context
+            .executor
+            .place_market_order(OrderSide::Buy, amount, pair)
+            .await
+        {
+            Ok(order_id) => {
+                self.entry = Some(Entry {
+                    order_id,
+                    quantity: Decimal::ZERO,
+                    notional: Decimal::ZERO,
+                })
+            }
+            Err(e) => error!("Bollinger entry of {} failed: {}", amount, e),
+        }
+    }
+
+    /// Sells what the entry bought, or what is left of it in the balance. The entry is kept
+    /// until the sell is accepted so a failed exit is tried again on the next signal.
+    async fn exit(&mut self, signal: Signal, close: Decimal, context: &StrategyContext) {
+        let Some(entry) = &self.entry else {
+            return;
+        };
+        let pair = &context.currency_pair;
+        let base_available = available_balance(&context.balances.read().await, &pair.base_currency);
+        let quantity = entry.quantity.min(base_available);
+        if quantity <= Decimal::ZERO {
+            warn!("No {} available to exit the position", pair.base_currency);
+            return;
+        }
+        println!(
+            "{}| {} {:?} at close {}, selling {} {}",
+            Utc::now().to_rfc3339().blue(),
+            "Bollinger".on_bright_blue(),
+            signal,
+            close.to_string().red(),
+            quantity,
+            pair.base_currency
+        );
+        match context
+            .executor
+            .place_market_order(OrderSide::Sell, quantity, pair)
+            .await
+        {
+            Ok(_) => self.entry = None,
+            Err(e) => error!("Bollinger exit of {} failed: {}", quantity, e),
+        }
+    }
+}
+
+#[async_trait]
+impl Strategy for BollingerMeanReversion {
+    fn name(&self) -> &'static str {
+        "bollinger_mean_reversion"
+    }
+
+    fn timeframes(&self) -> Vec<Timeframe> {
+        vec![self.config.timeframe]
+    }
+
+    fn indicators(&self) -> Vec<(Timeframe, String, Indicator)> {
+        vec![(
+            self.config.timeframe,
+            BANDS_INDICATOR.to_string(),
+            Indicator::BollingerBands(BollingerBands::new(
+                self.config.period,
+                self.config.standard_deviations,
+            )),
+        )]
+    }
+
+    async fn on_candle(
+        &mut self,
+        timeframe: Timeframe,
+        candles: &[MarkPriceBucket],
+        context: &StrategyContext,
+    ) {
+        if timeframe != self.config.timeframe {
+            return;
+        }
+        let Some(close) = candles.last().map(|c| c.close) else {
+            return;
+        };
+        let Some(bands) = context
+            .candles
+            .read()
+            .await
+            .indicator(timeframe, BANDS_INDICATOR)
+            .and_then(|value| value.bands())
+        else {
+            return;
+        };
+        let entry_price = match &self.entry {
+            None => None,
+            Some(entry) => match entry.price() {
+                Some(price) => Some(price),
+                // the entry order has not filled yet
+                None => return,
+            },
+        };
+        match mean_reversion_signal(close, bands, entry_price, self.config.stop_percent) {
+            Some(Signal::Enter) => self.enter(close, context).await,
+            Some(signal) => self.exit(signal, close, context).await,
+            None => {}
+        }
+    }
+
+    async fn on_account_trade(&mut self, account_trade: &AccountTrade, context: &StrategyContext) {
+        let Some(entry) = self
+            .entry
+            .as_mut()
+            .filter(|entry| entry.order_id == account_trade.order_id)
+        else {
+            return;
+        };
+        let fee_in_base = match &account_trade.fee_currency {
+            Some(fee_currency) if *fee_currency == context.currency_pair.base_currency => {
+                account_trade.fee.unwrap_or(Decimal::ZERO)
+            }
+            _ => Decimal::ZERO,
+        };
+        entry.quantity += account_trade.quantity - fee_in_base;
+        entry.notional += account_trade.price * account_trade.quantity;
+    }
+
+    /// An entry order that failed before it filled leaves nothing to exit
+    async fn on_order_status(&mut self, update: &OrderStatusUpdate, context: &StrategyContext) {
+        let unfilled = self.entry.as_ref().is_some_and(|entry| {
+            entry.order_id == update.order_id && entry.quantity == Decimal::ZERO
+        });
+        if unfilled
+            && matches!(
+                update.order_status_type,
+                OrderStatus::Cancelled | OrderStatus::Failed
+            )
+        {
+            warn!(
+                "Bollinger entry {} {}",
+                update.order_id, update.order_status_type
+            );
+            self.entry = None;
+        }
+    }
+}
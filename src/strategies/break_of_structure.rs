@@ -1,217 +1,290 @@
-// #[path = "../rusty_bot_models.rs"]
-// pub mod rusty_bot_models;
+use async_trait::async_trait;
+use chrono::Utc;
+use colored::Colorize;
+use log::{error, warn};
+use rust_decimal::Decimal;
 
-use log::warn;
-use std::sync::Arc;
-
-use crate::rusty_bot_models::{BalanceUpdate, CurrencyPair, MarkPriceBucket};
-use tokio::sync::RwLock;
+use crate::candles::Timeframe;
+use crate::config::BosConfig;
+use crate::rusty_bot_models::{MarkPriceBucket, OrderSide};
+use crate::strategies::{available_balance, Strategy, StrategyContext};
 
 #[path = "../helper.rs"]
 pub mod helper;
 
-pub async fn test_for_break_of_structure(
-    bucket_prices: Vec<MarkPriceBucket>,
-    asks: &Arc<RwLock<Vec<Vec<String>>>>,
-    bids: &Arc<RwLock<Vec<Vec<String>>>>,
-    balances: Arc<RwLock<Vec<BalanceUpdate>>>,
-    currency_pair: CurrencyPair,
-) {
-    let width = 3; //width of the spread under consideration
-    let length = (width * 2) + 1;
-    if bucket_prices.is_empty() {
-        warn!("No Buckets available");
-        return;
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwingKind {
+    High,
+    Low,
+}
 
-    let asks_reader = asks.read().await;
-    let bids_reader = bids.read().await;
-    let balances_reader = balances.read().await;
+/// A fractal swing: the candle's high is above, or its low below, those of the `width`
+/// candles on either side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Swing {
+    pub index: usize,
+    pub kind: SwingKind,
+    pub price: Decimal,
+}
 
-    if asks_reader.is_empty() || bids_reader.is_empty() || balances_reader.is_empty() {
-        warn!("No Asks or Bids or balance available");
-        return;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Bullish,
+    Bearish,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakKind {
+    /// The close broke a swing in the direction of the trend, or set the first trend
+    BreakOfStructure,
+    /// The close broke a swing against the trend, which turns
+    ChangeOfCharacter,
+}
+
+/// A close through the last unbroken swing high or low
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StructureBreak {
+    /// Index of the candle that closed through the swing
+    pub index: usize,
+    pub direction: Direction,
+    pub kind: BreakKind,
+    /// Index and price of the swing that was broken
+    pub swing_index: usize,
+    pub level: Decimal,
+}
+
+/// Swings of the candles, in order. Only candles with `width` candles after them are
+/// confirmed, so the last `width` candles are never swings.
+#[cfg(test)]
+pub fn find_swings(candles: &[MarkPriceBucket], width: usize) -> Vec<Swing> {
+    (0..candles.len())
+        .flat_map(|index| swings_at(candles, index, width))
+        .collect()
+}
+
+/// Swings of the candle at `index`, none unless `width` candles either side of it are given
+pub fn swings_at(candles: &[MarkPriceBucket], index: usize, width: usize) -> Vec<Swing> {
+    if width == 0 || index < width || index + width >= candles.len() {
+        return vec![];
     }
-    let balance_update_quote_total = &balances_reader
+    let candle = &candles[index];
+    let neighbours: Vec<&MarkPriceBucket> = candles[index - width..=index + width]
         .iter()
-        .find(|b| b.currency.symbol == currency_pair.quote_currency)
-        .map(|b| b.total.parse::<f64>());
-    let balance_update_base_total = &balances_reader
-        .iter()
-        .find(|b| b.currency.symbol == currency_pair.base_currency)
-        .map(|b| b.total.parse::<f64>());
-
-    let balance_update_quote_total = match  balance_update_quote_total.clone() {
-        None => 0f64,
-        Some(_qa) => _qa.unwrap() 
-    };
+        .enumerate()
+        .filter(|(offset, _)| *offset != width)
+        .map(|(_, neighbour)| neighbour)
+        .collect();
+    let mut swings = vec![];
+    if neighbours.iter().all(|n| candle.high > n.high) {
+        swings.push(Swing {
+            index,
+            kind: SwingKind::High,
+            price: candle.high,
+        });
+    }
+    if neighbours.iter().all(|n| candle.low < n.low) {
+        swings.push(Swing {
+            index,
+            kind: SwingKind::Low,
+            price: candle.low,
+        });
+    }
+    swings
+}
 
-    let balance_update_base_total = match balance_update_base_total {
-        None => 0f64,
-        Some(_qa) => _qa.clone().unwrap()
-    };
+/// Walks the candles in order, tracking the last swing high and low as they are confirmed, and
+/// returns every close through one of them. Each swing is broken at most once.
+pub fn structure_breaks(candles: &[MarkPriceBucket], width: usize) -> Vec<StructureBreak> {
+    let mut trend: Option<Direction> = None;
+    let mut swing_high: Option<Swing> = None;
+    let mut swing_low: Option<Swing> = None;
+    let mut breaks = vec![];
+    for (index, candle) in candles.iter().enumerate() {
+        // the candle confirms the swing `width` candles back
+        if index >= width {
+            for swing in swings_at(&candles[..=index], index - width, width) {
+                match swing.kind {
+                    SwingKind::High => swing_high = Some(swing),
+                    SwingKind::Low => swing_low = Some(swing),
+                }
+            }
+        }
 
-    let best_ask = asks_reader.first().unwrap();
-    let best_bid = bids_reader.first().unwrap();
-
-    let current_index = bucket_prices.len() - width - 1;
-    let previous_close = bucket_prices.last().unwrap().close;
-
-    let swing_high: f64 = -1.0f64;
-    let swing_low: f64 = -1.0f64;
-
-    let best_bid_price = &best_bid[0].parse::<f64>().unwrap();
-    let best_ask_price = &best_ask[0].parse::<f64>().unwrap();
-
-    for i in 1..width {
-        let left_neighbor_index = current_index - i;
-        let right_neighbor_index = current_index + i;
-
-        let current = bucket_prices.get(i).unwrap();
-        let left = bucket_prices.get(left_neighbor_index).unwrap();
-        let right = bucket_prices.get(right_neighbor_index).unwrap();
-
-        let current_high = current.high;
-        let current_low = current.low;
-        let current_close = current.close;
-
-        let right_high = right.high;
-        let right_low = right.low;
-        let right_close = right.close;
-
-        let left_high = left.high;
-        let left_low = left.low;
-        let left_close = left.close;
-
-        let is_high_swing = !(current_high <= left_high || right_high < current_high);
-
-        let is_low_swing = !(current_low >= left_low || right_low > current_low);
-
-        let swing_high: f64 = if is_high_swing { current_high } else { -1.0f64 };
-        let swing_low: f64 = if is_low_swing { current_low } else { -1.0 };
-
-        if is_high_swing {
-            println!("IS HIGH SWING");
-            println!();
-            println!(
-                "LEFT    BUCKET CLOSE: {}, HIGH: {}, LOW: {}",
-                left_close, left_high, left_low
-            );
-            println!(
-                "CURRENT BUCKET CLOSE: {}, HIGH: {}, LOW: {}",
-                current_close, current_high, current_low
-            );
-            println!(
-                "RIGHT   BUCKET CLOSE: {}, HIGH: {}, LOW: {}",
-                right_close, right_high, right_low
-            );
-            println!();
-            println!("best_bid_price: {}", best_bid_price);
-            println!("best_ask_price: {}", best_ask_price);
-            println!();
-            println!("swing high: {} > 0 = {}", swing_high, swing_high > 0f64);
-            println!(
-                "best_bid_price: {} > swing_high: {} = {}",
-                best_bid_price,
-                swing_high,
-                *best_bid_price > swing_high
-            );
-            println!(
-                "previous_close > swing_high? ({} > {}): {}",
-                previous_close,
-                swing_high,
-                previous_close > swing_high
-            );
-            println!();
+        let broken = match (swing_high, swing_low) {
+            (Some(high), _) if candle.close > high.price => {
+                swing_high = None;
+                Some((Direction::Bullish, high))
+            }
+            (_, Some(low)) if candle.close < low.price => {
+                swing_low = None;
+                Some((Direction::Bearish, low))
+            }
+            _ => None,
+        };
+        if let Some((direction, swing)) = broken {
+            let kind = match trend {
+                Some(trend) if trend != direction => BreakKind::ChangeOfCharacter,
+                _ => BreakKind::BreakOfStructure,
+            };
+            trend = Some(direction);
+            breaks.push(StructureBreak {
+                index,
+                direction,
+                kind,
+                swing_index: swing.index,
+                level: swing.price,
+            });
         }
+    }
+    breaks
+}
 
-        if is_low_swing {
-            println!("IS LOW SWING");
-            println!();
-            println!(
-                "LEFT    BUCKET CLOSE: {}, HIGH: {}, LOW: {}",
-                left_close, left_high, left_low
-            );
-            println!(
-                "CURRENT BUCKET CLOSE: {}, HIGH: {}, LOW: {}",
-                current_close, current_high, current_low
-            );
-            println!(
-                "RIGHT   BUCKET CLOSE: {}, HIGH: {}, LOW: {}",
-                right_close, right_high, right_low
-            );
-            println!();
-            println!("best_bid_price: {}", best_bid_price);
-            println!("best_ask_price: {}", best_ask_price);
-            println!();
-            println!("swing low: {} > 0 = {}", swing_low, swing_low > 0f64);
-            println!(
-                "best_ask_price: {} < swing_low: {} = {}",
-                best_ask_price,
-                swing_low,
-                *best_ask_price > swing_low
-            );
-            println!(
-                "previous_close < swing_low? ({} < {}) : {}",
-                previous_close,
-                swing_low,
-                previous_close < swing_low
-            );
-            println!();
+/// Looks for fractal swings over the last `lookback` closed candles and trades the close that
+/// breaks one: a BUY limit at the best ask when a swing high breaks and a SELL limit at the best
+/// bid when a swing low breaks, sized by the available balance. A break against the trend is
+/// reported as a change of character.
+pub struct BreakOfStructure {
+    config: BosConfig,
+    /// Start of the candle whose break was last traded, so it is traded once
+    last_break: Option<String>,
+}
+
+impl BreakOfStructure {
+    pub fn new(config: BosConfig) -> Self {
+        BreakOfStructure {
+            config,
+            last_break: None,
         }
     }
-    if swing_high > 0f64 && best_bid_price > &swing_high && previous_close > swing_high {
-        buy(
-            *best_ask_price,
-            best_ask[1].clone(),
-            currency_pair,
-            balance_update_quote_total,
-        )
-        .await;
-    } else if swing_low > 0f64 && *best_ask_price < swing_low && previous_close < swing_low {
-        sell(
-            *best_bid_price,
-            best_bid[1].clone(),
-            currency_pair,
-            balance_update_base_total,
-        )
-        .await;
+}
+
+#[async_trait]
+impl Strategy for BreakOfStructure {
+    fn name(&self) -> &'static str {
+        "break_of_structure"
+    }
+
+    fn timeframes(&self) -> Vec<Timeframe> {
+        vec![self.config.timeframe]
+    }
+
+    async fn on_candle(
+        &mut self,
+        timeframe: Timeframe,
+        bucket_prices: &[MarkPriceBucket],
+        context: &StrategyContext,
+    ) {
+        if timeframe != self.config.timeframe {
+            return;
+        }
+        // the last candle is still forming
+        let closed = &bucket_prices[..bucket_prices.len().saturating_sub(1)];
+        let window = &closed[closed.len().saturating_sub(self.config.lookback)..];
+        let Some(structure_break) = structure_breaks(window, self.config.swing_width).pop() else {
+            return;
+        };
+        let candle = &window[structure_break.index];
+        if structure_break.index + 1 != window.len()
+            || self.last_break.as_ref() == Some(&candle.start_time)
+        {
+            return;
+        }
+        self.last_break = Some(candle.start_time.clone());
+        println!(
+            "{}| {} {:?} {:?}, {} closed at {} through {}",
+            Utc::now().to_rfc3339().blue(),
+            "BOS".on_bright_blue(),
+            structure_break.direction,
+            structure_break.kind,
+            candle.start_time,
+            candle.close,
+            structure_break.level
+        );
+        match structure_break.direction {
+            Direction::Bullish => buy(context).await,
+            Direction::Bearish => sell(context).await,
+        }
     }
-    drop(bids_reader);
-    drop(asks_reader);
 }
 
-async fn sell(
-    best_bid_price: f64,
-    quantity: String,
-    currency_pair: CurrencyPair,
-    balance_update_base_total: f64,
-) {
-    println!(
-        "Total {}: {}",
-        currency_pair.base_currency, balance_update_base_total
+async fn buy(context: &StrategyContext) {
+    let currency_pair = &context.currency_pair;
+    let Some(best_ask) = context.order_book.read().await.best_ask().copied() else {
+        warn!("No asks to BUY from");
+        return;
+    };
+    let quote_available = available_balance(
+        &context.balances.read().await,
+        &currency_pair.quote_currency,
     );
+    let affordable_quantity = quote_available
+        .checked_div(best_ask.price)
+        .unwrap_or(Decimal::ZERO);
+    let quantity = best_ask.quantity.min(affordable_quantity);
+    if quantity <= Decimal::ZERO {
+        warn!("No {} available to BUY", currency_pair.quote_currency);
+        return;
+    }
+    cancel_stale_orders(OrderSide::Buy, context).await;
     println!(
-        "Place SELL at price: {} and quantity: {}",
-        best_bid_price, quantity
+        "Place BUY at price: {} and quantity: {}",
+        best_ask.price, quantity
     );
-    //drop sells?
+    if let Err(e) = context
+        .executor
+        .place_limit_order(OrderSide::Buy, best_ask.price, quantity, currency_pair)
+        .await
+    {
+        error!("Failed to place BUY order: {}", e);
+    }
 }
 
-async fn buy(
-    best_ask_price: f64,
-    quantity: String,
-    currency_pair: CurrencyPair,
-    balance_update_quote_total: f64,
-) {
-
-    println!(
-        "Total {}: {}",
-        currency_pair.quote_currency, balance_update_quote_total
-    );
+async fn sell(context: &StrategyContext) {
+    let currency_pair = &context.currency_pair;
+    let Some(best_bid) = context.order_book.read().await.best_bid().copied() else {
+        warn!("No bids to SELL to");
+        return;
+    };
+    let base_available =
+        available_balance(&context.balances.read().await, &currency_pair.base_currency);
+    let quantity = best_bid.quantity.min(base_available);
+    if quantity <= Decimal::ZERO {
+        warn!("No {} available to SELL", currency_pair.base_currency);
+        return;
+    }
+    cancel_stale_orders(OrderSide::Sell, context).await;
     println!(
         "Place SELL at price: {} and quantity: {}",
-        best_ask_price, quantity
+        best_bid.price, quantity
     );
-    //drop buys?
+    if let Err(e) = context
+        .executor
+        .place_limit_order(OrderSide::Sell, best_bid.price, quantity, currency_pair)
+        .await
+    {
+        error!("Failed to place SELL order: {}", e);
+    }
+}
+
+/// Orders still resting from an earlier break were priced off an older book
+async fn cancel_stale_orders(side: OrderSide, context: &StrategyContext) {
+    let currency_pair = &context.currency_pair;
+    let stale_order_ids: Vec<String> = context
+        .orders
+        .read()
+        .await
+        .open_orders_for(&currency_pair.symbol, side)
+        .iter()
+        .map(|o| o.order_id.clone())
+        .collect();
+    for order_id in stale_order_ids {
+        println!("Cancel stale {} order: {}", side, order_id);
+        if let Err(e) = context
+            .executor
+            .cancel_order(&order_id, currency_pair)
+            .await
+        {
+            error!("Failed to cancel stale {} order {}: {}", side, order_id, e);
+        }
+    }
 }
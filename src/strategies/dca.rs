@@ -0,0 +1,144 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use colored::Colorize;
+use log::{error, warn};
+use rust_decimal::Decimal;
+
+use crate::candles::Timeframe;
+use crate::config::DcaConfig;
+use crate::indicators::{Indicator, Sma};
+use crate::rusty_bot_models::{MarkPriceBucket, OrderSide};
+use crate::strategies::{available_balance, Strategy, StrategyContext};
+
+const MA_INDICATOR: &str = "dca_sma";
+
+/// Quote amount of a scheduled buy, scaled by the dip multiplier when the price is below the
+/// moving average
+pub fn buy_amount(
+    quote_amount: Decimal,
+    price: Decimal,
+    moving_average: Option<Decimal>,
+    dip_multiplier: Decimal,
+) -> Decimal {
+    match moving_average {
+        Some(moving_average) if price < moving_average => quote_amount * dip_multiplier,
+        _ => quote_amount,
+    }
+}
+
+/// Market buys a fixed quote amount every interval, timed by the 1m candles so backtests follow
+/// the same schedule. A buy the available quote balance cannot cover is skipped until the next
+/// one is due.
+pub struct Dca {
+    config: DcaConfig,
+    last_buy: Option<DateTime<Utc>>,
+}
+
+impl Dca {
+    pub fn new(config: DcaConfig) -> Self {
+        Dca {
+            config,
+            last_buy: None,
+        }
+    }
+
+    fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.last_buy.is_none_or(|last_buy| {
+            now >= last_buy + Duration::minutes(self.config.interval_minutes)
+        })
+    }
+}
+
+#[async_trait]
+impl Strategy for Dca {
+    fn name(&self) -> &'static str {
+        "dca"
+    }
+
+    fn timeframes(&self) -> Vec<Timeframe> {
+        let mut timeframes = vec![Timeframe::OneMinute];
+        if self.config.ma_timeframe != Timeframe::OneMinute {
+            timeframes.push(self.config.ma_timeframe);
+        }
+        timeframes
+    }
+
+    fn indicators(&self) -> Vec<(Timeframe, String, Indicator)> {
+        if self.config.ma_period == 0 {
+            return vec![];
+        }
+        vec![(
+            self.config.ma_timeframe,
+            MA_INDICATOR.to_string(),
+            Indicator::Sma(Sma::new(self.config.ma_period)),
+        )]
+    }
+
+    async fn on_candle(
+        &mut self,
+        timeframe: Timeframe,
+        candles: &[MarkPriceBucket],
+        context: &StrategyContext,
+    ) {
+        if timeframe != Timeframe::OneMinute {
+            return;
+        }
+        let Some(candle) = candles.last() else {
+            return;
+        };
+        let Ok(now) = DateTime::parse_from_rfc3339(&candle.start_time) else {
+            return;
+        };
+        let now = now.with_timezone(&Utc);
+        if !self.is_due(now) {
+            return;
+        }
+
+        let balances_reader = context.balances.read().await;
+        if balances_reader.is_empty() {
+            // wait for the first balance update rather than skip the buy
+            return;
+        }
+        let quote_available =
+            available_balance(&balances_reader, &context.currency_pair.quote_currency);
+        drop(balances_reader);
+        self.last_buy = Some(now);
+
+        let moving_average = context
+            .candles
+            .read()
+            .await
+            .indicator(self.config.ma_timeframe, MA_INDICATOR)
+            .and_then(|value| value.single());
+        let amount = buy_amount(
+            self.config.quote_amount,
+            candle.close,
+            moving_average,
+            self.config.dip_multiplier,
+        );
+        if amount > quote_available {
+            warn!(
+                "DCA skipping the buy of {} {}, only {} available",
+                amount, context.currency_pair.quote_currency, quote_available
+            );
+            return;
+        }
+
+        println!(
+            "{}| {} buying {} {} of {} at {}",
+            now.to_rfc3339().blue(),
+            "DCA".on_bright_blue(),
+            amount,
+            context.currency_pair.quote_currency,
+            context.currency_pair.base_currency,
+            candle.close.to_string().green()
+        );
+        if let Err(e) = context
+            .executor
+            .place_market_order(OrderSide::Buy, amount, &context.currency_pair)
+            .await
+        {
+            error!("DCA buy of {} failed: {}", amount, e);
+        }
+    }
+}
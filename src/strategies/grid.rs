@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use colored::Colorize;
+use log::{error, warn};
+use rust_decimal::Decimal;
+
+use crate::config::GridConfig;
+use crate::rusty_bot_models::{
+    AccountTrade, BalanceUpdate, OrderSide, OrderStatus, OrderStatusUpdate,
+};
+use crate::sizing::{round_to_tick, size_limit_order, SizingError};
+use crate::strategies::{available_balance, Strategy, StrategyContext};
+
+/// Evenly spaced prices from the lower to the upper bound rounded down to the tick size.
+/// Prices that round to the same tick are merged.
+pub fn grid_prices(
+    lower: Decimal,
+    upper: Decimal,
+    levels: usize,
+    tick_size: Decimal,
+) -> Vec<Decimal> {
+    if levels < 2 || lower <= Decimal::ZERO || upper <= lower {
+        return vec![];
+    }
+    let step = (upper - lower) / Decimal::from(levels - 1);
+    let mut prices: Vec<Decimal> = (0..levels)
+        .map(|level| {
+            round_to_tick(
+                OrderSide::Buy,
+                lower + step * Decimal::from(level),
+                tick_size,
+            )
+        })
+        .filter(|price| *price > Decimal::ZERO)
+        .collect();
+    prices.dedup();
+    prices
+}
+
+#[derive(Debug, Clone, Copy)]
+struct GridOrder {
+    level: usize,
+    side: OrderSide,
+    quantity: Decimal,
+    /// Taken from the account's trades on the order
+    filled: Decimal,
+}
+
+/// Ladders limit buys below and limit sells above the mid price. When a buy fills, a sell for
+/// the same quantity goes one level up, when a sell fills a buy goes one level down.
+///
+/// Every fill, partial ones included, adds its quantity to the opposite order. That order waits
+/// until the balances can cover it and is shrunk to what is available, or until enough fills add
+/// up to the pair's minimum. A cancelled or failed grid order is placed again for what it had
+/// left once the balances are next updated.
+pub struct Grid {
+    config: GridConfig,
+    prices: Vec<Decimal>,
+    started: bool,
+    orders: HashMap<String, GridOrder>,
+    pending: Vec<GridOrder>,
+}
+
+impl Grid {
+    pub fn new(config: GridConfig) -> Self {
+        Grid {
+            config,
+            prices: vec![],
+            started: false,
+            orders: HashMap::new(),
+            pending: vec![],
+        }
+    }
+
+    /// Places the first ladder, split evenly over the available balances
+    async fn start(&mut self, context: &StrategyContext) {
+        let order_book_reader = context.order_book.read().await;
+        let Some(mid_price) = order_book_reader.mid_price() else {
+            return;
+        };
+        drop(order_book_reader);
+        let balances_reader = context.balances.read().await;
+        if balances_reader.is_empty() {
+            return;
+        }
+        let pair = &context.currency_pair;
+        let quote_available = available_balance(&balances_reader, &pair.quote_currency);
+        let base_available = available_balance(&balances_reader, &pair.base_currency);
+        drop(balances_reader);
+        self.started = true;
+
+        let range = mid_price * self.config.range_percent / Decimal::ONE_HUNDRED;
+        let lower = self.config.lower_price.unwrap_or(mid_price - range);
+        let upper = self.config.upper_price.unwrap_or(mid_price + range);
+        self.prices = grid_prices(lower, upper, self.config.levels, pair.tick_size);
+        if self.prices.is_empty() {
+            error!(
+                "Grid from {} to {} with {} levels has no prices to trade",
+                lower, upper, self.config.levels
+            );
+            return;
+        }
+
+        let buy_levels: Vec<usize> = (0..self.prices.len())
+            .filter(|level| self.prices[*level] < mid_price)
+            .collect();
+        let sell_levels: Vec<usize> = (0..self.prices.len())
+            .filter(|level| self.prices[*level] > mid_price)
+            .collect();
+        println!(
+            "{}| {} {} buys and {} sells from {} to {} around {}",
+            Utc::now().to_rfc3339().blue(),
+            "Grid".on_bright_blue(),
+            buy_levels.len(),
+            sell_levels.len(),
+            self.prices[0],
+            self.prices[self.prices.len() - 1],
+            mid_price
+        );
+
+        for level in &buy_levels {
+            let quantity = quote_available / Decimal::from(buy_levels.len()) / self.prices[*level];
+            self.place(level_order(*level, OrderSide::Buy, quantity), context)
+                .await;
+        }
+        for level in &sell_levels {
+            let quantity = base_available / Decimal::from(sell_levels.len());
+            self.place(level_order(*level, OrderSide::Sell, quantity), context)
+                .await;
+        }
+    }
+
+    /// Sized here so the fills can be counted against the quantity that was sent
+    async fn place(&mut self, order: GridOrder, context: &StrategyContext) {
+        let price = self.prices[order.level];
+        let sized =
+            match size_limit_order(order.side, price, order.quantity, &context.currency_pair) {
+                Ok(sized) => sized,
+                Err(
+                    SizingError::BelowMinBaseAmount { .. }
+                    | SizingError::BelowMinQuoteAmount { .. },
+                ) => {
+                    self.queue(order);
+                    return;
+                }
+                Err(e) => {
+                    error!("Grid {} at {} failed: {}", order.side, price, e);
+                    return;
+                }
+            };
+        match context
+            .executor
+            .place_limit_order(order.side, price, sized.quantity, &context.currency_pair)
+            .await
+        {
+            Ok(order_id) => {
+                let order = GridOrder {
+                    quantity: sized.quantity,
+                    ..order
+                };
+                self.orders.insert(order_id, order);
+            }
+            Err(e) => error!("Grid {} at {} failed: {}", order.side, price, e),
+        }
+    }
+
+    /// Adds to the order waiting at the same level and side, if there is one
+    fn queue(&mut self, order: GridOrder) {
+        match self
+            .pending
+            .iter_mut()
+            .find(|pending| pending.level == order.level && pending.side == order.side)
+        {
+            Some(pending) => pending.quantity += order.quantity,
+            None => self.pending.push(order),
+        }
+    }
+
+    /// The order one level up for a buy or one level down for a sell
+    fn opposite(&self, order: &GridOrder, quantity: Decimal) -> Option<GridOrder> {
+        match order.side {
+            OrderSide::Buy if order.level + 1 < self.prices.len() => {
+                Some(level_order(order.level + 1, OrderSide::Sell, quantity))
+            }
+            OrderSide::Sell if order.level > 0 => {
+                Some(level_order(order.level - 1, OrderSide::Buy, quantity))
+            }
+            _ => None,
+        }
+    }
+
+    /// Places the opposite orders the balances can cover, the rest wait for a balance update
+    async fn place_pending(&mut self, context: &StrategyContext) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let balances_reader = context.balances.read().await;
+        let pair = &context.currency_pair;
+        let mut quote_available = available_balance(&balances_reader, &pair.quote_currency);
+        let mut base_available = available_balance(&balances_reader, &pair.base_currency);
+        drop(balances_reader);
+
+        let mut ready = vec![];
+        let mut waiting = vec![];
+        for order in self.pending.drain(..) {
+            let price = self.prices[order.level];
+            let quantity = match order.side {
+                OrderSide::Buy => order.quantity.min(quote_available / price),
+                OrderSide::Sell => order.quantity.min(base_available),
+            };
+            if quantity <= Decimal::ZERO {
+                waiting.push(order);
+                continue;
+            }
+            match order.side {
+                OrderSide::Buy => quote_available -= quantity * price,
+                OrderSide::Sell => base_available -= quantity,
+            }
+            ready.push(GridOrder { quantity, ..order });
+        }
+        self.pending = waiting;
+        for order in ready {
+            self.place(order, context).await;
+        }
+    }
+}
+
+fn level_order(level: usize, side: OrderSide, quantity: Decimal) -> GridOrder {
+    GridOrder {
+        level,
+        side,
+        quantity,
+        filled: Decimal::ZERO,
+    }
+}
+
+#[async_trait]
+impl Strategy for Grid {
+    fn name(&self) -> &'static str {
+        "grid"
+    }
+
+    async fn on_book(&mut self, context: &StrategyContext) {
+        if !self.started {
+            self.start(context).await;
+        }
+    }
+
+    async fn on_balance(&mut self, balance_update: &BalanceUpdate, context: &StrategyContext) {
+        self.place_pending(context).await;
+    }
+
+    async fn on_order_status(&mut self, update: &OrderStatusUpdate, context: &StrategyContext) {
+        if !matches!(
+            update.order_status_type,
+            OrderStatus::Cancelled | OrderStatus::Failed
+        ) {
+            return;
+        }
+        let Some(order) = self.orders.remove(&update.order_id) else {
+            return;
+        };
+        let unfilled = order.quantity - order.filled;
+        warn!(
+            "Grid {} at {} was {}, {} waits to be placed again",
+            order.side, self.prices[order.level], update.order_status_type, unfilled
+        );
+        if unfilled > Decimal::ZERO {
+            self.queue(level_order(order.level, order.side, unfilled));
+        }
+    }
+
+    async fn on_account_trade(&mut self, account_trade: &AccountTrade, context: &StrategyContext) {
+        let Some(order) = self.orders.get_mut(&account_trade.order_id) else {
+            return;
+        };
+        order.filled += account_trade.quantity;
+        let order = *order;
+        if order.filled >= order.quantity {
+            self.orders.remove(&account_trade.order_id);
+        }
+        println!(
+            "{}| {} {} {} of {} at {} filled",
+            Utc::now().to_rfc3339().blue(),
+            "Grid".on_bright_blue(),
+            order.side,
+            account_trade.quantity,
+            order.quantity,
+            self.prices[order.level]
+        );
+        match self.opposite(&order, account_trade.quantity) {
+            Some(opposite) => self.queue(opposite),
+            None => warn!("Grid {} filled at the edge of the grid", order.side),
+        }
+        self.place_pending(context).await;
+    }
+}
@@ -0,0 +1,269 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use colored::Colorize;
+use log::{error, warn};
+use rust_decimal::Decimal;
+
+use crate::candles::Timeframe;
+use crate::config::MarketMakingConfig;
+use crate::indicators::{Atr, Indicator};
+use crate::rusty_bot_models::{AccountTrade, OrderSide, OrderStatus, OrderStatusUpdate};
+use crate::sizing::round_to_tick;
+use crate::strategies::{available_balance, total_balance, Strategy, StrategyContext};
+
+const ATR_INDICATOR: &str = "atr_14";
+const ATR_PERIOD: usize = 14;
+const BASIS_POINTS: Decimal = Decimal::from_parts(10_000, 0, 0, false, 0);
+
+/// Share of the inventory's value held in base, from -1 when it is all quote to 1 when it is
+/// all base
+pub fn inventory_ratio(base_total: Decimal, quote_total: Decimal, mid_price: Decimal) -> Decimal {
+    let base_value = base_total * mid_price;
+    let total_value = base_value + quote_total;
+    if total_value <= Decimal::ZERO {
+        return Decimal::ZERO;
+    }
+    (base_value - quote_total) / total_value
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Quotes {
+    pub bid: Decimal,
+    pub ask: Decimal,
+}
+
+/// Quotes `half_spread` either side of the mid, both moved down by `skew` when long base and
+/// up when short. They are kept a tick inside the opposite side so they stay post-only.
+pub fn quote_prices(
+    mid_price: Decimal,
+    half_spread: Decimal,
+    skew: Decimal,
+    best_bid: Decimal,
+    best_ask: Decimal,
+    tick_size: Decimal,
+) -> Quotes {
+    let bid = (mid_price - half_spread - skew).min(best_ask - tick_size);
+    let ask = (mid_price + half_spread - skew).max(best_bid + tick_size);
+    Quotes {
+        bid: round_to_tick(OrderSide::Buy, bid, tick_size),
+        ask: round_to_tick(OrderSide::Sell, ask, tick_size),
+    }
+}
+
+/// Quotes a post-only bid and ask around the mid price. The spread widens with the ATR of the
+/// 1m candles and both quotes are skewed to bring the inventory back to an even split of value
+/// between base and quote, each quote is sized from the available balance. Quotes are amended
+/// when the mid moves past the refresh threshold, cancelled and re-placed when either of them
+/// fills or is reported filled, cancelled or failed, and pulled when the book empties.
+pub struct MarketMaker {
+    config: MarketMakingConfig,
+    bid_order: Option<String>,
+    ask_order: Option<String>,
+    quoted_mid: Option<Decimal>,
+}
+
+impl MarketMaker {
+    pub fn new(config: MarketMakingConfig) -> Self {
+        MarketMaker {
+            config,
+            bid_order: None,
+            ask_order: None,
+            quoted_mid: None,
+        }
+    }
+
+    fn needs_refresh(&self, mid_price: Decimal) -> bool {
+        match self.quoted_mid {
+            None => true,
+            Some(quoted_mid) => {
+                (mid_price - quoted_mid).abs() / quoted_mid * BASIS_POINTS > self.config.refresh_bps
+            }
+        }
+    }
+
+    fn is_quote(&self, order_id: &str) -> bool {
+        self.bid_order.as_deref() == Some(order_id) || self.ask_order.as_deref() == Some(order_id)
+    }
+
+    async fn cancel_quotes(&mut self, context: &StrategyContext) {
+        self.quoted_mid = None;
+        for order_id in [self.bid_order.take(), self.ask_order.take()]
+            .into_iter()
+            .flatten()
+        {
+            // the quote may have filled in the meantime
+            if let Err(e) = context
+                .executor
+                .cancel_order(&order_id, &context.currency_pair)
+                .await
+            {
+                warn!("Market maker could not cancel quote {}: {}", order_id, e);
+            }
+        }
+    }
+
+    /// Amends the resting quote to the new price and size, or places a new one when there is
+    /// none or the amend is rejected
+    async fn requote(
+        &self,
+        order_id: Option<String>,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        context: &StrategyContext,
+    ) -> Option<String> {
+        if let Some(order_id) = order_id {
+            match context
+                .executor
+                .modify_order(&order_id, side, price, quantity, &context.currency_pair)
+                .await
+            {
+                Ok(()) => return Some(order_id),
+                Err(e) => {
+                    warn!("Market maker could not amend quote {}: {}", order_id, e);
+                    // the quote may have filled in the meantime
+                    let _ = context
+                        .executor
+                        .cancel_order(&order_id, &context.currency_pair)
+                        .await;
+                }
+            }
+        }
+        self.place_quote(side, price, quantity, context).await
+    }
+
+    async fn place_quote(
+        &self,
+        side: OrderSide,
+        price: Decimal,
+        quantity: Decimal,
+        context: &StrategyContext,
+    ) -> Option<String> {
+        match context
+            .executor
+            .place_post_only_order(side, price, quantity, &context.currency_pair)
+            .await
+        {
+            Ok(order_id) => Some(order_id),
+            Err(e) => {
+                error!("Market maker {} quote at {} failed: {}", side, price, e);
+                None
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Strategy for MarketMaker {
+    fn name(&self) -> &'static str {
+        "market_making"
+    }
+
+    fn indicators(&self) -> Vec<(Timeframe, String, Indicator)> {
+        vec![(
+            Timeframe::OneMinute,
+            ATR_INDICATOR.to_string(),
+            Indicator::Atr(Atr::new(ATR_PERIOD)),
+        )]
+    }
+
+    async fn on_book(&mut self, context: &StrategyContext) {
+        let order_book_reader = context.order_book.read().await;
+        let book = order_book_reader
+            .mid_price()
+            .zip(order_book_reader.best_bid().map(|level| level.price))
+            .zip(order_book_reader.best_ask().map(|level| level.price))
+            .zip(order_book_reader.spread());
+        drop(order_book_reader);
+        let Some((((mid_price, best_bid), best_ask), spread)) = book else {
+            if self.quoted_mid.is_some() {
+                warn!("Market maker pulling quotes, the book is empty");
+                self.cancel_quotes(context).await;
+            }
+            return;
+        };
+        if !self.needs_refresh(mid_price) {
+            return;
+        }
+
+        let balances_reader = context.balances.read().await;
+        let pair = &context.currency_pair;
+        let quote_total = total_balance(&balances_reader, &pair.quote_currency);
+        let base_total = total_balance(&balances_reader, &pair.base_currency);
+        let quote_available = available_balance(&balances_reader, &pair.quote_currency);
+        let base_available = available_balance(&balances_reader, &pair.base_currency);
+        drop(balances_reader);
+        let atr = context
+            .candles
+            .read()
+            .await
+            .indicator(Timeframe::OneMinute, ATR_INDICATOR)
+            .and_then(|value| value.single())
+            .unwrap_or(Decimal::ZERO);
+
+        let half_spread = mid_price * self.config.half_spread_bps / BASIS_POINTS
+            + atr * self.config.volatility_multiplier;
+        let skew = inventory_ratio(base_total, quote_total, mid_price)
+            * self.config.inventory_skew
+            * half_spread;
+        let quotes = quote_prices(
+            mid_price,
+            half_spread,
+            skew,
+            best_bid,
+            best_ask,
+            pair.tick_size,
+        );
+
+        let order_share = self.config.order_percent / Decimal::ONE_HUNDRED;
+        self.bid_order = self
+            .requote(
+                self.bid_order.clone(),
+                OrderSide::Buy,
+                quotes.bid,
+                quote_available * order_share / quotes.bid,
+                context,
+            )
+            .await;
+        self.ask_order = self
+            .requote(
+                self.ask_order.clone(),
+                OrderSide::Sell,
+                quotes.ask,
+                base_available * order_share,
+                context,
+            )
+            .await;
+        self.quoted_mid = Some(mid_price);
+        println!(
+            "{}| {} bid {} ask {} around {} (spread {}, ATR {}, skew {})",
+            Utc::now().to_rfc3339().blue(),
+            "Market maker".on_bright_blue(),
+            quotes.bid.to_string().green(),
+            quotes.ask.to_string().red(),
+            mid_price,
+            spread,
+            atr.round_dp(8),
+            skew.round_dp(8)
+        );
+    }
+
+    /// A quote that is done is quoted again on both sides on the next book update. Quotes this
+    /// strategy cancelled itself are already forgotten.
+    async fn on_order_status(&mut self, update: &OrderStatusUpdate, context: &StrategyContext) {
+        if matches!(
+            update.order_status_type,
+            OrderStatus::Filled | OrderStatus::Cancelled | OrderStatus::Failed
+        ) && self.is_quote(&update.order_id)
+        {
+            self.cancel_quotes(context).await;
+        }
+    }
+
+    /// Any fill moves the inventory the quotes were skewed for
+    async fn on_account_trade(&mut self, account_trade: &AccountTrade, context: &StrategyContext) {
+        if self.is_quote(&account_trade.order_id) {
+            self.cancel_quotes(context).await;
+        }
+    }
+}
@@ -1 +1,183 @@
-pub mod break_of_structure;
\ No newline at end of file
+pub mod bollinger;
+pub mod break_of_structure;
+pub mod dca;
+pub mod grid;
+pub mod market_making;
+pub mod triangular_arbitrage;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use tokio::sync::RwLock;
+
+use crate::candles::{CandleEngine, Timeframe};
+use crate::config::{
+    ArbitrageConfig, BollingerConfig, BosConfig, DcaConfig, GridConfig, MarketMakingConfig,
+};
+use crate::indicators::Indicator;
+use crate::order_book::OrderBook;
+use crate::order_manager::OrderManager;
+use crate::orders::OrderExecutor;
+use crate::position::PositionTracker;
+use crate::rusty_bot_models::{
+    AccountTrade, BalanceUpdate, CurrencyPair, MarkPriceBucket, Order, OrderStatusUpdate,
+};
+use crate::smc::SmcEvent;
+use crate::strategies::bollinger::BollingerMeanReversion;
+use crate::strategies::break_of_structure::BreakOfStructure;
+use crate::strategies::dca::Dca;
+use crate::strategies::grid::Grid;
+use crate::strategies::market_making::MarketMaker;
+use crate::strategies::triangular_arbitrage::TriangularArbitrage;
+
+pub type SharedStrategy = Arc<RwLock<Box<dyn Strategy>>>;
+
+/// Market state shared with every strategy hook
+#[derive(Clone)]
+pub struct StrategyContext {
+    pub currency_pair: CurrencyPair,
+    pub order_book: Arc<RwLock<OrderBook>>,
+    pub balances: Arc<RwLock<Vec<BalanceUpdate>>>,
+    /// Open orders and their lifecycle, see `OrderManager` for the queries
+    pub orders: Arc<RwLock<OrderManager>>,
+    /// Position, average entry and P&L per pair, fed by the account's fills
+    pub positions: Arc<RwLock<PositionTracker>>,
+    /// Candles of every timeframe the strategy subscribed to
+    pub candles: Arc<RwLock<CandleEngine>>,
+    pub executor: Arc<dyn OrderExecutor>,
+}
+
+/// A trading strategy driven by the WebSocket feeds; every hook is optional
+#[async_trait]
+pub trait Strategy: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Timeframes whose candles are loaded and passed to `on_candle`
+    fn timeframes(&self) -> Vec<Timeframe> {
+        vec![Timeframe::OneMinute]
+    }
+
+    /// Indicators kept up to date on the candles, read them by timeframe and name through
+    /// `context.candles`. Their timeframes are subscribed too.
+    fn indicators(&self) -> Vec<(Timeframe, String, Indicator)> {
+        vec![]
+    }
+
+    /// Timeframes and swing widths whose smart money concepts events are passed to
+    /// `on_smc_event`. Their timeframes are subscribed too.
+    fn smc_detectors(&self) -> Vec<(Timeframe, usize)> {
+        vec![]
+    }
+
+    /// Called whenever a candle of one of the timeframes changes, the last candle is still forming
+    async fn on_candle(
+        &mut self,
+        timeframe: Timeframe,
+        candles: &[MarkPriceBucket],
+        context: &StrategyContext,
+    ) {
+    }
+
+    /// Called once with each fair value gap, order block and liquidity sweep of the
+    /// `smc_detectors`, after `on_candle`, as the candle confirming it closes. The event's
+    /// indexes point into `candles`.
+    async fn on_smc_event(
+        &mut self,
+        timeframe: Timeframe,
+        event: &SmcEvent,
+        candles: &[MarkPriceBucket],
+        context: &StrategyContext,
+    ) {
+    }
+
+    /// Pairs whose top of book is followed, the books of those besides the market are passed
+    /// to `on_pair_book`
+    fn pairs(&self) -> Vec<String> {
+        vec![]
+    }
+
+    async fn on_book(&mut self, context: &StrategyContext) {}
+
+    /// Called with each OB_L1_D10_SNAPSHOT of one of the `pairs`
+    async fn on_pair_book(
+        &mut self,
+        currency_pair: &CurrencyPair,
+        order_book: &OrderBook,
+        context: &StrategyContext,
+    ) {
+    }
+
+    async fn on_balance(&mut self, balance_update: &BalanceUpdate, context: &StrategyContext) {}
+
+    async fn on_order_update(&mut self, orders: &[Order], context: &StrategyContext) {}
+
+    /// Called with each ORDER_STATUS_UPDATE once the open orders reflect it
+    async fn on_order_status(&mut self, update: &OrderStatusUpdate, context: &StrategyContext) {}
+
+    /// Called once with each of the account's fills, simulated fills included
+    async fn on_account_trade(&mut self, account_trade: &AccountTrade, context: &StrategyContext) {}
+}
+
+type StrategyFactory = fn() -> Box<dyn Strategy>;
+
+/// Strategies selectable through the STRATEGY config value.
+/// Register a new strategy module here to make it available to the bot.
+const REGISTRY: &[(&str, StrategyFactory)] = &[
+    ("break_of_structure", || {
+        Box::new(BreakOfStructure::new(BosConfig::from_env()))
+    }),
+    ("bollinger_mean_reversion", || {
+        Box::new(BollingerMeanReversion::new(BollingerConfig::from_env()))
+    }),
+    ("dca", || Box::new(Dca::new(DcaConfig::from_env()))),
+    ("grid", || Box::new(Grid::new(GridConfig::from_env()))),
+    ("market_making", || {
+        Box::new(MarketMaker::new(MarketMakingConfig::from_env()))
+    }),
+    ("triangular_arbitrage", || {
+        Box::new(TriangularArbitrage::new(ArbitrageConfig::from_env()))
+    }),
+];
+
+pub fn create_strategy(name: &str) -> Option<Box<dyn Strategy>> {
+    REGISTRY
+        .iter()
+        .find(|(strategy_name, _)| *strategy_name == name)
+        .map(|(_, factory)| factory())
+}
+
+/// Available balance of the currency, zero before its first balance update
+pub fn available_balance(balances: &[BalanceUpdate], currency: &str) -> Decimal {
+    balances
+        .iter()
+        .find(|b| b.currency.symbol == currency)
+        .map(|b| b.available)
+        .unwrap_or(Decimal::ZERO)
+}
+
+/// Total balance of the currency, available plus reserved for open orders
+pub fn total_balance(balances: &[BalanceUpdate], currency: &str) -> Decimal {
+    balances
+        .iter()
+        .find(|b| b.currency.symbol == currency)
+        .map(|b| b.total)
+        .unwrap_or(Decimal::ZERO)
+}
+
+/// Candle engine subscribed to the strategy's timeframes, with its indicators and smart money
+/// concepts detectors attached
+pub fn create_candle_engine(strategy: &dyn Strategy) -> CandleEngine {
+    let mut candle_engine = CandleEngine::new(&strategy.timeframes());
+    for (timeframe, name, indicator) in strategy.indicators() {
+        candle_engine.attach_indicator(timeframe, &name, indicator);
+    }
+    for (timeframe, swing_width) in strategy.smc_detectors() {
+        candle_engine.attach_smc_detector(timeframe, swing_width);
+    }
+    candle_engine
+}
+
+pub fn available_strategies() -> Vec<&'static str> {
+    REGISTRY.iter().map(|(name, _)| *name).collect()
+}
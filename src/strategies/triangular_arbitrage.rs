@@ -0,0 +1,509 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use colored::Colorize;
+use log::{error, warn};
+use rust_decimal::{Decimal, RoundingStrategy};
+
+use crate::config::ArbitrageConfig;
+use crate::order_book::OrderBook;
+use crate::rusty_bot_models::{
+    AccountTrade, CurrencyPair, OrderSide, OrderStatus, OrderStatusUpdate,
+};
+use crate::sizing::size_limit_order;
+use crate::strategies::{available_balance, Strategy, StrategyContext};
+
+const BASIS_POINTS: Decimal = Decimal::from_parts(10_000, 0, 0, false, 0);
+const PERMUTATIONS: [[usize; 3]; 6] = [
+    [0, 1, 2],
+    [0, 2, 1],
+    [1, 0, 2],
+    [1, 2, 0],
+    [2, 0, 1],
+    [2, 1, 0],
+];
+
+/// One trade of a round trip, a BUY spends the quote currency and a SELL the base
+#[derive(Debug, Clone)]
+pub struct Leg {
+    pub currency_pair: CurrencyPair,
+    pub side: OrderSide,
+}
+
+impl Leg {
+    pub fn input_currency(&self) -> &str {
+        match self.side {
+            OrderSide::Buy => &self.currency_pair.quote_currency,
+            OrderSide::Sell => &self.currency_pair.base_currency,
+        }
+    }
+
+    pub fn output_currency(&self) -> &str {
+        match self.side {
+            OrderSide::Buy => &self.currency_pair.base_currency,
+            OrderSide::Sell => &self.currency_pair.quote_currency,
+        }
+    }
+}
+
+/// Both directions around the triangle from the start currency back to it, empty when the
+/// pairs do not form one
+pub fn triangle_routes(pairs: &[CurrencyPair], start_currency: &str) -> Vec<Vec<Leg>> {
+    if pairs.len() != 3 {
+        return vec![];
+    }
+    PERMUTATIONS
+        .iter()
+        .filter_map(|order| {
+            let mut currency = start_currency;
+            let mut legs = vec![];
+            for index in order {
+                let currency_pair = &pairs[*index];
+                let side = if currency_pair.quote_currency == currency {
+                    OrderSide::Buy
+                } else if currency_pair.base_currency == currency {
+                    OrderSide::Sell
+                } else {
+                    return None;
+                };
+                legs.push(Leg {
+                    currency_pair: currency_pair.clone(),
+                    side,
+                });
+                currency = legs.last().unwrap().output_currency();
+            }
+            (currency == start_currency).then_some(legs)
+        })
+        .collect()
+}
+
+pub fn route_name(legs: &[Leg]) -> String {
+    let mut currencies: Vec<&str> = legs.iter().map(|leg| leg.input_currency()).collect();
+    currencies.extend(legs.last().map(|leg| leg.output_currency()));
+    currencies.join(" > ")
+}
+
+/// A leg priced at the top of its book
+#[derive(Debug, Clone)]
+pub struct PlannedLeg {
+    pub currency_pair: CurrencyPair,
+    pub side: OrderSide,
+    pub price: Decimal,
+    /// Base quantity of the order
+    pub quantity: Decimal,
+    /// Amount of the input currency the leg spends
+    pub input_amount: Decimal,
+}
+
+#[derive(Debug, Clone)]
+pub struct Opportunity {
+    pub route: String,
+    /// Fee adjusted return of a round trip, 0.001 is 10 bps
+    pub profit_ratio: Decimal,
+    /// Largest round trip, in the start currency, the top levels can fill
+    pub start_amount: Decimal,
+    pub expected_profit: Decimal,
+    pub legs: Vec<PlannedLeg>,
+}
+
+/// Prices the round trip against the best ask of each BUY leg and the best bid of each SELL
+/// leg, paying the fee on every leg. The size is the most the top levels can fill, capped at
+/// `max_start_amount`. None while a book is missing or empty.
+pub fn evaluate_route(
+    legs: &[Leg],
+    books: &HashMap<String, OrderBook>,
+    fee_rate: Decimal,
+    max_start_amount: Option<Decimal>,
+) -> Option<Opportunity> {
+    // start currency turned into each leg's input currency so far
+    let mut factor = Decimal::ONE;
+    let mut start_amount = max_start_amount;
+    let mut priced = vec![];
+    for leg in legs {
+        let book = books.get(&leg.currency_pair.symbol)?;
+        let level = *match leg.side {
+            OrderSide::Buy => book.best_ask(),
+            OrderSide::Sell => book.best_bid(),
+        }?;
+        if level.price <= Decimal::ZERO {
+            return None;
+        }
+        let (capacity, rate) = match leg.side {
+            OrderSide::Buy => (level.price * level.quantity, Decimal::ONE / level.price),
+            OrderSide::Sell => (level.quantity, level.price),
+        };
+        let leg_max = capacity / factor;
+        start_amount = Some(start_amount.map_or(leg_max, |amount| amount.min(leg_max)));
+        priced.push((leg, level.price, factor));
+        factor *= rate * (Decimal::ONE - fee_rate);
+    }
+    let start_amount = start_amount?;
+    let planned = priced
+        .into_iter()
+        .map(|(leg, price, leg_factor)| {
+            let input_amount = start_amount * leg_factor;
+            PlannedLeg {
+                currency_pair: leg.currency_pair.clone(),
+                side: leg.side,
+                price,
+                quantity: match leg.side {
+                    OrderSide::Buy => input_amount / price,
+                    OrderSide::Sell => input_amount,
+                },
+                input_amount,
+            }
+        })
+        .collect();
+    Some(Opportunity {
+        route: route_name(legs),
+        profit_ratio: factor - Decimal::ONE,
+        start_amount,
+        expected_profit: start_amount * (factor - Decimal::ONE),
+        legs: planned,
+    })
+}
+
+/// The order placed for one leg of an executed round trip
+#[derive(Debug, Clone)]
+struct LegOrder {
+    order_id: String,
+    currency_pair: CurrencyPair,
+    side: OrderSide,
+    quantity: Decimal,
+    filled: Decimal,
+    /// Output currency received so far, net of fees charged in it
+    received: Decimal,
+    /// Reported filled, cancelled or failed, nothing more fills
+    closed: bool,
+}
+
+impl LegOrder {
+    /// Fee adjusted or rounded fills may not add up to the quantity, a leg reported filled is
+    /// done all the same
+    fn is_done(&self) -> bool {
+        self.closed || self.filled >= self.quantity
+    }
+
+    /// A market order on the leg's pair that spends `amount` of what the leg received
+    async fn trade_back(&self, amount: Decimal, context: &StrategyContext) {
+        if amount <= Decimal::ZERO {
+            return;
+        }
+        let side = match self.side {
+            OrderSide::Buy => OrderSide::Sell,
+            OrderSide::Sell => OrderSide::Buy,
+        };
+        if let Err(e) = context
+            .executor
+            .place_market_order(side, amount, &self.currency_pair)
+            .await
+        {
+            error!(
+                "Arbitrage unwind of {} {} {} failed: {}",
+                self.currency_pair.symbol, side, amount, e
+            );
+        }
+    }
+}
+
+/// The legs of an executed round trip, followed until every one of them filled or was killed
+#[derive(Debug, Clone)]
+struct RoundTrip {
+    route: String,
+    legs: Vec<LegOrder>,
+    /// A leg could not be placed or was killed, what the others receive is traded back
+    failed: bool,
+}
+
+impl RoundTrip {
+    async fn fail(&mut self, context: &StrategyContext) {
+        if self.failed {
+            return;
+        }
+        self.failed = true;
+        warn!("Arbitrage {} failed, unwinding its legs", self.route);
+        for leg in &self.legs {
+            leg.trade_back(leg.received, context).await;
+        }
+    }
+}
+
+/// Follows the top of book of the three pairs of a triangle and logs every fee adjusted round
+/// trip above the minimum profit, sized by the depth of the top levels. With `execute` on, the
+/// three legs are placed at once as fill-or-kill orders at the prices seen, from balances
+/// already held in each leg's input currency, so the round trip is also limited by those
+/// balances. When a leg cannot be placed or is killed, whatever the other legs fill is traded
+/// back to their input currency at market.
+pub struct TriangularArbitrage {
+    config: ArbitrageConfig,
+    pairs: HashMap<String, CurrencyPair>,
+    books: HashMap<String, OrderBook>,
+    /// Built once every pair has been seen
+    routes: Option<Vec<Vec<Leg>>>,
+    /// Profit and size last logged per route, an unchanged opportunity is not logged again
+    logged: HashMap<String, (Decimal, Decimal)>,
+    /// Executed round trips with a leg still to fill or be killed
+    round_trips: Vec<RoundTrip>,
+}
+
+impl TriangularArbitrage {
+    pub fn new(config: ArbitrageConfig) -> Self {
+        TriangularArbitrage {
+            config,
+            pairs: HashMap::new(),
+            books: HashMap::new(),
+            routes: None,
+            logged: HashMap::new(),
+            round_trips: vec![],
+        }
+    }
+
+    async fn update(
+        &mut self,
+        currency_pair: &CurrencyPair,
+        order_book: OrderBook,
+        context: &StrategyContext,
+    ) {
+        if !self.config.pairs.contains(&currency_pair.symbol) {
+            return;
+        }
+        self.pairs
+            .insert(currency_pair.symbol.clone(), currency_pair.clone());
+        self.books.insert(currency_pair.symbol.clone(), order_book);
+        if self.routes.is_none() && self.pairs.len() == self.config.pairs.len() {
+            let pairs: Vec<CurrencyPair> = self
+                .config
+                .pairs
+                .iter()
+                .filter_map(|symbol| self.pairs.get(symbol).cloned())
+                .collect();
+            let routes = triangle_routes(&pairs, &self.config.start_currency);
+            if routes.is_empty() {
+                error!(
+                    "{} do not form a triangle through {}",
+                    self.config.pairs.join(", "),
+                    self.config.start_currency
+                );
+            }
+            self.routes = Some(routes);
+        }
+
+        for route in self.routes.clone().unwrap_or_default() {
+            let Some(opportunity) = evaluate_route(
+                &route,
+                &self.books,
+                self.config.fee_rate,
+                self.config.max_amount,
+            ) else {
+                continue;
+            };
+            if opportunity.profit_ratio * BASIS_POINTS < self.config.min_profit_bps
+                || opportunity.start_amount <= Decimal::ZERO
+            {
+                self.logged.remove(&opportunity.route);
+                continue;
+            }
+            self.log(&opportunity);
+            if self.config.execute {
+                self.execute(&route, opportunity, context).await;
+                return;
+            }
+        }
+    }
+
+    fn log(&mut self, opportunity: &Opportunity) {
+        let logged = (opportunity.profit_ratio, opportunity.start_amount);
+        if self.logged.get(&opportunity.route) == Some(&logged) {
+            return;
+        }
+        self.logged.insert(opportunity.route.clone(), logged);
+        println!(
+            "{}| {} {} {} bps on {} {}, expected profit {}",
+            Utc::now().to_rfc3339().blue(),
+            "Arbitrage".on_bright_blue(),
+            opportunity.route,
+            (opportunity.profit_ratio * BASIS_POINTS)
+                .round_dp(2)
+                .to_string()
+                .green(),
+            opportunity.start_amount.round_dp(8),
+            self.config.start_currency,
+            opportunity.expected_profit.round_dp(8)
+        );
+    }
+
+    async fn execute(
+        &mut self,
+        route: &[Leg],
+        opportunity: Opportunity,
+        context: &StrategyContext,
+    ) {
+        let balances_reader = context.balances.read().await;
+        // every leg is placed at once, so each spends a balance already held
+        let scale = route
+            .iter()
+            .zip(&opportunity.legs)
+            .map(|(leg, planned)| {
+                available_balance(&balances_reader, leg.input_currency()) / planned.input_amount
+            })
+            .fold(Decimal::ONE, Decimal::min);
+        drop(balances_reader);
+        if scale <= Decimal::ZERO {
+            warn!("No balance to trade {}", opportunity.route);
+            return;
+        }
+        let Some(opportunity) = evaluate_route(
+            route,
+            &self.books,
+            self.config.fee_rate,
+            Some(opportunity.start_amount * scale),
+        ) else {
+            return;
+        };
+        // nothing is placed unless every leg fits its pair's limits, a leg cut down to a maximum
+        // would leave the currency the next leg does not spend behind
+        let mut sized_legs = vec![];
+        for leg in &opportunity.legs {
+            let truncated = leg.quantity.round_dp_with_strategy(
+                leg.currency_pair.base_decimal_places,
+                RoundingStrategy::ToZero,
+            );
+            match size_limit_order(leg.side, leg.price, leg.quantity, &leg.currency_pair) {
+                Ok(sized) if sized.quantity < truncated => {
+                    warn!(
+                        "Arbitrage {} {} leg of {} is above the pair's maximum, not placed",
+                        leg.currency_pair.symbol, leg.side, opportunity.route
+                    );
+                    return;
+                }
+                Ok(sized) => sized_legs.push((leg, sized)),
+                Err(e) => {
+                    warn!(
+                        "Arbitrage {} {} leg of {} not placed: {}",
+                        leg.currency_pair.symbol, leg.side, opportunity.route, e
+                    );
+                    return;
+                }
+            }
+        }
+        // the books are stale once the legs are placed, wait for fresh snapshots of all three
+        self.books.clear();
+        let mut round_trip = RoundTrip {
+            route: opportunity.route.clone(),
+            legs: vec![],
+            failed: false,
+        };
+        for (leg, sized) in sized_legs {
+            match context
+                .executor
+                .place_fill_or_kill_order(leg.side, sized.price, sized.quantity, &leg.currency_pair)
+                .await
+            {
+                Ok(order_id) => round_trip.legs.push(LegOrder {
+                    order_id,
+                    currency_pair: leg.currency_pair.clone(),
+                    side: leg.side,
+                    quantity: sized.quantity,
+                    filled: Decimal::ZERO,
+                    received: Decimal::ZERO,
+                    closed: false,
+                }),
+                Err(e) => {
+                    error!(
+                        "Arbitrage {} {} leg of {} failed: {}",
+                        leg.currency_pair.symbol, leg.side, opportunity.route, e
+                    );
+                    round_trip.fail(context).await;
+                    break;
+                }
+            }
+        }
+        if !round_trip.legs.is_empty() {
+            self.round_trips.push(round_trip);
+        }
+    }
+
+    fn leg_order(&mut self, order_id: &str) -> Option<(&mut RoundTrip, usize)> {
+        self.round_trips.iter_mut().find_map(|round_trip| {
+            let index = round_trip
+                .legs
+                .iter()
+                .position(|leg| leg.order_id == order_id)?;
+            Some((round_trip, index))
+        })
+    }
+}
+
+#[async_trait]
+impl Strategy for TriangularArbitrage {
+    fn name(&self) -> &'static str {
+        "triangular_arbitrage"
+    }
+
+    fn pairs(&self) -> Vec<String> {
+        self.config.pairs.clone()
+    }
+
+    async fn on_book(&mut self, context: &StrategyContext) {
+        let order_book = context.order_book.read().await.clone();
+        self.update(&context.currency_pair, order_book, context)
+            .await;
+    }
+
+    async fn on_pair_book(
+        &mut self,
+        currency_pair: &CurrencyPair,
+        order_book: &OrderBook,
+        context: &StrategyContext,
+    ) {
+        self.update(currency_pair, order_book.clone(), context)
+            .await;
+    }
+
+    /// A filled leg is done, a killed leg fails its round trip
+    async fn on_order_status(&mut self, update: &OrderStatusUpdate, context: &StrategyContext) {
+        if !matches!(
+            update.order_status_type,
+            OrderStatus::Filled | OrderStatus::Cancelled | OrderStatus::Failed
+        ) {
+            return;
+        }
+        let Some((round_trip, index)) = self.leg_order(&update.order_id) else {
+            return;
+        };
+        round_trip.legs[index].closed = true;
+        if update.order_status_type != OrderStatus::Filled {
+            round_trip.fail(context).await;
+        }
+        self.round_trips
+            .retain(|round_trip| !round_trip.legs.iter().all(LegOrder::is_done));
+    }
+
+    async fn on_account_trade(&mut self, account_trade: &AccountTrade, context: &StrategyContext) {
+        let Some((round_trip, index)) = self.leg_order(&account_trade.order_id) else {
+            return;
+        };
+        let leg = &mut round_trip.legs[index];
+        let (received, received_currency) = match leg.side {
+            OrderSide::Buy => (account_trade.quantity, &leg.currency_pair.base_currency),
+            OrderSide::Sell => (
+                account_trade.price * account_trade.quantity,
+                &leg.currency_pair.quote_currency,
+            ),
+        };
+        let fee = match &account_trade.fee_currency {
+            Some(fee_currency) if fee_currency == received_currency => {
+                account_trade.fee.unwrap_or(Decimal::ZERO)
+            }
+            _ => Decimal::ZERO,
+        };
+        leg.filled += account_trade.quantity;
+        leg.received += received - fee;
+        if round_trip.failed {
+            leg.trade_back(received - fee, context).await;
+        }
+        self.round_trips
+            .retain(|round_trip| !round_trip.legs.iter().all(LegOrder::is_done));
+    }
+}
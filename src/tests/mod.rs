@@ -1 +1,133 @@
-pub mod test_sub_account;
\ No newline at end of file
+pub mod test_backtest;
+pub mod test_bollinger;
+pub mod test_break_of_structure;
+pub mod test_candles;
+pub mod test_connection;
+pub mod test_dca;
+pub mod test_grid;
+pub mod test_indicators;
+pub mod test_market_making;
+pub mod test_models;
+pub mod test_order_book;
+pub mod test_order_manager;
+pub mod test_paper;
+pub mod test_position;
+pub mod test_risk;
+pub mod test_shutdown;
+pub mod test_sizing;
+pub mod test_smc;
+pub mod test_strategies;
+// kept as it was written against the live API
+#[allow(clippy::unit_arg)]
+pub mod test_sub_account;
+pub mod test_triangular_arbitrage;
+
+#[cfg(test)]
+pub fn currency_pair() -> crate::rusty_bot_models::CurrencyPair {
+    currency_pair_of("BTC", "ZAR")
+}
+
+/// A pair with the limits of the BTCZAR fixture
+#[cfg(test)]
+pub fn currency_pair_of(base: &str, quote: &str) -> crate::rusty_bot_models::CurrencyPair {
+    serde_json::from_value(serde_json::json!({
+        "symbol": format!("{}{}", base, quote),
+        "baseCurrency": base,
+        "quoteCurrency": quote,
+        "shortName": format!("{}/{}", base, quote),
+        "active": true,
+        "minBaseAmount": "0.0001",
+        "maxBaseAmount": "100",
+        "minQuoteAmount": "10",
+        "maxQuoteAmount": "100000",
+        "tickSize": "1",
+        "baseDecimalPlaces": "8",
+        "marginTradingAllowed": false,
+        "currencyPairType": "SPOT"
+    }))
+    .unwrap()
+}
+
+/// A 1m BTCZAR candle without volume
+#[cfg(test)]
+pub fn candle(
+    start_time: &str,
+    open: i64,
+    high: i64,
+    low: i64,
+    close: i64,
+) -> crate::rusty_bot_models::MarkPriceBucket {
+    crate::rusty_bot_models::MarkPriceBucket {
+        currency_pair_symbol: String::from("BTCZAR"),
+        bucket_period_in_seconds: 60,
+        start_time: start_time.to_string(),
+        open: rust_decimal::Decimal::from(open),
+        high: rust_decimal::Decimal::from(high),
+        low: rust_decimal::Decimal::from(low),
+        close: rust_decimal::Decimal::from(close),
+        volume: rust_decimal::Decimal::ZERO,
+    }
+}
+
+/// Consecutive 1m candles from (open, high, low, close), the first starting at
+/// 2024-06-01T00:00:00Z
+#[cfg(test)]
+pub fn candles(prices: &[(i64, i64, i64, i64)]) -> Vec<crate::rusty_bot_models::MarkPriceBucket> {
+    prices
+        .iter()
+        .enumerate()
+        .map(|(minute, (open, high, low, close))| {
+            candle(
+                &format!("2024-06-01T{:02}:{:02}:00Z", minute / 60, minute % 60),
+                *open,
+                *high,
+                *low,
+                *close,
+            )
+        })
+        .collect()
+}
+
+/// A BTCZAR fill of one of the account's orders
+#[cfg(test)]
+pub fn account_trade(
+    order_id: &str,
+    side: crate::rusty_bot_models::OrderSide,
+    price: i64,
+    quantity: rust_decimal::Decimal,
+) -> crate::rusty_bot_models::AccountTrade {
+    crate::rusty_bot_models::AccountTrade {
+        price: rust_decimal::Decimal::from(price),
+        quantity,
+        currency_pair: String::from("BTCZAR"),
+        traded_at: String::from("2024-06-01T10:00:00.000Z"),
+        side,
+        order_id: order_id.to_string(),
+        id: format!("{}-trade", order_id),
+        fee: None,
+        fee_currency: None,
+    }
+}
+
+/// An ORDER_STATUS_UPDATE for a BTCZAR limit order
+#[cfg(test)]
+pub fn order_status(
+    order_id: &str,
+    status: crate::rusty_bot_models::OrderStatus,
+    side: crate::rusty_bot_models::OrderSide,
+) -> crate::rusty_bot_models::OrderStatusUpdate {
+    crate::rusty_bot_models::OrderStatusUpdate {
+        order_id: order_id.to_string(),
+        order_status_type: status,
+        currency_pair: String::from("BTCZAR"),
+        original_price: None,
+        remaining_quantity: rust_decimal::Decimal::ZERO,
+        original_quantity: rust_decimal::Decimal::ZERO,
+        order_side: side,
+        order_type: crate::rusty_bot_models::OrderType::Limit,
+        failed_reason: None,
+        order_updated_at: String::from("2024-06-01T10:00:00.000Z"),
+        order_created_at: String::from("2024-06-01T10:00:00.000Z"),
+        customer_order_id: None,
+    }
+}
@@ -0,0 +1,211 @@
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use rust_decimal::Decimal;
+
+    use crate::backtest::run_backtest;
+    use crate::candles::Timeframe;
+    use crate::rusty_bot_models::{AccountTrade, MarkPriceBucket, OrderSide};
+    use crate::strategies::{available_balance, Strategy, StrategyContext};
+    use crate::tests::{candle, currency_pair};
+
+    /// Buys with all quote on the first candle and sells everything on the third
+    struct BuyThenSell;
+
+    #[async_trait]
+    impl Strategy for BuyThenSell {
+        fn name(&self) -> &'static str {
+            "buy_then_sell"
+        }
+
+        async fn on_candle(
+            &mut self,
+            timeframe: Timeframe,
+            bucket_prices: &[MarkPriceBucket],
+            context: &StrategyContext,
+        ) {
+            let pair = &context.currency_pair;
+            match bucket_prices.len() {
+                1 => {
+                    context
+                        .executor
+                        .place_market_order(OrderSide::Buy, Decimal::from(1000), pair)
+                        .await
+                        .unwrap();
+                }
+                3 => {
+                    let close = bucket_prices.last().unwrap().close;
+                    context
+                        .executor
+                        .place_limit_order(
+                            OrderSide::Sell,
+                            close + Decimal::from(10),
+                            Decimal::from(100),
+                            pair,
+                        )
+                        .await
+                        .unwrap();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Rests a limit buy of 5 at 95 on the first candle and records what each candle hook sees
+    #[derive(Default)]
+    struct RestingBuy {
+        fills: usize,
+        /// Open orders, available base and fills so far at each candle
+        seen: Vec<(usize, Decimal, usize)>,
+    }
+
+    #[async_trait]
+    impl Strategy for RestingBuy {
+        fn name(&self) -> &'static str {
+            "resting_buy"
+        }
+
+        async fn on_candle(
+            &mut self,
+            timeframe: Timeframe,
+            bucket_prices: &[MarkPriceBucket],
+            context: &StrategyContext,
+        ) {
+            let pair = &context.currency_pair;
+            self.seen.push((
+                context.orders.read().await.open_orders().len(),
+                available_balance(&context.balances.read().await, &pair.base_currency),
+                self.fills,
+            ));
+            if bucket_prices.len() == 1 {
+                context
+                    .executor
+                    .place_limit_order(OrderSide::Buy, Decimal::from(95), Decimal::from(5), pair)
+                    .await
+                    .unwrap();
+            }
+        }
+
+        async fn on_account_trade(
+            &mut self,
+            account_trade: &AccountTrade,
+            context: &StrategyContext,
+        ) {
+            self.fills += 1;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_backtest_fills_on_following_candles() {
+        let buckets = vec![
+            candle("2024-06-01T00:03:00Z", 120, 130, 115, 125),
+            candle("2024-06-01T00:00:00Z", 100, 105, 95, 100),
+            candle("2024-06-01T00:01:00Z", 100, 112, 99, 110),
+            candle("2024-06-01T00:02:00Z", 110, 118, 108, 115),
+        ];
+
+        let report = run_backtest(
+            &mut BuyThenSell,
+            currency_pair(),
+            buckets,
+            Decimal::from(1000),
+            Decimal::ZERO,
+        )
+        .await;
+
+        assert_eq!(report.candles, 4);
+        assert_eq!(report.trades.len(), 2);
+        // the market buy fills at the open of the second candle
+        assert_eq!(report.trades[0].price, Decimal::from(100));
+        assert_eq!(report.trades[0].quantity, Decimal::from(10));
+        // the limit sell at 125 fills once the last candle trades through it
+        assert_eq!(report.trades[1].price, Decimal::from(125));
+        assert_eq!(report.ending_equity, Decimal::from(1250));
+        assert_eq!(report.profit_and_loss(), Decimal::from(250));
+    }
+
+    #[tokio::test]
+    async fn test_backtest_fills_resting_orders_before_the_candle_hooks() {
+        let mut strategy = RestingBuy::default();
+        let buckets = vec![
+            candle("2024-06-01T00:00:00Z", 100, 105, 98, 100),
+            candle("2024-06-01T00:01:00Z", 100, 101, 96, 97),
+            candle("2024-06-01T00:02:00Z", 97, 98, 94, 96),
+        ];
+
+        let report = run_backtest(
+            &mut strategy,
+            currency_pair(),
+            buckets,
+            Decimal::from(1000),
+            Decimal::ZERO,
+        )
+        .await;
+
+        assert_eq!(report.trades.len(), 1);
+        assert_eq!(report.trades[0].price, Decimal::from(95));
+        // the candle that trades through the buy shows its fill, balance and closed order
+        assert_eq!(
+            strategy.seen,
+            vec![
+                (0, Decimal::ZERO, 0),
+                (1, Decimal::ZERO, 0),
+                (0, Decimal::from(5), 1),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_backtest_charges_the_fee_rate_on_each_fill() {
+        let buckets = vec![
+            candle("2024-06-01T00:00:00Z", 100, 105, 95, 100),
+            candle("2024-06-01T00:01:00Z", 100, 112, 99, 110),
+            candle("2024-06-01T00:02:00Z", 110, 118, 108, 115),
+            candle("2024-06-01T00:03:00Z", 120, 130, 115, 125),
+        ];
+
+        let report = run_backtest(
+            &mut BuyThenSell,
+            currency_pair(),
+            buckets,
+            Decimal::from(1010),
+            Decimal::new(1, 2),
+        )
+        .await;
+
+        // the buy spends the fee out of the quote balance as well
+        assert_eq!(report.trades[0].quantity, Decimal::from(10));
+        assert_eq!(report.trades[0].fee, Decimal::from(10));
+        assert_eq!(report.trades[1].fee, Decimal::new(125, 1));
+        assert_eq!(report.fees_paid, Decimal::new(225, 1));
+        assert_eq!(report.quote_balance, Decimal::new(12375, 1));
+        assert_eq!(report.profit_and_loss(), Decimal::new(2275, 1));
+    }
+
+    #[tokio::test]
+    async fn test_backtest_report_marks_the_open_position_at_the_last_close() {
+        let buckets = vec![
+            candle("2024-06-01T00:00:00Z", 100, 105, 95, 100),
+            candle("2024-06-01T00:01:00Z", 100, 101, 90, 92),
+            candle("2024-06-01T00:02:00Z", 92, 93, 85, 90),
+        ];
+
+        let report = run_backtest(
+            &mut BuyThenSell,
+            currency_pair(),
+            buckets,
+            Decimal::from(1000),
+            Decimal::ZERO,
+        )
+        .await;
+
+        // the sell placed on the last candle never gets to fill
+        assert_eq!(report.candles, 3);
+        assert_eq!(report.trades.len(), 1);
+        assert_eq!(report.base_balance, Decimal::from(10));
+        assert_eq!(report.quote_balance, Decimal::ZERO);
+        assert_eq!(report.starting_equity, Decimal::from(1000));
+        assert_eq!(report.ending_equity, Decimal::from(900));
+        assert_eq!(report.profit_and_loss(), Decimal::from(-100));
+    }
+}
@@ -0,0 +1,329 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use rust_decimal::Decimal;
+    use tokio::sync::{Mutex, RwLock};
+
+    use crate::backtest::run_backtest;
+    use crate::candles::Timeframe;
+    use crate::config::BollingerConfig;
+    use crate::indicators::Bands;
+    use crate::order_book::OrderBook;
+    use crate::order_manager::OrderManager;
+    use crate::orders::{OrderError, OrderExecutor};
+    use crate::position::PositionTracker;
+    use crate::rusty_bot_models::{CurrencyPair, MarkPriceBucket, OrderSide};
+    use crate::simulation::{SimulatedAccount, SimulatedOrderExecutor};
+    use crate::strategies::bollinger::{mean_reversion_signal, BollingerMeanReversion, Signal};
+    use crate::strategies::{create_candle_engine, Strategy, StrategyContext};
+    use crate::tests::{candles, currency_pair};
+
+    fn strategy() -> BollingerMeanReversion {
+        BollingerMeanReversion::new(BollingerConfig {
+            timeframe: Timeframe::OneMinute,
+            period: 3,
+            standard_deviations: Decimal::ONE,
+            stop_percent: Decimal::from(2),
+            order_percent: Decimal::ONE_HUNDRED,
+        })
+    }
+
+    fn trades(report: &crate::backtest::BacktestReport) -> Vec<(OrderSide, Decimal, Decimal)> {
+        report
+            .trades
+            .iter()
+            .map(|t| (t.side, t.price, t.quantity))
+            .collect()
+    }
+
+    #[test]
+    fn test_signal_enters_below_the_band_and_exits_at_the_mean_or_stop() {
+        let bands = Bands {
+            upper: Decimal::from(110),
+            middle: Decimal::from(100),
+            lower: Decimal::from(90),
+        };
+        let signal = |close: i64, entry_price: Option<i64>| {
+            mean_reversion_signal(
+                Decimal::from(close),
+                bands,
+                entry_price.map(Decimal::from),
+                Decimal::from(5),
+            )
+        };
+        assert_eq!(signal(89, None), Some(Signal::Enter));
+        assert_eq!(signal(90, None), None);
+        // the upper band is not traded
+        assert_eq!(signal(111, None), None);
+        assert_eq!(signal(95, Some(89)), None);
+        assert_eq!(signal(100, Some(89)), Some(Signal::TakeProfit));
+        assert_eq!(signal(84, Some(89)), Some(Signal::StopLoss));
+    }
+
+    #[tokio::test]
+    async fn test_bollinger_buys_the_dip_and_sells_at_the_mean() {
+        let buckets = candles(&[
+            (100, 100, 100, 100),
+            (100, 100, 100, 100),
+            (100, 100, 100, 100),
+            (100, 100, 88, 88),
+            (80, 92, 80, 92),
+            (92, 95, 92, 95),
+            (96, 96, 96, 96),
+        ]);
+
+        let report = run_backtest(
+            &mut strategy(),
+            currency_pair(),
+            buckets,
+            Decimal::from(1000),
+            Decimal::ZERO,
+        )
+        .await;
+
+        // 88 is below the lower band of 96 - 5.66, 95 is above the middle of 91.67
+        assert_eq!(
+            trades(&report),
+            vec![
+                (OrderSide::Buy, Decimal::from(80), Decimal::new(125, 1)),
+                (OrderSide::Sell, Decimal::from(96), Decimal::new(125, 1)),
+            ]
+        );
+        assert_eq!(report.quote_balance, Decimal::from(1200));
+    }
+
+    #[tokio::test]
+    async fn test_bollinger_stops_out_below_the_entry() {
+        let buckets = candles(&[
+            (100, 100, 100, 100),
+            (100, 100, 100, 100),
+            (100, 100, 100, 100),
+            (100, 100, 88, 88),
+            (88, 88, 85, 85),
+            (85, 85, 85, 85),
+        ]);
+
+        let report = run_backtest(
+            &mut strategy(),
+            currency_pair(),
+            buckets,
+            Decimal::from(880),
+            Decimal::ZERO,
+        )
+        .await;
+
+        // 85 is under the 86.24 stop before the mean of 91 is reached
+        assert_eq!(
+            trades(&report),
+            vec![
+                (OrderSide::Buy, Decimal::from(88), Decimal::from(10)),
+                (OrderSide::Sell, Decimal::from(85), Decimal::from(10)),
+            ]
+        );
+        assert_eq!(report.quote_balance, Decimal::from(850));
+    }
+
+    /// Simulated executor whose first market sells are rejected
+    struct RejectingSells {
+        simulated: SimulatedOrderExecutor,
+        rejections: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl OrderExecutor for RejectingSells {
+        async fn place_limit_order(
+            &self,
+            side: OrderSide,
+            price: Decimal,
+            quantity: Decimal,
+            currency_pair: &CurrencyPair,
+        ) -> Result<String, OrderError> {
+            self.simulated
+                .place_limit_order(side, price, quantity, currency_pair)
+                .await
+        }
+
+        async fn place_market_order(
+            &self,
+            side: OrderSide,
+            amount: Decimal,
+            currency_pair: &CurrencyPair,
+        ) -> Result<String, OrderError> {
+            if side == OrderSide::Sell
+                && self
+                    .rejections
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                    .is_ok()
+            {
+                return Err(OrderError::Rejected(String::from("Insufficient liquidity")));
+            }
+            self.simulated
+                .place_market_order(side, amount, currency_pair)
+                .await
+        }
+
+        async fn cancel_order(
+            &self,
+            order_id: &str,
+            currency_pair: &CurrencyPair,
+        ) -> Result<(), OrderError> {
+            self.simulated.cancel_order(order_id, currency_pair).await
+        }
+
+        async fn cancel_all_orders(&self, currency_pair: &CurrencyPair) -> Result<(), OrderError> {
+            self.simulated.cancel_all_orders(currency_pair).await
+        }
+
+        async fn modify_order(
+            &self,
+            order_id: &str,
+            side: OrderSide,
+            price: Decimal,
+            quantity: Decimal,
+            currency_pair: &CurrencyPair,
+        ) -> Result<(), OrderError> {
+            self.simulated
+                .modify_order(order_id, side, price, quantity, currency_pair)
+                .await
+        }
+    }
+
+    fn context(
+        strategy: &BollingerMeanReversion,
+        account: &Arc<Mutex<SimulatedAccount>>,
+        sell_rejections: usize,
+    ) -> StrategyContext {
+        StrategyContext {
+            currency_pair: currency_pair(),
+            order_book: Arc::new(RwLock::new(OrderBook::default())),
+            balances: Arc::new(RwLock::new(Vec::new())),
+            orders: Arc::new(RwLock::new(OrderManager::default())),
+            positions: Arc::new(RwLock::new(PositionTracker::default())),
+            candles: Arc::new(RwLock::new(create_candle_engine(strategy))),
+            executor: Arc::new(RejectingSells {
+                simulated: SimulatedOrderExecutor {
+                    account: account.clone(),
+                },
+                rejections: AtomicUsize::new(sell_rejections),
+            }),
+        }
+    }
+
+    /// Fills the account's orders on each candle, then passes its trades and the candle on
+    async fn feed(
+        strategy: &mut BollingerMeanReversion,
+        context: &StrategyContext,
+        account: &Arc<Mutex<SimulatedAccount>>,
+        candles: &[MarkPriceBucket],
+    ) {
+        for candle in candles {
+            let mut account_writer = account.lock().await;
+            let trades = account_writer.match_candle(candle);
+            *context.balances.write().await = account_writer.balance_updates();
+            drop(account_writer);
+            for trade in &trades {
+                strategy
+                    .on_account_trade(&trade.to_account_trade(&context.currency_pair), context)
+                    .await;
+            }
+            let mut candles_writer = context.candles.write().await;
+            let updated: Vec<_> = candles_writer
+                .apply_bucket(candle)
+                .into_iter()
+                .map(|timeframe| (timeframe, candles_writer.candles(timeframe).to_vec()))
+                .collect();
+            drop(candles_writer);
+            for (timeframe, candles) in updated {
+                strategy.on_candle(timeframe, &candles, context).await;
+            }
+        }
+    }
+
+    fn account_trades(account: &SimulatedAccount) -> Vec<(OrderSide, Decimal)> {
+        account
+            .trades
+            .iter()
+            .map(|t| (t.side, t.quantity))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_bollinger_exit_sells_only_what_the_entry_bought() {
+        let mut strategy = strategy();
+        let account = Arc::new(Mutex::new(SimulatedAccount::new(
+            currency_pair(),
+            Decimal::from(1000),
+            Decimal::from(3),
+            Decimal::ZERO,
+        )));
+        let context = context(&strategy, &account, 0);
+
+        feed(
+            &mut strategy,
+            &context,
+            &account,
+            &candles(&[
+                (100, 100, 100, 100),
+                (100, 100, 100, 100),
+                (100, 100, 100, 100),
+                (100, 100, 88, 88),
+                (80, 92, 80, 92),
+                (92, 95, 92, 95),
+                (96, 96, 96, 96),
+            ]),
+        )
+        .await;
+
+        let account_reader = account.lock().await;
+        // the 3 BTC held before the entry are left alone
+        assert_eq!(
+            account_trades(&account_reader),
+            vec![
+                (OrderSide::Buy, Decimal::new(125, 1)),
+                (OrderSide::Sell, Decimal::new(125, 1)),
+            ]
+        );
+        assert_eq!(account_reader.base_balance, Decimal::from(3));
+    }
+
+    #[tokio::test]
+    async fn test_bollinger_keeps_the_entry_until_its_exit_is_accepted() {
+        let mut strategy = strategy();
+        let account = Arc::new(Mutex::new(SimulatedAccount::new(
+            currency_pair(),
+            Decimal::from(1000),
+            Decimal::ZERO,
+            Decimal::ZERO,
+        )));
+        let context = context(&strategy, &account, 1);
+
+        feed(
+            &mut strategy,
+            &context,
+            &account,
+            &candles(&[
+                (100, 100, 100, 100),
+                (100, 100, 100, 100),
+                (100, 100, 100, 100),
+                (100, 100, 88, 88),
+                (80, 92, 80, 92),
+                (92, 95, 92, 95),
+                (96, 96, 96, 96),
+                (97, 97, 97, 97),
+            ]),
+        )
+        .await;
+
+        // the exit rejected at the mean is placed again on the next close
+        assert_eq!(
+            account_trades(&*account.lock().await),
+            vec![
+                (OrderSide::Buy, Decimal::new(125, 1)),
+                (OrderSide::Sell, Decimal::new(125, 1)),
+            ]
+        );
+    }
+}
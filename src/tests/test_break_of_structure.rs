@@ -0,0 +1,116 @@
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::backtest::run_backtest;
+    use crate::candles::Timeframe;
+    use crate::config::BosConfig;
+    use crate::rusty_bot_models::OrderSide;
+    use crate::strategies::break_of_structure::{
+        find_swings, structure_breaks, BreakKind, BreakOfStructure, Direction, StructureBreak,
+        Swing, SwingKind,
+    };
+    use crate::tests::{candles, currency_pair};
+
+    /// (open, high, low, close), each candle opens at the previous close
+    const FIXTURE: [(i64, i64, i64, i64); 11] = [
+        (9, 10, 8, 9),
+        (9, 11, 9, 10),
+        (10, 14, 10, 13),
+        (13, 12, 9, 10),
+        (10, 12, 7, 8),
+        (8, 11, 8, 10),
+        (10, 13, 9, 12),
+        (12, 15, 11, 15),
+        (15, 14, 12, 13),
+        (13, 13, 10, 11),
+        (11, 12, 6, 6),
+    ];
+
+    #[test]
+    fn test_find_swings_confirms_fractals() {
+        let swing = |index: usize, kind: SwingKind, price: i64| Swing {
+            index,
+            kind,
+            price: Decimal::from(price),
+        };
+        assert_eq!(
+            find_swings(&candles(&FIXTURE), 2),
+            vec![
+                swing(2, SwingKind::High, 14),
+                swing(4, SwingKind::Low, 7),
+                swing(7, SwingKind::High, 15),
+            ]
+        );
+        // the high at 7 needs two candles after it to be confirmed
+        assert_eq!(
+            find_swings(&candles(&FIXTURE[..9]), 2),
+            vec![swing(2, SwingKind::High, 14), swing(4, SwingKind::Low, 7)]
+        );
+        assert!(find_swings(&candles(&FIXTURE[..4]), 2).is_empty());
+        assert!(find_swings(&candles(&FIXTURE), 20).is_empty());
+        assert!(find_swings(&[], 3).is_empty());
+    }
+
+    #[test]
+    fn test_structure_breaks_then_changes_character() {
+        assert_eq!(
+            structure_breaks(&candles(&FIXTURE), 2),
+            vec![
+                StructureBreak {
+                    index: 7,
+                    direction: Direction::Bullish,
+                    kind: BreakKind::BreakOfStructure,
+                    swing_index: 2,
+                    level: Decimal::from(14),
+                },
+                StructureBreak {
+                    index: 10,
+                    direction: Direction::Bearish,
+                    kind: BreakKind::ChangeOfCharacter,
+                    swing_index: 4,
+                    level: Decimal::from(7),
+                },
+            ]
+        );
+        // a swing is only broken once, the close at 15 leaves no high to break above
+        let breaks = structure_breaks(&candles(&FIXTURE[..10]), 2);
+        assert_eq!(breaks.len(), 1);
+        assert!(structure_breaks(&candles(&FIXTURE[..3]), 5).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_break_of_structure_buys_the_break_and_sells_the_change() {
+        let mut prices = FIXTURE.to_vec();
+        prices.extend([(6, 9, 5, 5), (5, 8, 4, 5)]);
+        let mut strategy = BreakOfStructure::new(BosConfig {
+            timeframe: Timeframe::OneMinute,
+            swing_width: 2,
+            lookback: 60,
+        });
+
+        let report = run_backtest(
+            &mut strategy,
+            currency_pair(),
+            candles(&prices),
+            Decimal::from(1300),
+            Decimal::ZERO,
+        )
+        .await;
+
+        // the break at 7 is traded once the next candle opens, at its close of 13, and the
+        // change at 10 sells at the close of 5 of the candle after it
+        let trades: Vec<(OrderSide, Decimal, Decimal)> = report
+            .trades
+            .iter()
+            .map(|t| (t.side, t.price, t.quantity))
+            .collect();
+        assert_eq!(
+            trades,
+            vec![
+                (OrderSide::Buy, Decimal::from(13), Decimal::from(100)),
+                (OrderSide::Sell, Decimal::from(5), Decimal::from(100)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,127 @@
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::candles::{CandleEngine, Timeframe};
+    use crate::rusty_bot_models::MarkPriceBucket;
+
+    fn bucket(
+        start_time: &str,
+        period: u16,
+        open: i64,
+        high: i64,
+        low: i64,
+        close: i64,
+    ) -> MarkPriceBucket {
+        MarkPriceBucket {
+            currency_pair_symbol: String::from("BTCZAR"),
+            bucket_period_in_seconds: period,
+            start_time: start_time.to_string(),
+            open: Decimal::from(open),
+            high: Decimal::from(high),
+            low: Decimal::from(low),
+            close: Decimal::from(close),
+            volume: Decimal::ZERO,
+        }
+    }
+
+    fn ohlc(candle: &MarkPriceBucket) -> (Decimal, Decimal, Decimal, Decimal) {
+        (candle.open, candle.high, candle.low, candle.close)
+    }
+
+    #[test]
+    fn test_timeframe_from_str() {
+        assert_eq!("15m".parse::<Timeframe>(), Ok(Timeframe::FifteenMinutes));
+        assert_eq!("4H".parse::<Timeframe>(), Ok(Timeframe::FourHours));
+        assert!("2m".parse::<Timeframe>().is_err());
+        assert_eq!(Timeframe::OneHour.to_string(), "1h");
+    }
+
+    #[test]
+    fn test_trade_buckets_build_candles_for_every_timeframe() {
+        let mut engine = CandleEngine::new(&[Timeframe::OneMinute, Timeframe::FiveMinutes]);
+
+        assert_eq!(
+            engine.apply_bucket(&bucket("2024-06-01T00:00:00Z", 60, 100, 100, 100, 100)),
+            vec![Timeframe::OneMinute, Timeframe::FiveMinutes]
+        );
+        engine.apply_bucket(&bucket("2024-06-01T00:00:00Z", 60, 100, 110, 95, 95));
+        engine.apply_bucket(&bucket("2024-06-01T00:03:00Z", 60, 105, 105, 105, 105));
+
+        let one_minute = engine.candles(Timeframe::OneMinute);
+        assert_eq!(one_minute.len(), 4);
+        assert_eq!(one_minute[0].start_time, "2024-06-01T00:00:00Z");
+        assert_eq!(
+            ohlc(&one_minute[0]),
+            (100.into(), 110.into(), 95.into(), 95.into())
+        );
+        // minutes without trades carry the previous close
+        assert_eq!(one_minute[1].start_time, "2024-06-01T00:01:00Z");
+        assert_eq!(
+            ohlc(&one_minute[2]),
+            (95.into(), 95.into(), 95.into(), 95.into())
+        );
+        assert_eq!(one_minute[3].start_time, "2024-06-01T00:03:00Z");
+
+        let five_minutes = engine.candles(Timeframe::FiveMinutes);
+        assert_eq!(five_minutes.len(), 1);
+        assert_eq!(five_minutes[0].bucket_period_in_seconds, 300);
+        assert_eq!(
+            ohlc(&five_minutes[0]),
+            (100.into(), 110.into(), 95.into(), 105.into())
+        );
+
+        // a late bucket still counts towards the forming 5m candle but not the closed 1m one
+        assert_eq!(
+            engine.apply_bucket(&bucket("2024-06-01T00:02:00Z", 60, 500, 500, 500, 500)),
+            vec![Timeframe::FiveMinutes]
+        );
+        assert_eq!(
+            engine.candles(Timeframe::OneMinute)[2].high,
+            Decimal::from(95)
+        );
+        assert!(engine.candles(Timeframe::OneHour).is_empty());
+    }
+
+    #[test]
+    fn test_history_is_rolled_up_into_longer_timeframes() {
+        let mut engine = CandleEngine::default();
+        engine.seed(
+            Timeframe::FourHours,
+            vec![
+                bucket("2024-06-01T04:00:00Z", 3600, 120, 125, 118, 121),
+                bucket("2024-06-01T01:00:00Z", 3600, 105, 115, 104, 112),
+                bucket("2024-06-01T00:00:00Z", 3600, 100, 108, 99, 105),
+                bucket("2024-06-01T03:00:00Z", 3600, 114, 119, 97, 120),
+                bucket("2024-06-01T02:00:00Z", 3600, 112, 116, 110, 114),
+            ],
+        );
+
+        let candles = engine.candles(Timeframe::FourHours);
+        assert_eq!(engine.timeframes(), vec![Timeframe::FourHours]);
+        assert_eq!(candles.len(), 2);
+        assert_eq!(
+            ohlc(&candles[0]),
+            (100.into(), 119.into(), 97.into(), 120.into())
+        );
+        assert_eq!(candles[1].start_time, "2024-06-01T04:00:00Z");
+
+        // repeated updates of the forming bucket merge into the same candle
+        engine.apply_bucket(&bucket("2024-06-01T05:00:00Z", 3600, 121, 122, 121, 122));
+        engine.apply_bucket(&bucket("2024-06-01T05:00:00Z", 3600, 121, 130, 121, 128));
+        let candles = engine.candles(Timeframe::FourHours);
+        assert_eq!(candles.len(), 2);
+        assert_eq!(
+            ohlc(&candles[1]),
+            (120.into(), 130.into(), 118.into(), 128.into())
+        );
+
+        // 4h buckets cannot be split into 1h candles
+        engine.subscribe(Timeframe::OneHour);
+        assert_eq!(
+            engine.apply_bucket(&bucket("2024-06-01T08:00:00Z", 14400, 128, 128, 128, 128)),
+            vec![Timeframe::FourHours]
+        );
+        assert!(engine.candles(Timeframe::OneHour).is_empty());
+    }
+}
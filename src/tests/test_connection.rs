@@ -0,0 +1,31 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use crate::connection::{reconnect_delay, LastPong};
+
+    #[test]
+    fn test_reconnect_delay_backs_off_exponentially_up_to_a_minute() {
+        assert_eq!(reconnect_delay(0), Duration::from_secs(1));
+        assert_eq!(reconnect_delay(1), Duration::from_secs(2));
+        assert_eq!(reconnect_delay(5), Duration::from_secs(32));
+        assert_eq!(reconnect_delay(6), Duration::from_secs(60));
+        assert_eq!(reconnect_delay(u32::MAX), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_connection_is_dead_once_pongs_stop() {
+        let connected_at = Instant::now();
+        let pong_timeout = Duration::from_secs(30);
+        let last_pong = LastPong::new(connected_at);
+
+        assert!(!last_pong.is_overdue(connected_at + Duration::from_secs(30), pong_timeout));
+        assert!(last_pong.is_overdue(connected_at + Duration::from_secs(31), pong_timeout));
+
+        last_pong.record(connected_at + Duration::from_secs(20));
+        assert!(!last_pong.is_overdue(connected_at + Duration::from_secs(31), pong_timeout));
+        assert!(last_pong.is_overdue(connected_at + Duration::from_secs(51), pong_timeout));
+    }
+}
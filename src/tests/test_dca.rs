@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::backtest::run_backtest;
+    use crate::candles::Timeframe;
+    use crate::config::DcaConfig;
+    use crate::rusty_bot_models::MarkPriceBucket;
+    use crate::strategies::dca::{buy_amount, Dca};
+    use crate::tests::{candles, currency_pair};
+
+    /// Candles that open and close at each price
+    fn flat_candles(prices: &[i64]) -> Vec<MarkPriceBucket> {
+        let prices: Vec<(i64, i64, i64, i64)> = prices.iter().map(|p| (*p, *p, *p, *p)).collect();
+        candles(&prices)
+    }
+
+    fn dca() -> Dca {
+        Dca::new(DcaConfig {
+            quote_amount: Decimal::from(100),
+            interval_minutes: 2,
+            ma_timeframe: Timeframe::OneMinute,
+            ma_period: 2,
+            dip_multiplier: Decimal::from(2),
+        })
+    }
+
+    #[test]
+    fn test_buy_amount_scales_below_the_moving_average() {
+        let amount = |price: i64, moving_average: Option<i64>| {
+            buy_amount(
+                Decimal::from(100),
+                Decimal::from(price),
+                moving_average.map(Decimal::from),
+                Decimal::new(15, 1),
+            )
+        };
+        assert_eq!(amount(90, Some(100)), Decimal::from(150));
+        assert_eq!(amount(100, Some(100)), Decimal::from(100));
+        assert_eq!(amount(110, Some(100)), Decimal::from(100));
+        assert_eq!(amount(90, None), Decimal::from(100));
+    }
+
+    #[tokio::test]
+    async fn test_dca_buys_on_schedule_and_more_in_a_dip() {
+        let buckets = flat_candles(&[100, 100, 80, 80, 100, 100]);
+
+        let report = run_backtest(
+            &mut dca(),
+            currency_pair(),
+            buckets,
+            Decimal::from(1000),
+            Decimal::ZERO,
+        )
+        .await;
+
+        // market buys fill at the next candle's open, the buy at 80 is under the 90 average
+        let trades: Vec<(Decimal, Decimal)> = report
+            .trades
+            .iter()
+            .map(|t| (t.price, t.quantity))
+            .collect();
+        assert_eq!(
+            trades,
+            vec![
+                (Decimal::from(100), Decimal::ONE),
+                (Decimal::from(80), Decimal::new(25, 1)),
+                (Decimal::from(100), Decimal::ONE),
+            ]
+        );
+        assert_eq!(report.quote_balance, Decimal::from(600));
+    }
+
+    #[tokio::test]
+    async fn test_dca_skips_a_buy_the_balance_cannot_cover() {
+        let buckets = flat_candles(&[100, 100, 80, 80, 100, 100]);
+
+        let report = run_backtest(
+            &mut dca(),
+            currency_pair(),
+            buckets,
+            Decimal::from(250),
+            Decimal::ZERO,
+        )
+        .await;
+
+        // the doubled buy needs 200 with 150 left, the next regular buy goes ahead
+        assert_eq!(report.trades.len(), 2);
+        assert_eq!(report.quote_balance, Decimal::from(50));
+    }
+}
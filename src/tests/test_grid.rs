@@ -0,0 +1,187 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rust_decimal::Decimal;
+    use tokio::sync::RwLock;
+
+    use crate::backtest::run_backtest;
+    use crate::candles::CandleEngine;
+    use crate::config::GridConfig;
+    use crate::order_book::{OrderBook, PriceLevel};
+    use crate::order_manager::OrderManager;
+    use crate::paper::PaperOrderExecutor;
+    use crate::position::PositionTracker;
+    use crate::rusty_bot_models::{OrderSide, OrderStatus};
+    use crate::simulation::SimulatedAccount;
+    use crate::strategies::grid::{grid_prices, Grid};
+    use crate::strategies::{Strategy, StrategyContext};
+    use crate::tests::{account_trade, candle, currency_pair, order_status};
+
+    fn grid_config() -> GridConfig {
+        GridConfig {
+            lower_price: Some(Decimal::from(100)),
+            upper_price: Some(Decimal::from(200)),
+            levels: 5,
+            range_percent: Decimal::from(5),
+        }
+    }
+
+    /// Open orders as (side, price, quantity) with their ids
+    async fn open_orders(context: &StrategyContext) -> Vec<(String, (OrderSide, i64, Decimal))> {
+        context
+            .orders
+            .read()
+            .await
+            .open_orders()
+            .iter()
+            .map(|o| {
+                let price = o.price.trunc().try_into().unwrap();
+                (o.order_id.clone(), (o.side, price, o.original_quantity))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_grid_prices_are_evenly_spaced_on_ticks() {
+        let to_decimals =
+            |prices: &[i64]| prices.iter().map(|p| Decimal::from(*p)).collect::<Vec<_>>();
+        assert_eq!(
+            grid_prices(Decimal::from(100), Decimal::from(200), 5, Decimal::ONE),
+            to_decimals(&[100, 125, 150, 175, 200])
+        );
+        assert_eq!(
+            grid_prices(Decimal::from(100), Decimal::from(200), 4, Decimal::from(10)),
+            to_decimals(&[100, 130, 160, 200])
+        );
+        // levels closer than a tick collapse into one
+        assert_eq!(
+            grid_prices(Decimal::from(100), Decimal::from(102), 5, Decimal::ONE),
+            to_decimals(&[100, 101, 102])
+        );
+        assert!(grid_prices(Decimal::from(200), Decimal::from(100), 5, Decimal::ONE).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_grid_replaces_the_opposite_order_on_each_fill() {
+        let mut grid = Grid::new(grid_config());
+        let buckets = vec![
+            candle("2024-06-01T00:00:00Z", 150, 150, 150, 150),
+            candle("2024-06-01T00:01:00Z", 140, 140, 120, 130),
+            candle("2024-06-01T00:02:00Z", 135, 160, 130, 155),
+            candle("2024-06-01T00:03:00Z", 150, 150, 124, 126),
+        ];
+
+        let report = run_backtest(
+            &mut grid,
+            currency_pair(),
+            buckets,
+            Decimal::from(1000),
+            Decimal::ZERO,
+        )
+        .await;
+
+        let trades: Vec<(OrderSide, Decimal, Decimal)> = report
+            .trades
+            .iter()
+            .map(|t| (t.side, t.price, t.quantity))
+            .collect();
+        // half the quote goes to each of the buys at 100 and 125
+        assert_eq!(
+            trades,
+            vec![
+                (OrderSide::Buy, Decimal::from(125), Decimal::from(4)),
+                (OrderSide::Sell, Decimal::from(150), Decimal::from(4)),
+                (OrderSide::Buy, Decimal::from(125), Decimal::from(4)),
+            ]
+        );
+        assert_eq!(report.quote_balance, Decimal::from(600));
+    }
+
+    #[tokio::test]
+    async fn test_grid_follows_partial_fills_and_replaces_cancelled_orders() {
+        let level = |price: i64| PriceLevel {
+            price: Decimal::from(price),
+            quantity: Decimal::ONE,
+        };
+        let book = Arc::new(RwLock::new(OrderBook {
+            asks: vec![level(151)],
+            bids: vec![level(149)],
+        }));
+        let balances = Arc::new(RwLock::new(vec![]));
+        let orders = Arc::new(RwLock::new(OrderManager::default()));
+        let positions = Arc::new(RwLock::new(PositionTracker::default()));
+        let executor = PaperOrderExecutor::new(
+            SimulatedAccount::new(
+                currency_pair(),
+                Decimal::from(1000),
+                Decimal::from(8),
+                Decimal::ZERO,
+            ),
+            book.clone(),
+            balances.clone(),
+            orders.clone(),
+            positions.clone(),
+        )
+        .await;
+        let context = StrategyContext {
+            currency_pair: currency_pair(),
+            order_book: book,
+            balances,
+            orders,
+            positions,
+            candles: Arc::new(RwLock::new(CandleEngine::default())),
+            executor: Arc::new(executor),
+        };
+        let mut grid = Grid::new(grid_config());
+        let find = |orders: &[(String, (OrderSide, i64, Decimal))], side, price| {
+            orders
+                .iter()
+                .find(|(_, (s, p, _))| *s == side && *p == price)
+                .map(|(order_id, _)| order_id.clone())
+                .unwrap()
+        };
+
+        grid.on_book(&context).await;
+        let ladder = open_orders(&context).await;
+        assert_eq!(ladder.len(), 4);
+        let buy_125 = find(&ladder, OrderSide::Buy, 125);
+        let sell_175 = find(&ladder, OrderSide::Sell, 175);
+
+        // a partial fill of the buy at 125 is sold one level up straight away
+        grid.on_account_trade(
+            &account_trade(&buy_125, OrderSide::Buy, 125, Decimal::ONE),
+            &context,
+        )
+        .await;
+        let orders = open_orders(&context).await;
+        assert_eq!(orders.len(), 5);
+        assert!(orders
+            .iter()
+            .any(|(_, order)| *order == (OrderSide::Sell, 150, Decimal::ONE)));
+
+        // a cancelled order is not a fill, it is placed again once the balances are updated
+        context
+            .executor
+            .cancel_order(&sell_175, &context.currency_pair)
+            .await
+            .unwrap();
+        grid.on_order_status(
+            &order_status(&sell_175, OrderStatus::Cancelled, OrderSide::Sell),
+            &context,
+        )
+        .await;
+        assert_eq!(open_orders(&context).await.len(), 4);
+        let balance_update = context.balances.read().await[0].clone();
+        grid.on_balance(&balance_update, &context).await;
+        let orders = open_orders(&context).await;
+        assert_eq!(orders.len(), 5);
+        assert!(orders
+            .iter()
+            .any(|(_, order)| *order == (OrderSide::Sell, 175, Decimal::from(4))));
+        // a fill would have bought back one level down
+        assert!(!orders
+            .iter()
+            .any(|(_, (side, price, _))| *side == OrderSide::Buy && *price == 150));
+    }
+}
@@ -0,0 +1,207 @@
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::candles::{CandleEngine, Timeframe};
+    use crate::indicators::{
+        Atr, Bands, BollingerBands, Donchian, Ema, Indicator, IndicatorValue, Macd, Rsi, Sma, Vwap,
+    };
+    use crate::rusty_bot_models::MarkPriceBucket;
+    use crate::tests::candle;
+
+    /// A 1m bucket that opens and closes at the price
+    fn bucket(start_time: &str, price: i64) -> MarkPriceBucket {
+        candle(start_time, price, price, price, price)
+    }
+
+    #[test]
+    fn test_moving_averages() {
+        let mut sma = Sma::new(3);
+        let smas: Vec<_> = [1, 2, 3, 4]
+            .iter()
+            .map(|close| sma.update_value(Decimal::from(*close)))
+            .collect();
+        assert_eq!(smas, vec![None, None, Some(2.into()), Some(3.into())]);
+
+        let mut ema = Ema::new(3);
+        let emas: Vec<_> = [1, 2, 3, 4, 5]
+            .iter()
+            .map(|close| ema.update_value(Decimal::from(*close)))
+            .collect();
+        assert_eq!(
+            emas,
+            vec![None, None, Some(2.into()), Some(3.into()), Some(4.into())]
+        );
+
+        let mut macd = Macd::new(2, 3, 2);
+        for close in 1..=4 {
+            macd.update_value(Decimal::from(close));
+        }
+        let value = macd.update_value(Decimal::from(5)).unwrap();
+        assert_eq!(value.macd.round_dp(8), Decimal::new(5, 1));
+        assert_eq!(value.signal.round_dp(8), Decimal::new(5, 1));
+        assert_eq!(value.histogram.round_dp(8), Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_oscillators_and_volatility() {
+        let mut rsi = Rsi::new(2);
+        assert_eq!(rsi.update_value(Decimal::from(1)), None);
+        assert_eq!(rsi.update_value(Decimal::from(2)), None);
+        assert_eq!(
+            rsi.update_value(Decimal::from(3)),
+            Some(Decimal::ONE_HUNDRED)
+        );
+        assert_eq!(rsi.update_value(Decimal::from(2)), Some(Decimal::from(50)));
+
+        let mut atr = Atr::new(2);
+        assert_eq!(
+            atr.update(&candle("2024-06-01T00:00:00Z", 9, 10, 8, 9)),
+            None
+        );
+        assert_eq!(
+            atr.update(&candle("2024-06-01T00:01:00Z", 10, 11, 9, 10)),
+            Some(Decimal::from(2))
+        );
+        // gaps up from the previous close count towards the true range
+        assert_eq!(
+            atr.update(&candle("2024-06-01T00:02:00Z", 13, 14, 11, 13)),
+            Some(Decimal::new(3, 0))
+        );
+
+        let mut bollinger_bands = BollingerBands::new(8, Decimal::TWO);
+        let mut bands = None;
+        for close in [2, 4, 4, 4, 5, 5, 7, 9] {
+            bands = bollinger_bands.update_value(Decimal::from(close));
+        }
+        let bands = bands.unwrap();
+        assert_eq!(bands.middle, Decimal::from(5));
+        assert_eq!(bands.upper.round_dp(8), Decimal::from(9));
+        assert_eq!(bands.lower.round_dp(8), Decimal::from(1));
+
+        let mut donchian = Donchian::new(2);
+        assert_eq!(
+            donchian.update(&candle("2024-06-01T00:00:00Z", 9, 10, 8, 9)),
+            None
+        );
+        donchian.update(&candle("2024-06-01T00:01:00Z", 10, 12, 9, 10));
+        assert_eq!(
+            donchian.update(&candle("2024-06-01T00:02:00Z", 11, 11, 10, 11)),
+            Some(Bands {
+                upper: Decimal::from(12),
+                middle: Decimal::new(105, 1),
+                lower: Decimal::from(9),
+            })
+        );
+    }
+
+    #[test]
+    fn test_vwap_resets_every_utc_day() {
+        let mut vwap = Vwap::new();
+        assert_eq!(
+            vwap.update(&candle("2024-06-01T00:00:00Z", 9, 12, 9, 9)),
+            None
+        );
+        vwap.update(&MarkPriceBucket {
+            volume: Decimal::from(1),
+            ..candle("2024-06-01T00:01:00Z", 9, 12, 9, 9)
+        });
+        assert_eq!(
+            vwap.update(&MarkPriceBucket {
+                volume: Decimal::from(3),
+                ..candle("2024-06-01T00:02:00Z", 13, 13, 10, 13)
+            }),
+            Some(Decimal::new(115, 1))
+        );
+        assert_eq!(
+            vwap.update(&MarkPriceBucket {
+                volume: Decimal::from(2),
+                ..candle("2024-06-02T00:00:00Z", 20, 21, 19, 20)
+            }),
+            Some(Decimal::from(20))
+        );
+    }
+
+    #[test]
+    fn test_attached_indicator_feeds_each_closed_candle_once() {
+        let mut engine = CandleEngine::new(&[Timeframe::OneMinute]);
+        engine.apply_bucket(&bucket("2024-06-01T00:00:00Z", 100));
+        engine.attach_indicator(Timeframe::OneMinute, "sma_2", Indicator::Sma(Sma::new(2)));
+        assert_eq!(engine.indicator(Timeframe::OneMinute, "sma_2"), None);
+
+        engine.apply_bucket(&bucket("2024-06-01T00:01:00Z", 110));
+        assert_eq!(
+            engine.indicator(Timeframe::OneMinute, "sma_2"),
+            Some(IndicatorValue::Single(Decimal::from(105)))
+        );
+        // an update of the forming candle moves the value without being counted twice
+        engine.apply_bucket(&bucket("2024-06-01T00:01:00Z", 120));
+        assert_eq!(
+            engine
+                .indicator(Timeframe::OneMinute, "sma_2")
+                .and_then(|value| value.single()),
+            Some(Decimal::from(110))
+        );
+        engine.apply_bucket(&bucket("2024-06-01T00:02:00Z", 130));
+        assert_eq!(
+            engine
+                .indicator(Timeframe::OneMinute, "sma_2")
+                .and_then(|value| value.single()),
+            Some(Decimal::from(125))
+        );
+        assert_eq!(
+            engine.closed_indicator_values(Timeframe::OneMinute),
+            vec![("sma_2", IndicatorValue::Single(Decimal::from(110)))]
+        );
+        assert_eq!(engine.indicator(Timeframe::OneMinute, "ema_2"), None);
+    }
+
+    #[test]
+    fn test_indicators_parse_from_their_settings() {
+        let mut engine = CandleEngine::new(&[Timeframe::OneMinute]);
+        for (name, setting) in [
+            ("rsi", "RSI:2"),
+            ("bands", "bollinger:2:2"),
+            ("macd", "macd:1:2:1"),
+        ] {
+            engine.attach_indicator(Timeframe::OneMinute, name, setting.parse().unwrap());
+        }
+        for (start_time, price) in [
+            ("2024-06-01T00:00:00Z", 100),
+            ("2024-06-01T00:01:00Z", 110),
+            ("2024-06-01T00:02:00Z", 120),
+        ] {
+            engine.apply_bucket(&bucket(start_time, price));
+        }
+        assert_eq!(
+            engine.indicator(Timeframe::OneMinute, "rsi"),
+            Some(IndicatorValue::Single(Decimal::from(100)))
+        );
+        assert_eq!(
+            engine
+                .indicator(Timeframe::OneMinute, "bands")
+                .and_then(|value| value.bands())
+                .map(|bands| bands.middle),
+            Some(Decimal::from(115))
+        );
+        assert!(matches!(
+            engine.indicator(Timeframe::OneMinute, "macd"),
+            Some(IndicatorValue::Macd(_))
+        ));
+
+        for setting in ["vwap", "ema:20", "atr:14", "sma:5", "donchian:20"] {
+            assert!(setting.parse::<Indicator>().is_ok(), "{}", setting);
+        }
+        for setting in [
+            "",
+            "rsi",
+            "rsi:0",
+            "vwap:14",
+            "macd:12:26",
+            "bollinger:20:x",
+            "kama:10",
+        ] {
+            assert!(setting.parse::<Indicator>().is_err(), "{}", setting);
+        }
+    }
+}
@@ -0,0 +1,201 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rust_decimal::Decimal;
+    use tokio::sync::RwLock;
+
+    use crate::candles::CandleEngine;
+    use crate::config::MarketMakingConfig;
+    use crate::order_book::{OrderBook, PriceLevel};
+    use crate::order_manager::OrderManager;
+    use crate::paper::PaperOrderExecutor;
+    use crate::position::PositionTracker;
+    use crate::rusty_bot_models::{OrderSide, OrderStatus};
+    use crate::simulation::SimulatedAccount;
+    use crate::strategies::market_making::{inventory_ratio, quote_prices, MarketMaker, Quotes};
+    use crate::strategies::{Strategy, StrategyContext};
+    use crate::tests::{currency_pair, order_status};
+
+    fn order_book(best_bid: i64, best_ask: i64) -> OrderBook {
+        let level = |price: i64| PriceLevel {
+            price: Decimal::from(price),
+            quantity: Decimal::ONE,
+        };
+        OrderBook {
+            asks: vec![level(best_ask)],
+            bids: vec![level(best_bid)],
+        }
+    }
+
+    /// Paper account with 10000 ZAR and 10 BTC
+    async fn paper_context(book: Arc<RwLock<OrderBook>>) -> StrategyContext {
+        let balances = Arc::new(RwLock::new(vec![]));
+        let orders = Arc::new(RwLock::new(OrderManager::default()));
+        let positions = Arc::new(RwLock::new(PositionTracker::default()));
+        let executor = PaperOrderExecutor::new(
+            SimulatedAccount::new(
+                currency_pair(),
+                Decimal::from(10000),
+                Decimal::from(10),
+                Decimal::ZERO,
+            ),
+            book.clone(),
+            balances.clone(),
+            orders.clone(),
+            positions.clone(),
+        )
+        .await;
+        StrategyContext {
+            currency_pair: currency_pair(),
+            order_book: book,
+            balances,
+            orders,
+            positions,
+            candles: Arc::new(RwLock::new(CandleEngine::default())),
+            executor: Arc::new(executor),
+        }
+    }
+
+    fn market_maker() -> MarketMaker {
+        MarketMaker::new(MarketMakingConfig {
+            half_spread_bps: Decimal::from(100),
+            volatility_multiplier: Decimal::ZERO,
+            order_percent: Decimal::from(10),
+            inventory_skew: Decimal::ONE,
+            refresh_bps: Decimal::from(50),
+        })
+    }
+
+    async fn order_ids(context: &StrategyContext) -> Vec<String> {
+        context
+            .orders
+            .read()
+            .await
+            .open_orders()
+            .iter()
+            .map(|o| o.order_id.clone())
+            .collect()
+    }
+
+    /// Open order prices, bids then asks
+    async fn quoted(context: &StrategyContext) -> (Vec<Decimal>, Vec<Decimal>) {
+        let orders_reader = context.orders.read().await;
+        let prices = |side: OrderSide| {
+            orders_reader
+                .open_orders()
+                .iter()
+                .filter(|o| o.side == side)
+                .map(|o| o.price)
+                .collect::<Vec<_>>()
+        };
+        (prices(OrderSide::Buy), prices(OrderSide::Sell))
+    }
+
+    #[test]
+    fn test_inventory_ratio_is_the_base_share_of_value() {
+        let ratio = |base: i64, quote: i64| {
+            inventory_ratio(
+                Decimal::from(base),
+                Decimal::from(quote),
+                Decimal::from(100),
+            )
+        };
+        assert_eq!(ratio(10, 1000), Decimal::ZERO);
+        assert_eq!(ratio(10, 0), Decimal::ONE);
+        assert_eq!(ratio(0, 1000), Decimal::NEGATIVE_ONE);
+        assert_eq!(ratio(15, 500), Decimal::new(5, 1));
+        assert_eq!(ratio(0, 0), Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_quotes_skew_and_stay_off_the_opposite_side() {
+        let quotes = |half_spread: i64, skew: i64| {
+            quote_prices(
+                Decimal::from(1000),
+                Decimal::from(half_spread),
+                Decimal::from(skew),
+                Decimal::from(995),
+                Decimal::from(1005),
+                Decimal::ONE,
+            )
+        };
+        let expected = |bid: i64, ask: i64| Quotes {
+            bid: Decimal::from(bid),
+            ask: Decimal::from(ask),
+        };
+        assert_eq!(quotes(10, 0), expected(990, 1010));
+        // long base, both quotes move down to sell more readily
+        assert_eq!(quotes(10, 4), expected(986, 1006));
+        // short base, both move up but the bid stays a tick below the best ask
+        assert_eq!(quotes(10, -12), expected(1002, 1022));
+        assert_eq!(quotes(10, -20), expected(1004, 1030));
+    }
+
+    #[tokio::test]
+    async fn test_market_maker_requotes_when_the_mid_moves() {
+        let book = Arc::new(RwLock::new(order_book(990, 1010)));
+        let context = paper_context(book.clone()).await;
+        let mut market_maker = market_maker();
+
+        market_maker.on_book(&context).await;
+        assert_eq!(
+            quoted(&context).await,
+            (vec![Decimal::from(990)], vec![Decimal::from(1010)])
+        );
+        let quotes = order_ids(&context).await;
+
+        // 10 bps is inside the refresh threshold
+        *book.write().await = order_book(991, 1011);
+        market_maker.on_book(&context).await;
+        assert_eq!(
+            quoted(&context).await,
+            (vec![Decimal::from(990)], vec![Decimal::from(1010)])
+        );
+
+        // base is now worth more than quote so both quotes skew down. The bid is amended in
+        // place, the ask the book moved through filled and is placed again.
+        *book.write().await = order_book(1010, 1030);
+        market_maker.on_book(&context).await;
+        assert_eq!(
+            quoted(&context).await,
+            (vec![Decimal::from(1009)], vec![Decimal::from(1031)])
+        );
+        let requoted = order_ids(&context).await;
+        assert_eq!(requoted[0], quotes[0]);
+        assert!(!quotes.contains(&requoted[1]));
+    }
+
+    #[tokio::test]
+    async fn test_market_maker_requotes_only_when_a_quote_is_done() {
+        let book = Arc::new(RwLock::new(order_book(990, 1010)));
+        let context = paper_context(book.clone()).await;
+        let mut market_maker = market_maker();
+        market_maker.on_book(&context).await;
+        let quotes = order_ids(&context).await;
+        assert_eq!(quotes.len(), 2);
+
+        // a snapshot that does not list the quotes yet leaves them resting
+        market_maker.on_order_update(&[], &context).await;
+        market_maker.on_book(&context).await;
+        assert_eq!(order_ids(&context).await, quotes);
+
+        // a quote cancelled elsewhere requotes both sides at the same mid
+        context
+            .executor
+            .cancel_order(&quotes[0], &context.currency_pair)
+            .await
+            .unwrap();
+        market_maker
+            .on_order_status(
+                &order_status(&quotes[0], OrderStatus::Cancelled, OrderSide::Buy),
+                &context,
+            )
+            .await;
+        assert!(order_ids(&context).await.is_empty());
+        market_maker.on_book(&context).await;
+        let requoted = order_ids(&context).await;
+        assert_eq!(requoted.len(), 2);
+        assert!(requoted.iter().all(|order_id| !quotes.contains(order_id)));
+    }
+}
@@ -0,0 +1,200 @@
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rust_decimal::Decimal;
+
+    use crate::rusty_bot_models::{MarkPriceBucket, OrderStatus, OrderType, WsMessage};
+
+    #[test]
+    fn test_balance_update_deserializes_exact_decimals() {
+        let serialized = r#"
+        {
+            "type": "BALANCE_UPDATE",
+            "data": {
+                "currency": {
+                    "symbol": "BTC",
+                    "decimalPlaces": 8,
+                    "isActive": true,
+                    "shortName": "BTC",
+                    "longName": "Bitcoin",
+                    "supportedWithdrawDecimalPlaces": 8,
+                    "collateral": true,
+                    "collateralWeight": "0.95"
+                },
+                "available": "0.10000001",
+                "reserved": "0.2",
+                "total": "0.30000001",
+                "updatedAt": "2024-06-01T10:00:00.000Z",
+                "lendReserved": "0",
+                "borrowReserved": "0",
+                "borrowedAmount": "0",
+                "totalInReference": "0.5",
+                "totalInReferenceWeighted": "0.475",
+                "referenceCurrency": "USDC"
+            }
+        }
+        "#;
+        match serde_json::from_str::<WsMessage>(serialized).unwrap() {
+            WsMessage::BalanceUpdate(balance_update) => {
+                assert_eq!(
+                    balance_update.available + balance_update.reserved,
+                    balance_update.total
+                );
+                assert_eq!(
+                    balance_update.total,
+                    Decimal::from_str("0.30000001").unwrap()
+                );
+            }
+            other => panic!("Unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_open_orders_update_deserializes_exact_decimals() {
+        let serialized = r#"
+        {
+            "type": "OPEN_ORDERS_UPDATE",
+            "data": [{
+                "orderId": "38511e49-a755-4f8f-a2b1-232bae6967dc",
+                "side": "sell",
+                "remainingQuantity": "0.1",
+                "price": "1200000",
+                "currencyPair": "BTCZAR",
+                "createdAt": "2024-06-01T10:00:00.000Z",
+                "originalQuantity": "0.3",
+                "filledPercentage": "66.67",
+                "updatedAt": "2024-06-01T10:00:01.000Z",
+                "status": "Partially Filled",
+                "type": "limit",
+                "timeInForce": "GTC",
+                "allowMargin": false
+            }]
+        }
+        "#;
+        match serde_json::from_str::<WsMessage>(serialized).unwrap() {
+            WsMessage::OpenOrdersUpdate(orders) => {
+                assert_eq!(orders[0].price, Decimal::from(1200000));
+                assert_eq!(
+                    orders[0].original_quantity - orders[0].remaining_quantity.unwrap(),
+                    Decimal::new(2, 1)
+                );
+            }
+            other => panic!("Unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_mark_price_bucket_deserializes_exact_decimals() {
+        let serialized = r#"
+        {
+            "currencyPairSymbol": "BTCZAR",
+            "bucketPeriodInSeconds": 60,
+            "startTime": "2024-06-01T10:00:00Z",
+            "open": "1200000.1",
+            "high": "1200000.3",
+            "low": "1199999.9",
+            "close": "1200000.2"
+        }
+        "#;
+        let bucket = serde_json::from_str::<MarkPriceBucket>(serialized).unwrap();
+        assert_eq!(bucket.open + bucket.close, Decimal::new(24000003, 1));
+        assert_eq!(bucket.volume, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_depth_snapshot_keeps_its_pair() {
+        let serialized = r#"
+        {
+            "type": "OB_L1_D10_SNAPSHOT",
+            "ps": "BTCUSDC",
+            "d": {
+                "a": [["61000", "0.5"]],
+                "b": [["60000", "0.25"]],
+                "lc": 1717236000000
+            }
+        }
+        "#;
+        match serde_json::from_str::<WsMessage>(serialized).unwrap() {
+            WsMessage::OrderbookLvOneDepthTenSnapshot(ob) => {
+                assert_eq!(ob.currency_pair, "BTCUSDC");
+                assert_eq!(ob.data.asks, vec![vec!["61000", "0.5"]]);
+                assert_eq!(ob.data.last_change, 1717236000000);
+            }
+            other => panic!("Unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_market_order_status_updates_deserialize() {
+        let update = |status: &str| {
+            let serialized = format!(
+                r#"
+                {{
+                    "type": "ORDER_STATUS_UPDATE",
+                    "data": {{
+                        "orderId": "0eb3e2f5-4f1c-4e5d-9c61-0a2f3c5e7a1b",
+                        "orderStatusType": "{}",
+                        "currencyPair": "BTCZAR",
+                        "remainingQuantity": "0",
+                        "originalQuantity": "0.01",
+                        "orderSide": "buy",
+                        "orderType": "market",
+                        "failedReason": "",
+                        "orderUpdatedAt": "2024-06-01T10:00:01.000Z",
+                        "orderCreatedAt": "2024-06-01T10:00:00.000Z"
+                    }}
+                }}
+                "#,
+                status
+            );
+            match serde_json::from_str::<WsMessage>(&serialized).unwrap() {
+                WsMessage::OrderStatusUpdate(update) => *update,
+                other => panic!("Unexpected message {:?}", other),
+            }
+        };
+
+        let reserved = update("Instant Order Balance Reserved");
+        assert_eq!(reserved.order_status_type, OrderStatus::Placed);
+        assert_eq!(reserved.order_type, OrderType::Market);
+        assert_eq!(reserved.original_price, None);
+        assert_eq!(
+            update("Instant Order Completed").order_status_type,
+            OrderStatus::Filled
+        );
+        assert_eq!(
+            update("Order Modified").order_status_type,
+            OrderStatus::Modified
+        );
+    }
+
+    #[test]
+    fn test_trade_bucket_becomes_a_candle() {
+        let serialized = r#"
+        {
+            "type": "NEW_TRADE_BUCKET",
+            "currencyPairSymbol": "BTCZAR",
+            "data": {
+                "currencyPairSymbol": "BTCZAR",
+                "bucketPeriodInSeconds": 60,
+                "startTime": "2024-06-01T10:00:00Z",
+                "open": "1200000",
+                "high": "1200500",
+                "low": "1199000",
+                "close": "1200100",
+                "volume": "0.25",
+                "quoteVolume": "300000"
+            }
+        }
+        "#;
+        match serde_json::from_str::<WsMessage>(serialized).unwrap() {
+            WsMessage::NewTradeBucket(bucket) => {
+                let candle = bucket.to_candle();
+                assert_eq!(candle.bucket_period_in_seconds, 60);
+                assert_eq!(candle.close, Decimal::from(1200100));
+                assert_eq!(candle.volume, Decimal::new(25, 2));
+            }
+            other => panic!("Unexpected message {:?}", other),
+        }
+    }
+}
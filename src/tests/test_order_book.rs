@@ -0,0 +1,215 @@
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::order_book::{FullOrderBook, OrderBook, OrderBookError};
+    use crate::rusty_bot_models::{
+        DepthOrderBookSnapshot, FullOrderBookEntry, FullOrderBookResponse, OrderBookData,
+        OrderLevel, OrderLevelOrder, OrderSide,
+    };
+
+    fn level(price: &str, quantities: &[&str]) -> OrderLevel {
+        OrderLevel {
+            price: price.to_string(),
+            orders: quantities
+                .iter()
+                .enumerate()
+                .map(|(i, quantity)| OrderLevelOrder {
+                    order_id: format!("{}-{}", price, i),
+                    quantity: quantity.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    fn book_data(
+        sn: u64,
+        asks: Vec<OrderLevel>,
+        bids: Vec<OrderLevel>,
+        checksum: &str,
+    ) -> OrderBookData {
+        OrderBookData {
+            last_change: sn,
+            asks,
+            bids,
+            sn,
+            checksum: crc32fast::hash(checksum.as_bytes()) as u64,
+        }
+    }
+
+    fn synced_book() -> FullOrderBook {
+        let mut full_order_book = FullOrderBook::default();
+        full_order_book
+            .apply_snapshot(book_data(
+                10,
+                vec![level("101", &["1"]), level("100", &["0.5", "0.25"])],
+                vec![level("99", &["2"]), level("98", &["3"])],
+                "99:2:100:0.75:98:3:101:1",
+            ))
+            .unwrap();
+        full_order_book
+    }
+
+    #[test]
+    fn test_full_order_book_applies_snapshot_and_updates() {
+        let mut full_order_book = synced_book();
+        assert_eq!(
+            full_order_book.ask_levels()[0],
+            (Decimal::from(100), Decimal::new(75, 2))
+        );
+        assert_eq!(full_order_book.bid_levels()[0].0, Decimal::from(99));
+
+        // the 100 level is taken out and a bid is added at 99.5
+        full_order_book
+            .apply_update(book_data(
+                11,
+                vec![level("100", &[])],
+                vec![level("99.5", &["1"])],
+                "99.5:1:101:1:99:2:98:3",
+            ))
+            .unwrap();
+        assert_eq!(full_order_book.sequence_number, Some(11));
+        assert_eq!(full_order_book.ask_levels()[0].0, Decimal::from(101));
+        assert_eq!(full_order_book.bid_levels()[0].0, Decimal::new(995, 1));
+
+        // stale updates are ignored
+        assert!(full_order_book
+            .apply_update(book_data(11, vec![], vec![], ""))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_full_order_book_detects_gaps_and_bad_checksums() {
+        let mut full_order_book = synced_book();
+        assert_eq!(
+            full_order_book.apply_update(book_data(13, vec![], vec![], "")),
+            Err(OrderBookError::SequenceGap {
+                expected: 11,
+                received: 13
+            })
+        );
+        assert!(!full_order_book.is_synced());
+        assert!(full_order_book.should_resync());
+        assert!(!full_order_book.should_resync());
+
+        let mut full_order_book = synced_book();
+        let result = full_order_book.apply_update(book_data(
+            11,
+            vec![level("102", &["1"])],
+            vec![],
+            "wrong",
+        ));
+        assert!(matches!(
+            result,
+            Err(OrderBookError::ChecksumMismatch { .. })
+        ));
+        assert!(!full_order_book.is_synced());
+    }
+
+    fn full_response(sn: u64) -> FullOrderBookResponse {
+        let entry =
+            |side: &str, price: &str, id: &str, position_at_price: u64| FullOrderBookEntry {
+                side: side.to_string(),
+                quantity: String::from("1"),
+                price: price.to_string(),
+                currency_pair: String::from("BTCZAR"),
+                id: id.to_string(),
+                position_at_price,
+            };
+        FullOrderBookResponse {
+            asks: vec![entry("sell", "101", "b", 1), entry("sell", "101", "a", 0)],
+            bids: vec![entry("buy", "99", "c", 0)],
+            sn,
+            last_change: None,
+            checksum: None,
+        }
+    }
+
+    #[test]
+    fn test_full_order_book_resyncs_and_replays_pending_updates() {
+        let mut full_order_book = synced_book();
+        // 11 is missed, 12 and 13 wait for the resync
+        assert!(full_order_book
+            .apply_update(book_data(12, vec![], vec![], ""))
+            .is_err());
+        assert!(full_order_book
+            .apply_update(book_data(13, vec![], vec![level("99", &[])], "101:2"))
+            .is_err());
+
+        let rebuilt = FullOrderBook::from_response(full_response(12)).unwrap();
+        assert_eq!(rebuilt.asks[&Decimal::from(101)].orders[0].order_id, "a");
+        assert!(!rebuilt.is_synced());
+        full_order_book.resync_from(rebuilt).unwrap();
+        // 12 is already in the rebuilt book and 13 verified it
+        assert_eq!(full_order_book.sequence_number, Some(13));
+        assert!(full_order_book.bid_levels().is_empty());
+        assert!(full_order_book.is_synced());
+
+        // a fetch older than the book is dropped
+        full_order_book
+            .resync_from(FullOrderBook::from_response(full_response(12)).unwrap())
+            .unwrap();
+        assert_eq!(full_order_book.sequence_number, Some(13));
+
+        // a rebuilt book that the next update does not match falls out of sync again
+        let mut full_order_book = FullOrderBook::default();
+        full_order_book
+            .resync_from(FullOrderBook::from_response(full_response(12)).unwrap())
+            .unwrap();
+        assert!(matches!(
+            full_order_book.apply_update(book_data(13, vec![], vec![], "wrong")),
+            Err(OrderBookError::ChecksumMismatch { .. })
+        ));
+        assert!(full_order_book.should_resync());
+    }
+
+    fn depth_snapshot(asks: &[[&str; 2]], bids: &[[&str; 2]]) -> DepthOrderBookSnapshot {
+        let to_levels = |levels: &[[&str; 2]]| {
+            levels
+                .iter()
+                .map(|level| level.iter().map(|v| v.to_string()).collect())
+                .collect()
+        };
+        DepthOrderBookSnapshot {
+            asks: to_levels(asks),
+            bids: to_levels(bids),
+            last_change: 1,
+        }
+    }
+
+    #[test]
+    fn test_order_book_from_snapshot() {
+        let order_book = OrderBook::from_snapshot(depth_snapshot(
+            &[
+                ["102", "1"],
+                ["100", "0.5"],
+                ["not a price", "1"],
+                ["101", "2"],
+            ],
+            &[["98", "1"], ["99", "0.25"]],
+        ));
+
+        assert_eq!(order_book.asks.len(), 3);
+        assert_eq!(order_book.best_ask().unwrap().price, Decimal::from(100));
+        assert_eq!(order_book.best_bid().unwrap().price, Decimal::from(99));
+        assert_eq!(order_book.mid_price(), Some(Decimal::new(995, 1)));
+        assert_eq!(order_book.spread(), Some(Decimal::from(1)));
+        assert_eq!(
+            order_book.depth_at_price(OrderSide::Sell, Decimal::from(101)),
+            Decimal::from(2)
+        );
+        assert_eq!(
+            order_book.depth_at_price(OrderSide::Buy, Decimal::from(97)),
+            Decimal::ZERO
+        );
+        // 0.5 at 100 and 1.5 at 101
+        assert_eq!(
+            order_book.volume_weighted_fill_price(OrderSide::Buy, Decimal::from(2)),
+            Some(Decimal::new(10075, 2))
+        );
+        assert_eq!(
+            order_book.volume_weighted_fill_price(OrderSide::Sell, Decimal::from(2)),
+            None
+        );
+    }
+}
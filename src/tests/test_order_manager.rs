@@ -0,0 +1,236 @@
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::order_manager::{record_account_trade, OrderManager};
+    use crate::rusty_bot_models::{
+        Order, OrderSide, OrderStatus, OrderStatusUpdate, OrderType, TimeInForce, WsMessage,
+    };
+
+    const ORDER_ID: &str = "e5886f2d-191b-4330-a221-c7b41b0bc553";
+
+    fn order_status_update(status: &str, remaining_quantity: &str) -> OrderStatusUpdate {
+        let serialized = format!(
+            r#"
+            {{
+                "type": "ORDER_STATUS_UPDATE",
+                "data": {{
+                    "orderId": "{}",
+                    "orderStatusType": "{}",
+                    "currencyPair": "BTCZAR",
+                    "originalPrice": "1000000",
+                    "remainingQuantity": "{}",
+                    "originalQuantity": "0.4",
+                    "orderSide": "buy",
+                    "orderType": "limit",
+                    "failedReason": "",
+                    "orderUpdatedAt": "2024-06-01T10:00:01.000Z",
+                    "orderCreatedAt": "2024-06-01T10:00:00.000Z",
+                    "customerOrderId": "rusty-bot-1"
+                }}
+            }}
+            "#,
+            ORDER_ID, status, remaining_quantity
+        );
+        match serde_json::from_str::<WsMessage>(&serialized).unwrap() {
+            WsMessage::OrderStatusUpdate(update) => *update,
+            other => panic!("Unexpected message {:?}", other),
+        }
+    }
+
+    fn open_order(order_id: &str, side: OrderSide, remaining_quantity: i64) -> Order {
+        Order {
+            order_id: order_id.to_string(),
+            side,
+            remaining_quantity: Some(Decimal::from(remaining_quantity)),
+            price: Decimal::from(1000),
+            currency_pair: String::from("BTCZAR"),
+            created_at: String::new(),
+            original_quantity: Decimal::from(remaining_quantity),
+            filled_percentage: Decimal::ZERO,
+            updated_at: String::new(),
+            status: OrderStatus::Placed,
+            r#type: OrderType::Limit,
+            time_in_force: TimeInForce::GoodTillCancelled,
+            allow_margin: false,
+        }
+    }
+
+    #[test]
+    fn test_order_status_updates_track_the_order_until_it_closes() {
+        let mut order_manager = OrderManager::default();
+
+        assert!(order_manager.apply_status_update(&order_status_update("Placed", "0.4")));
+        assert_eq!(order_manager.open_orders().len(), 1);
+        assert_eq!(
+            order_manager.get(ORDER_ID).unwrap().price,
+            Decimal::from(1000000)
+        );
+
+        order_manager.apply_status_update(&order_status_update("Partially Filled", "0.1"));
+        let order = order_manager.get(ORDER_ID).unwrap();
+        assert_eq!(order.status, OrderStatus::PartiallyFilled);
+        assert_eq!(order.remaining_quantity, Some(Decimal::new(1, 1)));
+        assert_eq!(order.filled_percentage, Decimal::from(75));
+
+        // a stale update cannot move the order back
+        assert!(!order_manager.apply_status_update(&order_status_update("Placed", "0.4")));
+        assert_eq!(
+            order_manager.get(ORDER_ID).unwrap().status,
+            OrderStatus::PartiallyFilled
+        );
+
+        // a modified order keeps resting with what is left of it
+        assert!(order_manager.apply_status_update(&order_status_update("Order Modified", "0.1")));
+        assert_eq!(
+            order_manager.get(ORDER_ID).unwrap().status,
+            OrderStatus::PartiallyFilled
+        );
+
+        order_manager.apply_status_update(&order_status_update("Filled", "0"));
+        assert!(!order_manager.has_open_orders());
+    }
+
+    #[test]
+    fn test_open_orders_update_replaces_the_open_set() {
+        let mut order_manager = OrderManager::default();
+        order_manager.record_placed(open_order("a", OrderSide::Buy, 1));
+        order_manager.record_placed(open_order("a", OrderSide::Buy, 1));
+        order_manager.record_placed(open_order("b", OrderSide::Sell, 2));
+        assert_eq!(order_manager.open_orders().len(), 2);
+
+        order_manager.replace_open_orders(vec![
+            open_order("b", OrderSide::Sell, 2),
+            open_order("c", OrderSide::Sell, 3),
+        ]);
+        order_manager.replace_open_orders(vec![
+            open_order("b", OrderSide::Sell, 2),
+            open_order("c", OrderSide::Sell, 3),
+        ]);
+        assert!(order_manager.get("a").is_none());
+        assert_eq!(order_manager.open_orders().len(), 2);
+        assert_eq!(
+            order_manager.open_quantity("BTCZAR", OrderSide::Sell),
+            Decimal::from(5)
+        );
+        assert!(order_manager
+            .open_orders_for("BTCZAR", OrderSide::Buy)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_cancelled_pairs_are_forgotten() {
+        let mut order_manager = OrderManager::default();
+        order_manager.record_placed(open_order("a", OrderSide::Buy, 1));
+        order_manager.record_placed(Order {
+            currency_pair: String::from("ETHZAR"),
+            ..open_order("b", OrderSide::Sell, 2)
+        });
+
+        order_manager.forget_pair("BTCZAR");
+        assert!(order_manager.get("a").is_none());
+        assert_eq!(order_manager.open_orders().len(), 1);
+        assert_eq!(
+            order_manager.open_quantity("ETHZAR", OrderSide::Sell),
+            Decimal::from(2)
+        );
+    }
+
+    #[test]
+    fn test_failed_orders_are_dropped() {
+        let mut order_manager = OrderManager::default();
+        order_manager.apply_status_update(&order_status_update("Placed", "0.4"));
+
+        let processed = r#"
+        {
+            "type": "ORDER_PROCESSED",
+            "data": {
+                "orderId": "e5886f2d-191b-4330-a221-c7b41b0bc553",
+                "success": false,
+                "failureReason": "Insufficient Balance"
+            }
+        }
+        "#;
+        match serde_json::from_str::<WsMessage>(processed).unwrap() {
+            WsMessage::OrderProcessed(processed) => {
+                assert_eq!(
+                    processed.failure_reason.as_deref(),
+                    Some("Insufficient Balance")
+                );
+                order_manager.apply_order_processed(&processed);
+            }
+            other => panic!("Unexpected message {:?}", other),
+        }
+        assert!(!order_manager.has_open_orders());
+
+        let update = order_status_update("Instant Order Balance Reserve Failed", "0.4");
+        assert_eq!(update.order_status_type, OrderStatus::Failed);
+    }
+
+    #[test]
+    fn test_account_trades_are_recorded_once() {
+        let serialized = r#"
+        {
+            "type": "NEW_ACCOUNT_TRADE",
+            "currencyPairSymbol": "BTCZAR",
+            "data": {
+                "price": "1000000",
+                "quantity": "0.3",
+                "currencyPair": "BTCZAR",
+                "tradedAt": "2024-06-01T10:00:01.000Z",
+                "side": "buy",
+                "orderId": "e5886f2d-191b-4330-a221-c7b41b0bc553",
+                "id": "1d8ca1bb-9a4b-4a4a-a6a4-3dfd6a0b5f41",
+                "fee": "0.0003",
+                "feeCurrency": "BTC"
+            }
+        }
+        "#;
+        let trade = match serde_json::from_str::<WsMessage>(serialized).unwrap() {
+            WsMessage::NewAccountTrade(trade) => *trade,
+            other => panic!("Unexpected message {:?}", other),
+        };
+        assert_eq!(trade.side, OrderSide::Buy);
+        assert_eq!(trade.fee, Some(Decimal::new(3, 4)));
+
+        let mut trades = vec![];
+        assert!(record_account_trade(&mut trades, trade.clone()));
+        assert!(!record_account_trade(&mut trades, trade));
+        assert_eq!(trades.len(), 1);
+    }
+
+    #[test]
+    fn test_new_trade_and_failed_cancel_deserialize() {
+        let new_trade = r#"
+        {
+            "type": "NEW_TRADE",
+            "currencyPairSymbol": "BTCZAR",
+            "data": {
+                "price": "1000001",
+                "quantity": "0.01",
+                "currencyPair": "BTCZAR",
+                "tradedAt": "2024-06-01T10:00:01.000Z",
+                "takerSide": "sell"
+            }
+        }
+        "#;
+        assert!(matches!(
+            serde_json::from_str::<WsMessage>(new_trade).unwrap(),
+            WsMessage::NewTrade(_)
+        ));
+
+        let failed_cancel = r#"
+        {
+            "type": "FAILED_CANCEL_ORDER",
+            "data": {
+                "orderId": "e5886f2d-191b-4330-a221-c7b41b0bc553",
+                "message": "An error occurred while cancelling your order."
+            }
+        }
+        "#;
+        assert!(matches!(
+            serde_json::from_str::<WsMessage>(failed_cancel).unwrap(),
+            WsMessage::FailedCancelOrder(_)
+        ));
+    }
+}
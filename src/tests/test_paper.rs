@@ -0,0 +1,208 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rust_decimal::Decimal;
+    use tokio::sync::RwLock;
+
+    use crate::order_book::{OrderBook, PriceLevel};
+use crate::order_manager::OrderManager;
+    use crate::orders::OrderExecutor;
+    use crate::paper::PaperOrderExecutor;
+use crate::position::PositionTracker;
+    use crate::rusty_bot_models::OrderSide;
+    use crate::simulation::SimulatedAccount;
+    use crate::tests::currency_pair;
+
+    fn order_book(asks: &[(i64, i64)], bids: &[(i64, i64)]) -> OrderBook {
+        let to_levels = |levels: &[(i64, i64)]| {
+            levels
+                .iter()
+                .map(|(price, quantity)| PriceLevel {
+                    price: Decimal::new(*price, 1),
+                    quantity: Decimal::from(*quantity),
+                })
+                .collect()
+        };
+        OrderBook {
+            asks: to_levels(asks),
+            bids: to_levels(bids),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_paper_limit_order_fills_against_book_and_rests() {
+        let book = Arc::new(RwLock::new(order_book(
+            &[(1000, 1), (1010, 1), (1050, 5)],
+            &[(990, 1)],
+        )));
+        let balances = Arc::new(RwLock::new(vec![]));
+        let orders = Arc::new(RwLock::new(OrderManager::default()));
+        let positions = Arc::new(RwLock::new(PositionTracker::default()));
+        let executor = PaperOrderExecutor::new(
+            SimulatedAccount::new(
+                currency_pair(),
+                Decimal::from(1000),
+                Decimal::ZERO,
+                Decimal::ZERO,
+            ),
+            book.clone(),
+            balances.clone(),
+            orders.clone(),
+            positions.clone(),
+        )
+        .await;
+
+        executor
+            .place_limit_order(
+                OrderSide::Buy,
+                Decimal::from(101),
+                Decimal::from(3),
+                &currency_pair(),
+            )
+            .await
+            .unwrap();
+
+        // two levels cross the limit, the third BTC keeps resting
+        let base = balances
+            .read()
+            .await
+            .iter()
+            .find(|b| b.currency.symbol == "BTC")
+            .unwrap()
+            .total;
+        assert_eq!(base, Decimal::from(2));
+        assert_eq!(orders.read().await.open_orders().len(), 1);
+        assert_eq!(
+            orders.read().await.open_orders()[0].remaining_quantity,
+            Some(Decimal::ONE)
+        );
+
+        *book.write().await = order_book(&[(1005, 10)], &[(990, 1)]);
+        assert!(executor.on_book_update().await);
+        assert!(!orders.read().await.has_open_orders());
+        let quote = balances
+            .read()
+            .await
+            .iter()
+            .find(|b| b.currency.symbol == "ZAR")
+            .unwrap()
+            .total;
+        assert_eq!(quote, Decimal::new(6985, 1));
+        let position = positions.read().await.position("BTCZAR").unwrap().clone();
+        assert_eq!(position.quantity, Decimal::from(3));
+        assert_eq!(position.average_entry_price, Decimal::new(1005, 1));
+    }
+
+    #[tokio::test]
+    async fn test_paper_orders_can_be_cancelled_and_modified() {
+        let book = Arc::new(RwLock::new(order_book(&[(1000, 5)], &[(990, 5)])));
+        let balances = Arc::new(RwLock::new(vec![]));
+        let orders = Arc::new(RwLock::new(OrderManager::default()));
+        let positions = Arc::new(RwLock::new(PositionTracker::default()));
+        let executor = PaperOrderExecutor::new(
+            SimulatedAccount::new(
+                currency_pair(),
+                Decimal::from(1000),
+                Decimal::ZERO,
+                Decimal::ZERO,
+            ),
+            book.clone(),
+            balances.clone(),
+            orders.clone(),
+            positions.clone(),
+        )
+        .await;
+
+        let first = executor
+            .place_limit_order(OrderSide::Buy, Decimal::from(90), Decimal::ONE, &currency_pair())
+            .await
+            .unwrap();
+        let second = executor
+            .place_limit_order(OrderSide::Buy, Decimal::from(95), Decimal::ONE, &currency_pair())
+            .await
+            .unwrap();
+        assert_eq!(orders.read().await.open_orders().len(), 2);
+
+        executor.cancel_order(&first, &currency_pair()).await.unwrap();
+        assert!(orders.read().await.get(&first).is_none());
+        assert!(executor.cancel_order(&first, &currency_pair()).await.is_err());
+
+        // moving the bid above the best ask fills it at the ask
+        executor
+            .modify_order(
+                &second,
+                OrderSide::Buy,
+                Decimal::new(1009, 1),
+                Decimal::from(2),
+                &currency_pair(),
+            )
+            .await
+            .unwrap();
+        assert!(!orders.read().await.has_open_orders());
+        let position = positions.read().await.position("BTCZAR").unwrap().clone();
+        assert_eq!(position.quantity, Decimal::from(2));
+        assert_eq!(position.average_entry_price, Decimal::from(100));
+
+        executor
+            .place_limit_order(OrderSide::Buy, Decimal::from(90), Decimal::ONE, &currency_pair())
+            .await
+            .unwrap();
+        executor.cancel_all_orders(&currency_pair()).await.unwrap();
+        assert!(!orders.read().await.has_open_orders());
+    }
+
+    #[tokio::test]
+    async fn test_paper_fills_do_not_take_the_same_liquidity_twice() {
+        let book = Arc::new(RwLock::new(order_book(&[(1000, 1)], &[(990, 1)])));
+        let balances = Arc::new(RwLock::new(vec![]));
+        let orders = Arc::new(RwLock::new(OrderManager::default()));
+        let positions = Arc::new(RwLock::new(PositionTracker::default()));
+        let executor = PaperOrderExecutor::new(
+            SimulatedAccount::new(
+                currency_pair(),
+                Decimal::from(1000),
+                Decimal::ZERO,
+                Decimal::ZERO,
+            ),
+            book.clone(),
+            balances.clone(),
+            orders.clone(),
+            positions.clone(),
+        )
+        .await;
+        let buy = || async {
+            executor
+                .place_limit_order(
+                    OrderSide::Buy,
+                    Decimal::from(101),
+                    Decimal::ONE,
+                    &currency_pair(),
+                )
+                .await
+                .unwrap()
+        };
+        let bought = || async { positions.read().await.position("BTCZAR").unwrap().quantity };
+
+        buy().await;
+        assert_eq!(bought().await, Decimal::ONE);
+        // the ask at 100 was taken, the second buy rests through the same snapshot
+        buy().await;
+        assert!(!executor.on_book_update().await);
+        assert_eq!(orders.read().await.open_orders().len(), 1);
+
+        // only the volume added to the level since is offered again
+        *book.write().await = order_book(&[(1000, 2)], &[(990, 1)]);
+        assert!(executor.on_book_update().await);
+        assert_eq!(bought().await, Decimal::from(2));
+        buy().await;
+        assert_eq!(bought().await, Decimal::from(2));
+
+        // a level that left the book is fresh when it comes back
+        *book.write().await = order_book(&[(1020, 1)], &[(990, 1)]);
+        assert!(!executor.on_book_update().await);
+        *book.write().await = order_book(&[(1000, 1)], &[(990, 1)]);
+        assert!(executor.on_book_update().await);
+        assert_eq!(bought().await, Decimal::from(3));
+    }
+}
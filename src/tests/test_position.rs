@@ -0,0 +1,125 @@
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use crate::order_book::{OrderBook, PriceLevel};
+    use crate::position::{Position, PositionTracker};
+    use crate::rusty_bot_models::{OrderSide, WsMessage};
+
+    #[test]
+    fn test_position_averages_entries_and_realizes_exits() {
+        let mut position = Position::new("BTCZAR");
+        position.apply_fill(
+            OrderSide::Buy,
+            Decimal::from(100),
+            Decimal::from(1),
+            Decimal::ONE,
+        );
+        position.apply_fill(
+            OrderSide::Buy,
+            Decimal::from(130),
+            Decimal::from(2),
+            Decimal::ONE,
+        );
+        assert_eq!(position.quantity, Decimal::from(3));
+        assert_eq!(position.average_entry_price, Decimal::from(120));
+
+        position.apply_fill(
+            OrderSide::Sell,
+            Decimal::from(150),
+            Decimal::from(2),
+            Decimal::ONE,
+        );
+        assert_eq!(position.quantity, Decimal::ONE);
+        assert_eq!(position.average_entry_price, Decimal::from(120));
+        assert_eq!(position.realized_pnl, Decimal::from(60));
+        assert_eq!(position.fees_paid, Decimal::from(3));
+        assert_eq!(position.net_realized_pnl(), Decimal::from(57));
+        assert_eq!(
+            position.unrealized_pnl_at(Decimal::from(110)),
+            Decimal::from(-10)
+        );
+
+        // selling past flat opens a short at the fill price
+        position.apply_fill(
+            OrderSide::Sell,
+            Decimal::from(140),
+            Decimal::from(3),
+            Decimal::ZERO,
+        );
+        assert_eq!(position.quantity, Decimal::from(-2));
+        assert_eq!(position.average_entry_price, Decimal::from(140));
+        assert_eq!(position.realized_pnl, Decimal::from(80));
+
+        position.apply_fill(
+            OrderSide::Buy,
+            Decimal::from(130),
+            Decimal::from(2),
+            Decimal::ZERO,
+        );
+        assert!(position.quantity.is_zero());
+        assert!(position.average_entry_price.is_zero());
+        assert_eq!(position.realized_pnl, Decimal::from(100));
+    }
+
+    #[test]
+    fn test_unrealized_pnl_marks_against_the_closing_side_of_the_book() {
+        let order_book = OrderBook {
+            asks: vec![PriceLevel {
+                price: Decimal::from(105),
+                quantity: Decimal::ONE,
+            }],
+            bids: vec![PriceLevel {
+                price: Decimal::from(95),
+                quantity: Decimal::ONE,
+            }],
+        };
+        let mut long = Position::new("BTCZAR");
+        long.apply_fill(
+            OrderSide::Buy,
+            Decimal::from(100),
+            Decimal::from(2),
+            Decimal::ZERO,
+        );
+        assert_eq!(long.unrealized_pnl(&order_book), Some(Decimal::from(-10)));
+
+        let mut short = Position::new("BTCZAR");
+        short.apply_fill(
+            OrderSide::Sell,
+            Decimal::from(100),
+            Decimal::from(2),
+            Decimal::ZERO,
+        );
+        assert_eq!(short.unrealized_pnl(&order_book), Some(Decimal::from(-10)));
+        assert_eq!(short.unrealized_pnl(&OrderBook::default()), None);
+    }
+
+    #[test]
+    fn test_account_trade_fees_are_converted_to_quote() {
+        let serialized = r#"
+        {
+            "type": "NEW_ACCOUNT_TRADE",
+            "data": {
+                "price": "1000000",
+                "quantity": "0.5",
+                "currencyPair": "BTCZAR",
+                "tradedAt": "2024-06-01T10:00:01.000Z",
+                "side": "buy",
+                "orderId": "e5886f2d-191b-4330-a221-c7b41b0bc553",
+                "id": "1d8ca1bb-9a4b-4a4a-a6a4-3dfd6a0b5f41",
+                "fee": "0.0005",
+                "feeCurrency": "BTC"
+            }
+        }
+        "#;
+        let trade = match serde_json::from_str::<WsMessage>(serialized).unwrap() {
+            WsMessage::NewAccountTrade(trade) => *trade,
+            other => panic!("Unexpected message {:?}", other),
+        };
+        let mut position_tracker = PositionTracker::default();
+        let position = position_tracker.apply_account_trade(&trade, "ZAR");
+        assert_eq!(position.quantity, Decimal::new(5, 1));
+        assert_eq!(position.fees_paid, Decimal::from(500));
+        assert!(position_tracker.position("ETHZAR").is_none());
+    }
+}
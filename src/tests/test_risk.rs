@@ -0,0 +1,477 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use chrono::{DateTime, Duration, Utc};
+    use rust_decimal::Decimal;
+    use tokio::sync::{Mutex, RwLock};
+
+    use crate::config::RiskConfig;
+    use crate::order_book::{OrderBook, PriceLevel};
+    use crate::order_manager::OrderManager;
+    use crate::orders::{OrderError, OrderExecutor};
+    use crate::position::PositionTracker;
+    use crate::risk::{RiskManagedExecutor, RiskManager, RiskViolation};
+    use crate::rusty_bot_models::{CurrencyPair, OrderSide};
+    use crate::simulation::{SimulatedAccount, SimulatedOrderExecutor};
+    use crate::tests::{currency_pair, currency_pair_of};
+
+    fn order_book(bid: i64, ask: i64) -> OrderBook {
+        OrderBook {
+            asks: vec![PriceLevel {
+                price: Decimal::from(ask),
+                quantity: Decimal::ONE,
+            }],
+            bids: vec![PriceLevel {
+                price: Decimal::from(bid),
+                quantity: Decimal::ONE,
+            }],
+        }
+    }
+
+    /// Simulated executor that remembers the pairs it cancelled all orders on
+    struct CancelRecorder {
+        simulated: SimulatedOrderExecutor,
+        cancelled: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl OrderExecutor for CancelRecorder {
+        async fn place_limit_order(
+            &self,
+            side: OrderSide,
+            price: Decimal,
+            quantity: Decimal,
+            currency_pair: &CurrencyPair,
+        ) -> Result<String, OrderError> {
+            self.simulated
+                .place_limit_order(side, price, quantity, currency_pair)
+                .await
+        }
+
+        async fn place_market_order(
+            &self,
+            side: OrderSide,
+            amount: Decimal,
+            currency_pair: &CurrencyPair,
+        ) -> Result<String, OrderError> {
+            self.simulated
+                .place_market_order(side, amount, currency_pair)
+                .await
+        }
+
+        async fn cancel_order(
+            &self,
+            order_id: &str,
+            currency_pair: &CurrencyPair,
+        ) -> Result<(), OrderError> {
+            self.simulated.cancel_order(order_id, currency_pair).await
+        }
+
+        async fn cancel_all_orders(&self, currency_pair: &CurrencyPair) -> Result<(), OrderError> {
+            self.cancelled
+                .lock()
+                .await
+                .push(currency_pair.symbol.clone());
+            self.simulated.cancel_all_orders(currency_pair).await
+        }
+
+        async fn modify_order(
+            &self,
+            order_id: &str,
+            side: OrderSide,
+            price: Decimal,
+            quantity: Decimal,
+            currency_pair: &CurrencyPair,
+        ) -> Result<(), OrderError> {
+            self.simulated
+                .modify_order(order_id, side, price, quantity, currency_pair)
+                .await
+        }
+    }
+
+    fn risk_managed_executor(
+        account: Arc<Mutex<SimulatedAccount>>,
+        limits: RiskConfig,
+        pair_books: HashMap<String, OrderBook>,
+    ) -> RiskManagedExecutor {
+        risk_managed_executor_with(
+            Arc::new(SimulatedOrderExecutor { account }),
+            limits,
+            pair_books,
+        )
+    }
+
+    fn risk_managed_executor_with(
+        inner: Arc<dyn OrderExecutor>,
+        limits: RiskConfig,
+        pair_books: HashMap<String, OrderBook>,
+    ) -> RiskManagedExecutor {
+        RiskManagedExecutor::new(
+            inner,
+            RiskManager::new(limits),
+            vec![currency_pair(), currency_pair_of("BTC", "USDC")],
+            Arc::new(RwLock::new(order_book(990, 1000))),
+            Arc::new(RwLock::new(pair_books)),
+            Arc::new(RwLock::new(OrderManager::default())),
+            Arc::new(RwLock::new(PositionTracker::default())),
+            Arc::new(RwLock::new(vec![])),
+        )
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-06-01T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_orders_breaking_a_limit_are_blocked() {
+        let mut risk_manager = RiskManager::new(RiskConfig {
+            max_position: HashMap::from([(String::from("BTCZAR"), Decimal::from(2))]),
+            max_order_notional: Some(Decimal::from(1000)),
+            max_orders_per_minute: Some(2),
+            daily_loss_limit: None,
+        });
+        let check = |risk_manager: &mut RiskManager, side, quantity: i64, position: i64, at| {
+            risk_manager.check_order(
+                "BTCZAR",
+                side,
+                Decimal::from(100),
+                Decimal::from(quantity),
+                Decimal::from(position),
+                Decimal::ZERO,
+                at,
+            )
+        };
+
+        assert_eq!(
+            check(&mut risk_manager, OrderSide::Buy, 11, 0, now()),
+            Err(RiskViolation::MaxOrderNotional {
+                notional: Decimal::from(1100),
+                max: Decimal::from(1000),
+            })
+        );
+        assert_eq!(
+            check(&mut risk_manager, OrderSide::Buy, 2, 1, now()),
+            Err(RiskViolation::MaxPositionSize {
+                position: Decimal::from(3),
+                max: Decimal::from(2),
+            })
+        );
+        // orders that shrink an oversized position are still allowed
+        assert!(check(&mut risk_manager, OrderSide::Sell, 1, 5, now()).is_ok());
+        assert!(check(&mut risk_manager, OrderSide::Sell, 1, 4, now()).is_ok());
+        assert_eq!(
+            check(&mut risk_manager, OrderSide::Sell, 1, 3, now()),
+            Err(RiskViolation::MaxOrdersPerMinute { max: 2 })
+        );
+        assert!(check(
+            &mut risk_manager,
+            OrderSide::Sell,
+            1,
+            3,
+            now() + Duration::minutes(1)
+        )
+        .is_ok());
+        // the position limit is set per pair
+        assert!(risk_manager
+            .check_order(
+                "ETHZAR",
+                OrderSide::Buy,
+                Decimal::from(100),
+                Decimal::from(5),
+                Decimal::ZERO,
+                Decimal::ZERO,
+                now() + Duration::minutes(2),
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn test_daily_loss_limit_activates_the_kill_switch() {
+        let mut risk_manager = RiskManager::new(RiskConfig {
+            daily_loss_limit: Some(Decimal::from(50)),
+            ..Default::default()
+        });
+        let check = |risk_manager: &mut RiskManager, pnl: i64, at| {
+            risk_manager.check_order(
+                "BTCZAR",
+                OrderSide::Buy,
+                Decimal::from(100),
+                Decimal::ONE,
+                Decimal::ZERO,
+                Decimal::from(pnl),
+                at,
+            )
+        };
+
+        assert!(risk_manager.roll_day(Decimal::from(100), now()));
+        assert!(!risk_manager.roll_day(Decimal::from(60), now()));
+        assert!(check(&mut risk_manager, 100, now()).is_ok());
+        assert!(check(&mut risk_manager, 60, now()).is_ok());
+        assert_eq!(
+            check(&mut risk_manager, 50, now()),
+            Err(RiskViolation::DailyLossLimit {
+                loss: Decimal::from(50),
+                max: Decimal::from(50),
+            })
+        );
+        assert!(risk_manager.is_killed());
+        assert_eq!(
+            check(&mut risk_manager, 200, now() + Duration::days(1)),
+            Err(RiskViolation::KillSwitchActive)
+        );
+    }
+
+    #[test]
+    fn test_daily_loss_is_measured_from_the_day_rollover() {
+        let mut risk_manager = RiskManager::new(RiskConfig {
+            daily_loss_limit: Some(Decimal::from(50)),
+            ..Default::default()
+        });
+        let check = |risk_manager: &mut RiskManager, pnl: i64, at| {
+            risk_manager.check_order(
+                "BTCZAR",
+                OrderSide::Buy,
+                Decimal::from(100),
+                Decimal::ONE,
+                Decimal::ZERO,
+                Decimal::from(pnl),
+                at,
+            )
+        };
+
+        risk_manager.roll_day(Decimal::from(100), now());
+        assert!(check(&mut risk_manager, 60, now()).is_ok());
+        // the next day starts from the P&L at midnight
+        risk_manager.roll_day(Decimal::from(70), now() + Duration::days(1));
+        assert!(check(&mut risk_manager, 30, now() + Duration::days(1)).is_ok());
+        // a first order later in the day does not move the start of the day
+        assert!(check(&mut risk_manager, 20, now() + Duration::days(1)).is_err());
+        assert!(risk_manager.is_killed());
+    }
+
+    #[tokio::test]
+    async fn test_kill_switch_cancels_open_orders_and_halts_trading() {
+        let account = Arc::new(Mutex::new(SimulatedAccount::new(
+            currency_pair(),
+            Decimal::from(100000),
+            Decimal::ZERO,
+            Decimal::ZERO,
+        )));
+        let recorder = Arc::new(CancelRecorder {
+            simulated: SimulatedOrderExecutor {
+                account: account.clone(),
+            },
+            cancelled: Mutex::new(vec![]),
+        });
+        let executor = risk_managed_executor_with(
+            recorder.clone(),
+            RiskConfig {
+                max_order_notional: Some(Decimal::from(5000)),
+                ..Default::default()
+            },
+            HashMap::from([(String::from("BTCUSDC"), order_book(59000, 60000))]),
+        );
+
+        executor
+            .place_limit_order(
+                OrderSide::Buy,
+                Decimal::from(900),
+                Decimal::ONE,
+                &currency_pair(),
+            )
+            .await
+            .unwrap();
+        // 10 BTC at the best ask of 1000 is worth more than the notional limit
+        assert!(executor
+            .place_market_order(OrderSide::Buy, Decimal::from(10000), &currency_pair())
+            .await
+            .is_err());
+        assert_eq!(account.lock().await.open_orders.len(), 1);
+
+        executor
+            .place_limit_order(
+                OrderSide::Buy,
+                Decimal::from(59000),
+                Decimal::new(1, 2),
+                &currency_pair_of("BTC", "USDC"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(account.lock().await.open_orders.len(), 2);
+
+        executor.activate_kill_switch().await;
+        assert!(account.lock().await.open_orders.is_empty());
+        assert_eq!(
+            *recorder.cancelled.lock().await,
+            vec![String::from("BTCZAR"), String::from("BTCUSDC")]
+        );
+        assert!(executor
+            .place_limit_order(
+                OrderSide::Buy,
+                Decimal::from(900),
+                Decimal::ONE,
+                &currency_pair()
+            )
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_orders_are_valued_against_the_book_of_their_pair() {
+        let account = Arc::new(Mutex::new(SimulatedAccount::new(
+            currency_pair(),
+            Decimal::from(100000),
+            Decimal::ZERO,
+            Decimal::ZERO,
+        )));
+        let executor = risk_managed_executor(
+            account.clone(),
+            RiskConfig {
+                max_position: HashMap::from([
+                    (String::from("BTCZAR"), Decimal::ONE),
+                    (String::from("BTCUSDC"), Decimal::ONE),
+                ]),
+                ..Default::default()
+            },
+            HashMap::from([(String::from("BTCUSDC"), order_book(59000, 60000))]),
+        );
+        let buy = |currency_pair| {
+            let executor = &executor;
+            async move {
+                executor
+                    .place_market_order(OrderSide::Buy, Decimal::from(10000), &currency_pair)
+                    .await
+            }
+        };
+
+        // 10 BTC at the market's ask of 1000, a sixth of a BTC at the BTCUSDC ask of 60000
+        assert!(buy(currency_pair()).await.is_err());
+        assert!(buy(currency_pair_of("BTC", "USDC")).await.is_ok());
+        // nothing to value an order on a pair without a book
+        assert!(buy(currency_pair_of("ETH", "ZAR")).await.is_err());
+        assert_eq!(account.lock().await.open_orders.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_daily_loss_is_converted_into_the_market_quote_currency() {
+        let usdc_zar = currency_pair_of("USDC", "ZAR");
+        let positions = Arc::new(RwLock::new(PositionTracker::default()));
+        let executor = RiskManagedExecutor::new(
+            Arc::new(SimulatedOrderExecutor {
+                account: Arc::new(Mutex::new(SimulatedAccount::new(
+                    currency_pair(),
+                    Decimal::from(100000),
+                    Decimal::ZERO,
+                    Decimal::ZERO,
+                ))),
+            }),
+            RiskManager::new(RiskConfig {
+                daily_loss_limit: Some(Decimal::from(100)),
+                ..Default::default()
+            }),
+            vec![
+                currency_pair(),
+                currency_pair_of("BTC", "USDC"),
+                usdc_zar.clone(),
+            ],
+            Arc::new(RwLock::new(order_book(990, 1000))),
+            Arc::new(RwLock::new(HashMap::from([
+                (String::from("BTCUSDC"), order_book(59000, 60000)),
+                (String::from("USDCZAR"), order_book(17, 19)),
+            ]))),
+            Arc::new(RwLock::new(OrderManager::default())),
+            positions.clone(),
+            Arc::new(RwLock::new(vec![])),
+        );
+        executor.roll_day().await;
+
+        // 10 USDC lost on a BTCUSDC round trip is 180 ZAR at the USDCZAR mid of 18
+        let mut positions_writer = positions.write().await;
+        for (side, price) in [(OrderSide::Buy, 60000), (OrderSide::Sell, 59990)] {
+            positions_writer.apply_fill(
+                "BTCUSDC",
+                side,
+                Decimal::from(price),
+                Decimal::ONE,
+                Decimal::ZERO,
+            );
+        }
+        drop(positions_writer);
+
+        let blocked = executor
+            .place_limit_order(OrderSide::Buy, Decimal::from(17), Decimal::ONE, &usdc_zar)
+            .await;
+        assert!(matches!(
+            blocked,
+            Err(OrderError::Rejected(reason)) if reason.starts_with("daily loss limit: lost 180 ")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_base_held_before_the_first_order_starts_the_position() {
+        let account = SimulatedAccount::new(
+            currency_pair(),
+            Decimal::from(100000),
+            Decimal::from(2),
+            Decimal::ZERO,
+        );
+        let balances = account.balance_updates();
+        let account = Arc::new(Mutex::new(account));
+        let positions = Arc::new(RwLock::new(PositionTracker::default()));
+        let executor = RiskManagedExecutor::new(
+            Arc::new(SimulatedOrderExecutor {
+                account: account.clone(),
+            }),
+            RiskManager::new(RiskConfig {
+                max_position: HashMap::from([
+                    (String::from("BTCZAR"), Decimal::ONE),
+                    (String::from("BTCUSDC"), Decimal::ONE),
+                ]),
+                ..Default::default()
+            }),
+            vec![currency_pair(), currency_pair_of("BTC", "USDC")],
+            Arc::new(RwLock::new(order_book(990, 1000))),
+            Arc::new(RwLock::new(HashMap::from([(
+                String::from("BTCUSDC"),
+                order_book(59000, 60000),
+            )]))),
+            Arc::new(RwLock::new(OrderManager::default())),
+            positions.clone(),
+            Arc::new(RwLock::new(balances)),
+        );
+
+        // selling 1.5 of the 2 BTC held leaves a long of 0.5, not a short over the limit
+        executor
+            .place_limit_order(
+                OrderSide::Sell,
+                Decimal::from(1000),
+                Decimal::new(15, 1),
+                &currency_pair(),
+            )
+            .await
+            .unwrap();
+        let position = positions.read().await.position("BTCZAR").cloned().unwrap();
+        assert_eq!(position.quantity, Decimal::from(2));
+        assert_eq!(position.average_entry_price, Decimal::from(995));
+
+        // the BTC is only held once, a sell on BTCUSDC still starts from nothing
+        let blocked = executor
+            .place_limit_order(
+                OrderSide::Sell,
+                Decimal::from(60000),
+                Decimal::from(2),
+                &currency_pair_of("BTC", "USDC"),
+            )
+            .await;
+        assert!(matches!(
+            blocked,
+            Err(OrderError::Rejected(reason)) if reason.starts_with("max position size")
+        ));
+        assert!(positions.read().await.position("BTCUSDC").is_none());
+    }
+}
@@ -0,0 +1,49 @@
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use serde_json::Value;
+
+    use crate::config::ShutdownPolicy;
+    use crate::order_manager::OrderManager;
+    use crate::position::PositionTracker;
+    use crate::rusty_bot_models::OrderSide;
+    use crate::shutdown::{persist_state, BotState};
+
+    #[test]
+    fn test_shutdown_policy_from_str() {
+        assert_eq!(
+            "cancel".parse::<ShutdownPolicy>(),
+            Ok(ShutdownPolicy::CancelOrders)
+        );
+        assert_eq!(
+            "KEEP".parse::<ShutdownPolicy>(),
+            Ok(ShutdownPolicy::KeepOrders)
+        );
+        assert!("close".parse::<ShutdownPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_state_is_persisted_as_json() {
+        let mut positions = PositionTracker::default();
+        positions.apply_fill(
+            "BTCZAR",
+            OrderSide::Buy,
+            Decimal::from(1000),
+            Decimal::new(5, 1),
+            Decimal::ONE,
+        );
+        let state = BotState::new(&positions, &OrderManager::default(), &[]);
+        let state_file =
+            std::env::temp_dir().join(format!("rusty_bot_state_{}.json", std::process::id()));
+        let state_file = state_file.to_str().unwrap();
+
+        persist_state(state_file, &state).unwrap();
+        let saved: Value =
+            serde_json::from_str(&std::fs::read_to_string(state_file).unwrap()).unwrap();
+        std::fs::remove_file(state_file).unwrap();
+
+        assert_eq!(saved["positions"][0]["currency_pair"], "BTCZAR");
+        assert_eq!(saved["positions"][0]["quantity"], "0.5");
+        assert_eq!(saved["openOrders"].as_array().unwrap().len(), 0);
+    }
+}
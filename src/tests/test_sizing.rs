@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rust_decimal::Decimal;
+
+    use crate::rusty_bot_models::OrderSide;
+    use crate::sizing::{round_to_tick, size_limit_order, size_market_order, SizingError};
+    use crate::tests::currency_pair;
+
+    fn decimal(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn test_round_to_tick_never_makes_orders_more_aggressive() {
+        assert_eq!(
+            round_to_tick(OrderSide::Buy, decimal("1000.7"), decimal("1")),
+            decimal("1000")
+        );
+        assert_eq!(
+            round_to_tick(OrderSide::Sell, decimal("1000.2"), decimal("1")),
+            decimal("1001")
+        );
+        assert_eq!(
+            round_to_tick(OrderSide::Sell, decimal("0.1234"), decimal("0.01")),
+            decimal("0.13")
+        );
+    }
+
+    #[test]
+    fn test_limit_order_is_rounded_and_clamped() {
+        let sized = size_limit_order(
+            OrderSide::Buy,
+            decimal("1000.9"),
+            decimal("0.123456789"),
+            &currency_pair(),
+        )
+        .unwrap();
+        assert_eq!(sized.price, decimal("1000"));
+        assert_eq!(sized.quantity, decimal("0.12345678"));
+
+        // 500 BTC at 1000 exceeds both the maximum base and the maximum quote amount
+        let sized = size_limit_order(
+            OrderSide::Sell,
+            decimal("1000"),
+            decimal("500"),
+            &currency_pair(),
+        )
+        .unwrap();
+        assert_eq!(sized.quantity, decimal("100"));
+        let sized = size_limit_order(
+            OrderSide::Sell,
+            decimal("2000"),
+            decimal("500"),
+            &currency_pair(),
+        )
+        .unwrap();
+        assert_eq!(sized.quantity, decimal("50"));
+    }
+
+    #[test]
+    fn test_orders_below_the_minimums_are_rejected() {
+        assert_eq!(
+            size_limit_order(
+                OrderSide::Buy,
+                decimal("1000000"),
+                decimal("0.00005"),
+                &currency_pair()
+            ),
+            Err(SizingError::BelowMinBaseAmount {
+                quantity: decimal("0.00005"),
+                min: decimal("0.0001"),
+            })
+        );
+        assert_eq!(
+            size_limit_order(
+                OrderSide::Buy,
+                decimal("1000"),
+                decimal("0.005"),
+                &currency_pair()
+            ),
+            Err(SizingError::BelowMinQuoteAmount {
+                amount: decimal("5"),
+                min: decimal("10"),
+            })
+        );
+        assert_eq!(
+            size_limit_order(
+                OrderSide::Buy,
+                decimal("0.4"),
+                decimal("1"),
+                &currency_pair()
+            ),
+            Err(SizingError::InvalidPrice(Decimal::ZERO))
+        );
+        assert!(size_market_order(OrderSide::Buy, decimal("9"), &currency_pair()).is_err());
+        assert_eq!(
+            size_market_order(OrderSide::Buy, decimal("250000"), &currency_pair()),
+            Ok(decimal("100000"))
+        );
+        assert_eq!(
+            size_market_order(OrderSide::Sell, decimal("0.5"), &currency_pair()),
+            Ok(decimal("0.5"))
+        );
+    }
+}
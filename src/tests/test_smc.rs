@@ -0,0 +1,189 @@
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use rust_decimal::Decimal;
+
+    use crate::backtest::run_backtest;
+    use crate::candles::{CandleEngine, Timeframe, MAX_CANDLES};
+    use crate::rusty_bot_models::MarkPriceBucket;
+    use crate::smc::{
+        fair_value_gaps, liquidity_sweeps, order_blocks, smc_events, FairValueGap, LiquiditySweep,
+        OrderBlock, SmcDetector, SmcEvent,
+    };
+    use crate::strategies::break_of_structure::Direction;
+    use crate::strategies::{Strategy, StrategyContext};
+    use crate::tests::{candle, candles, currency_pair};
+
+    /// Remembers the events it was called with
+    struct SmcRecorder {
+        events: Vec<SmcEvent>,
+    }
+
+    #[async_trait]
+    impl Strategy for SmcRecorder {
+        fn name(&self) -> &'static str {
+            "smc_recorder"
+        }
+
+        fn smc_detectors(&self) -> Vec<(Timeframe, usize)> {
+            vec![(Timeframe::OneMinute, 1)]
+        }
+
+        async fn on_smc_event(
+            &mut self,
+            timeframe: Timeframe,
+            event: &SmcEvent,
+            candles: &[MarkPriceBucket],
+            context: &StrategyContext,
+        ) {
+            self.events.push(*event);
+        }
+    }
+
+    /// (open, high, low, close) with a swing width of 1: the high of 13 at 1 is swept at 3,
+    /// the low of 8 at 2 is swept at 4 and the close at 5 breaks the high of 14 at 3
+    const FIXTURE: [(i64, i64, i64, i64); 8] = [
+        (10, 11, 9, 10),
+        (10, 13, 10, 12),
+        (12, 12, 8, 9),
+        (9, 14, 9, 11),
+        (11, 12, 7, 10),
+        (10, 17, 10, 16),
+        (16, 20, 15, 19),
+        (19, 21, 18, 20),
+    ];
+
+    fn expected_events() -> Vec<SmcEvent> {
+        vec![
+            SmcEvent::LiquiditySweep(LiquiditySweep {
+                index: 3,
+                direction: Direction::Bearish,
+                swing_index: 1,
+                level: Decimal::from(13),
+            }),
+            SmcEvent::LiquiditySweep(LiquiditySweep {
+                index: 4,
+                direction: Direction::Bullish,
+                swing_index: 2,
+                level: Decimal::from(8),
+            }),
+            SmcEvent::OrderBlock(OrderBlock {
+                index: 5,
+                block_index: 4,
+                direction: Direction::Bullish,
+                lower: Decimal::from(7),
+                upper: Decimal::from(12),
+            }),
+            SmcEvent::FairValueGap(FairValueGap {
+                index: 6,
+                direction: Direction::Bullish,
+                lower: Decimal::from(12),
+                upper: Decimal::from(15),
+            }),
+        ]
+    }
+
+    #[test]
+    fn test_detectors_over_the_fixture() {
+        let closed = candles(&FIXTURE[..7]);
+        assert_eq!(smc_events(&closed, 1), expected_events());
+
+        // the bearish mirror of a gap, the second candle dropped through it
+        let gaps = fair_value_gaps(&candles(&[
+            (20, 21, 18, 19),
+            (19, 19, 12, 13),
+            (13, 15, 11, 12),
+        ]));
+        assert_eq!(
+            gaps,
+            vec![FairValueGap {
+                index: 2,
+                direction: Direction::Bearish,
+                lower: Decimal::from(15),
+                upper: Decimal::from(18),
+            }]
+        );
+        // nothing to detect in too few candles
+        assert!(fair_value_gaps(&closed[..2]).is_empty());
+        assert!(order_blocks(&closed[..2], 3).is_empty());
+        assert!(liquidity_sweeps(&[], 1).is_empty());
+    }
+
+    #[test]
+    fn test_detector_reports_each_event_once_as_candles_close() {
+        let candles = candles(&FIXTURE);
+        let mut detector = SmcDetector::new(1);
+        let mut events = vec![];
+        for len in 1..=candles.len() {
+            events.extend(detector.new_events(&candles[..len]));
+            // a repeated update of the forming candle has nothing new
+            assert!(detector.new_events(&candles[..len]).is_empty());
+        }
+        assert_eq!(events, expected_events());
+
+        // history before the first call is only taken in
+        let mut detector = SmcDetector::new(1);
+        assert!(detector.new_events(&candles[..7]).is_empty());
+        assert_eq!(
+            detector.new_events(&candles),
+            expected_events()[3..].to_vec()
+        );
+    }
+
+    #[test]
+    fn test_detector_indexes_into_the_candles_it_is_given() {
+        let candles = candles(&FIXTURE);
+        let mut detector = SmcDetector::new(1);
+        let mut confirmed_at = vec![];
+        // only the last four candles are passed, as when older candles are dropped
+        for len in 1..=candles.len() {
+            let window = &candles[len.saturating_sub(4)..len];
+            for event in detector.new_events(window) {
+                if let SmcEvent::OrderBlock(block) = event {
+                    assert_eq!(window[block.block_index].start_time, candles[4].start_time);
+                }
+                confirmed_at.push(window[event.index()].start_time.clone());
+            }
+        }
+        let expected: Vec<_> = expected_events()
+            .iter()
+            .map(|event| candles[event.index()].start_time.clone())
+            .collect();
+        assert_eq!(confirmed_at, expected);
+    }
+
+    #[test]
+    fn test_detector_drops_events_whose_swing_left_the_candles() {
+        // a full window whose only swing is the high of 110 at 1
+        let mut prices = vec![(100, 101, 99, 100); MAX_CANDLES];
+        prices[1] = (100, 110, 99, 100);
+        let mut engine = CandleEngine::new(&[Timeframe::OneMinute]);
+        engine.seed(Timeframe::OneMinute, candles(&prices));
+        engine.attach_smc_detector(Timeframe::OneMinute, 1);
+
+        // the forming candle wicks through the swing high and closes back under it
+        let forming = engine.candles(Timeframe::OneMinute)[MAX_CANDLES - 1]
+            .start_time
+            .clone();
+        engine.apply_bucket(&candle(&forming, 100, 115, 99, 100));
+        // two minutes without trades close three candles at once, which push the swing the
+        // first of them swept out of the candles
+        engine.apply_bucket(&candle("2024-06-01T08:22:00Z", 100, 100, 100, 100));
+        assert_eq!(engine.candles(Timeframe::OneMinute).len(), MAX_CANDLES);
+        assert!(engine.take_smc_events().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_strategies_receive_the_events_of_their_detectors() {
+        let mut strategy = SmcRecorder { events: vec![] };
+        run_backtest(
+            &mut strategy,
+            currency_pair(),
+            candles(&FIXTURE),
+            Decimal::from(1000),
+            Decimal::ZERO,
+        )
+        .await;
+        assert_eq!(strategy.events, expected_events());
+    }
+}
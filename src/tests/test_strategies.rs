@@ -0,0 +1,17 @@
+#[cfg(test)]
+mod tests {
+    use crate::strategies::{available_strategies, create_strategy};
+
+    #[test]
+    fn test_create_registered_strategy() {
+        for name in available_strategies() {
+            let strategy = create_strategy(name).expect("Registered strategy should be created");
+            assert_eq!(strategy.name(), name);
+        }
+    }
+
+    #[test]
+    fn test_create_unknown_strategy() {
+        assert!(create_strategy("not_a_strategy").is_none());
+    }
+}
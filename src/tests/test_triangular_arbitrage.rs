@@ -0,0 +1,479 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use rust_decimal::Decimal;
+    use tokio::sync::{Mutex, RwLock};
+
+    use crate::candles::CandleEngine;
+    use crate::config::ArbitrageConfig;
+    use crate::order_book::{OrderBook, PriceLevel};
+    use crate::order_manager::OrderManager;
+    use crate::orders::{OrderError, OrderExecutor};
+    use crate::position::PositionTracker;
+    use crate::rusty_bot_models::{AccountTrade, CurrencyPair, OrderSide, OrderStatus};
+    use crate::simulation::SimulatedAccount;
+    use crate::strategies::triangular_arbitrage::{
+        evaluate_route, route_name, triangle_routes, TriangularArbitrage,
+    };
+    use crate::strategies::{Strategy, StrategyContext};
+    use crate::tests::{account_trade, currency_pair, currency_pair_of, order_status};
+
+    fn triangle() -> Vec<CurrencyPair> {
+        vec![
+            currency_pair_of("BTC", "ZAR"),
+            currency_pair_of("BTC", "USDC"),
+            currency_pair_of("USDC", "ZAR"),
+        ]
+    }
+
+    /// (bid, bid quantity, ask, ask quantity)
+    fn books() -> HashMap<String, OrderBook> {
+        let book = |bid: &str, bid_quantity: &str, ask: &str, ask_quantity: &str| {
+            let level = |price: &str, quantity: &str| PriceLevel {
+                price: price.parse().unwrap(),
+                quantity: quantity.parse().unwrap(),
+            };
+            OrderBook {
+                asks: vec![level(ask, ask_quantity)],
+                bids: vec![level(bid, bid_quantity)],
+            }
+        };
+        HashMap::from([
+            (
+                String::from("BTCZAR"),
+                book("990000", "0.02", "1000000", "0.01"),
+            ),
+            (
+                String::from("BTCUSDC"),
+                book("60000", "0.005", "61000", "1"),
+            ),
+            (String::from("USDCZAR"), book("17", "1000", "17.5", "1000")),
+        ])
+    }
+
+    #[test]
+    fn test_triangle_routes_go_both_ways_around() {
+        let routes = triangle_routes(&triangle(), "ZAR");
+        let described: Vec<(String, Vec<OrderSide>)> = routes
+            .iter()
+            .map(|legs| (route_name(legs), legs.iter().map(|leg| leg.side).collect()))
+            .collect();
+        assert_eq!(
+            described,
+            vec![
+                (
+                    String::from("ZAR > BTC > USDC > ZAR"),
+                    vec![OrderSide::Buy, OrderSide::Sell, OrderSide::Sell]
+                ),
+                (
+                    String::from("ZAR > USDC > BTC > ZAR"),
+                    vec![OrderSide::Buy, OrderSide::Buy, OrderSide::Sell]
+                ),
+            ]
+        );
+        // ETH is in none of the pairs and ETHZAR leaves BTC without a way back
+        assert!(triangle_routes(&triangle(), "ETH").is_empty());
+        let broken = vec![
+            currency_pair_of("BTC", "ZAR"),
+            currency_pair_of("BTC", "USDC"),
+            currency_pair_of("ETH", "ZAR"),
+        ];
+        assert!(triangle_routes(&broken, "ZAR").is_empty());
+        assert!(triangle_routes(&triangle()[..2], "ZAR").is_empty());
+    }
+
+    #[test]
+    fn test_evaluate_route_prices_and_sizes_by_the_top_levels() {
+        let routes = triangle_routes(&triangle(), "ZAR");
+        let books = books();
+
+        // 1 ZAR buys 0.000001 BTC, sold for 0.06 USDC, sold for 1.02 ZAR
+        let opportunity = evaluate_route(&routes[0], &books, Decimal::ZERO, None).unwrap();
+        assert_eq!(opportunity.profit_ratio, Decimal::new(2, 2));
+        // the 0.005 BTC bid of BTCUSDC limits the round trip to 5000 ZAR
+        assert_eq!(opportunity.start_amount, Decimal::from(5000));
+        assert_eq!(opportunity.expected_profit, Decimal::from(100));
+        let legs: Vec<(String, OrderSide, Decimal, Decimal)> = opportunity
+            .legs
+            .iter()
+            .map(|leg| {
+                (
+                    leg.currency_pair.symbol.clone(),
+                    leg.side,
+                    leg.price,
+                    leg.quantity.normalize(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            legs,
+            vec![
+                (
+                    String::from("BTCZAR"),
+                    OrderSide::Buy,
+                    Decimal::from(1000000),
+                    Decimal::new(5, 3)
+                ),
+                (
+                    String::from("BTCUSDC"),
+                    OrderSide::Sell,
+                    Decimal::from(60000),
+                    Decimal::new(5, 3)
+                ),
+                (
+                    String::from("USDCZAR"),
+                    OrderSide::Sell,
+                    Decimal::from(17),
+                    Decimal::from(300)
+                ),
+            ]
+        );
+
+        // a 0.1% fee on each leg and a cap on the size
+        let opportunity = evaluate_route(
+            &routes[0],
+            &books,
+            Decimal::new(1, 3),
+            Some(Decimal::from(2000)),
+        )
+        .unwrap();
+        assert_eq!(opportunity.profit_ratio, Decimal::new(1694305898, 11));
+        assert_eq!(opportunity.start_amount, Decimal::from(2000));
+
+        // the other way around loses to the spreads
+        let reverse = evaluate_route(&routes[1], &books, Decimal::ZERO, None).unwrap();
+        assert!(reverse.profit_ratio < Decimal::ZERO);
+
+        // nothing to price until every book has a level on the side needed
+        let mut missing = books.clone();
+        missing.get_mut("BTCUSDC").unwrap().bids.clear();
+        assert!(evaluate_route(&routes[0], &missing, Decimal::ZERO, None).is_none());
+        missing.remove("USDCZAR");
+        assert!(evaluate_route(&routes[1], &missing, Decimal::ZERO, None).is_none());
+    }
+
+    /// Records every order as (kind, pair, side, quantity or amount) and kills the fill-or-kill
+    /// orders of one pair
+    struct LegRecorder {
+        kill_pair: Option<String>,
+        placed: Mutex<Vec<(&'static str, String, OrderSide, Decimal)>>,
+    }
+
+    impl LegRecorder {
+        async fn record(
+            &self,
+            kind: &'static str,
+            side: OrderSide,
+            quantity: Decimal,
+            currency_pair: &CurrencyPair,
+        ) -> String {
+            let mut placed = self.placed.lock().await;
+            placed.push((
+                kind,
+                currency_pair.symbol.clone(),
+                side,
+                quantity.normalize(),
+            ));
+            format!("order-{}", placed.len())
+        }
+    }
+
+    #[async_trait]
+    impl OrderExecutor for LegRecorder {
+        async fn place_limit_order(
+            &self,
+            side: OrderSide,
+            price: Decimal,
+            quantity: Decimal,
+            currency_pair: &CurrencyPair,
+        ) -> Result<String, OrderError> {
+            Ok(self.record("limit", side, quantity, currency_pair).await)
+        }
+
+        async fn place_fill_or_kill_order(
+            &self,
+            side: OrderSide,
+            price: Decimal,
+            quantity: Decimal,
+            currency_pair: &CurrencyPair,
+        ) -> Result<String, OrderError> {
+            if self.kill_pair.as_ref() == Some(&currency_pair.symbol) {
+                return Err(OrderError::Rejected(String::from("killed")));
+            }
+            Ok(self.record("fok", side, quantity, currency_pair).await)
+        }
+
+        async fn place_market_order(
+            &self,
+            side: OrderSide,
+            amount: Decimal,
+            currency_pair: &CurrencyPair,
+        ) -> Result<String, OrderError> {
+            Ok(self.record("market", side, amount, currency_pair).await)
+        }
+
+        async fn cancel_order(
+            &self,
+            order_id: &str,
+            currency_pair: &CurrencyPair,
+        ) -> Result<(), OrderError> {
+            Ok(())
+        }
+
+        async fn cancel_all_orders(&self, currency_pair: &CurrencyPair) -> Result<(), OrderError> {
+            Ok(())
+        }
+
+        async fn modify_order(
+            &self,
+            order_id: &str,
+            side: OrderSide,
+            price: Decimal,
+            quantity: Decimal,
+            currency_pair: &CurrencyPair,
+        ) -> Result<(), OrderError> {
+            Ok(())
+        }
+    }
+
+    /// Executes the ZAR > BTC > USDC > ZAR round trip of `books` on an account holding the
+    /// 5000 ZAR, 0.005 BTC and 300 USDC its legs spend. Above 1666 ZAR the USDC leg is over the
+    /// maximum base amount of the fixture.
+    async fn execute_round_trip(
+        kill_pair: Option<&str>,
+        max_amount: Option<Decimal>,
+    ) -> (TriangularArbitrage, StrategyContext, Arc<LegRecorder>) {
+        let recorder = Arc::new(LegRecorder {
+            kill_pair: kill_pair.map(String::from),
+            placed: Mutex::new(vec![]),
+        });
+        // the first ZAR balance is the one read
+        let mut balances = SimulatedAccount::new(
+            currency_pair(),
+            Decimal::from(5000),
+            Decimal::new(5, 3),
+            Decimal::ZERO,
+        )
+        .balance_updates();
+        balances.extend(
+            SimulatedAccount::new(
+                currency_pair_of("USDC", "ZAR"),
+                Decimal::ZERO,
+                Decimal::from(300),
+                Decimal::ZERO,
+            )
+            .balance_updates(),
+        );
+        let context = StrategyContext {
+            currency_pair: currency_pair(),
+            order_book: Arc::new(RwLock::new(OrderBook::default())),
+            balances: Arc::new(RwLock::new(balances)),
+            orders: Arc::new(RwLock::new(OrderManager::default())),
+            positions: Arc::new(RwLock::new(PositionTracker::default())),
+            candles: Arc::new(RwLock::new(CandleEngine::default())),
+            executor: recorder.clone(),
+        };
+        let mut arbitrage = TriangularArbitrage::new(ArbitrageConfig {
+            pairs: triangle().iter().map(|pair| pair.symbol.clone()).collect(),
+            start_currency: String::from("ZAR"),
+            fee_rate: Decimal::ZERO,
+            min_profit_bps: Decimal::from(10),
+            max_amount,
+            execute: true,
+        });
+        let books = books();
+        for currency_pair in triangle() {
+            arbitrage
+                .on_pair_book(&currency_pair, &books[&currency_pair.symbol], &context)
+                .await;
+        }
+        (arbitrage, context, recorder)
+    }
+
+    fn fill(order_id: &str, side: OrderSide, price: i64, quantity: Decimal) -> AccountTrade {
+        account_trade(order_id, side, price, quantity)
+    }
+
+    #[tokio::test]
+    async fn test_no_leg_is_placed_when_one_is_above_its_maximum() {
+        let (_, _, recorder) = execute_round_trip(None, None).await;
+        assert!(recorder.placed.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_a_leg_that_cannot_be_placed_unwinds_the_others() {
+        let (mut arbitrage, context, recorder) =
+            execute_round_trip(Some("USDCZAR"), Some(Decimal::from(1600))).await;
+        assert_eq!(
+            *recorder.placed.lock().await,
+            vec![
+                (
+                    "fok",
+                    String::from("BTCZAR"),
+                    OrderSide::Buy,
+                    Decimal::new(16, 4)
+                ),
+                (
+                    "fok",
+                    String::from("BTCUSDC"),
+                    OrderSide::Sell,
+                    Decimal::new(16, 4)
+                ),
+            ]
+        );
+
+        // the BTC bought, net of the fee charged in it, is sold back
+        arbitrage
+            .on_account_trade(
+                &AccountTrade {
+                    fee: Some(Decimal::new(1, 5)),
+                    fee_currency: Some(String::from("BTC")),
+                    ..fill("order-1", OrderSide::Buy, 1000000, Decimal::new(16, 4))
+                },
+                &context,
+            )
+            .await;
+        // the USDC the BTC was sold for buys it back, fees in another currency are ignored
+        arbitrage
+            .on_account_trade(
+                &AccountTrade {
+                    fee: Some(Decimal::from(5)),
+                    fee_currency: Some(String::from("ZAR")),
+                    ..fill("order-2", OrderSide::Sell, 60000, Decimal::new(16, 4))
+                },
+                &context,
+            )
+            .await;
+        assert_eq!(
+            recorder.placed.lock().await[2..],
+            [
+                (
+                    "market",
+                    String::from("BTCZAR"),
+                    OrderSide::Sell,
+                    Decimal::new(159, 5)
+                ),
+                (
+                    "market",
+                    String::from("BTCUSDC"),
+                    OrderSide::Buy,
+                    Decimal::from(96)
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_a_killed_leg_unwinds_what_the_others_filled() {
+        let (mut arbitrage, context, recorder) =
+            execute_round_trip(None, Some(Decimal::from(1600))).await;
+        assert_eq!(
+            *recorder.placed.lock().await,
+            vec![
+                (
+                    "fok",
+                    String::from("BTCZAR"),
+                    OrderSide::Buy,
+                    Decimal::new(16, 4)
+                ),
+                (
+                    "fok",
+                    String::from("BTCUSDC"),
+                    OrderSide::Sell,
+                    Decimal::new(16, 4)
+                ),
+                (
+                    "fok",
+                    String::from("USDCZAR"),
+                    OrderSide::Sell,
+                    Decimal::from(96)
+                ),
+            ]
+        );
+
+        arbitrage
+            .on_account_trade(
+                &fill("order-1", OrderSide::Buy, 1000000, Decimal::new(16, 4)),
+                &context,
+            )
+            .await;
+        arbitrage
+            .on_account_trade(
+                &fill("order-3", OrderSide::Sell, 17, Decimal::from(96)),
+                &context,
+            )
+            .await;
+        assert_eq!(recorder.placed.lock().await.len(), 3);
+
+        arbitrage
+            .on_order_status(
+                &order_status("order-2", OrderStatus::Failed, OrderSide::Sell),
+                &context,
+            )
+            .await;
+        assert_eq!(
+            recorder.placed.lock().await[3..],
+            [
+                (
+                    "market",
+                    String::from("BTCZAR"),
+                    OrderSide::Sell,
+                    Decimal::new(16, 4)
+                ),
+                (
+                    "market",
+                    String::from("USDCZAR"),
+                    OrderSide::Buy,
+                    Decimal::from(1632)
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_a_leg_reported_filled_is_done_short_of_its_quantity() {
+        let (mut arbitrage, context, recorder) =
+            execute_round_trip(None, Some(Decimal::from(1600))).await;
+
+        // the BTC leg fills for less than the 0.0016 placed
+        arbitrage
+            .on_account_trade(
+                &fill("order-1", OrderSide::Buy, 1000000, Decimal::new(15, 4)),
+                &context,
+            )
+            .await;
+        arbitrage
+            .on_account_trade(
+                &fill("order-2", OrderSide::Sell, 60000, Decimal::new(16, 4)),
+                &context,
+            )
+            .await;
+        arbitrage
+            .on_account_trade(
+                &fill("order-3", OrderSide::Sell, 17, Decimal::from(96)),
+                &context,
+            )
+            .await;
+        for (order_id, side) in [
+            ("order-1", OrderSide::Buy),
+            ("order-2", OrderSide::Sell),
+            ("order-3", OrderSide::Sell),
+        ] {
+            arbitrage
+                .on_order_status(&order_status(order_id, OrderStatus::Filled, side), &context)
+                .await;
+        }
+
+        // the round trip is no longer followed, a late status of its legs unwinds nothing
+        arbitrage
+            .on_order_status(
+                &order_status("order-1", OrderStatus::Cancelled, OrderSide::Buy),
+                &context,
+            )
+            .await;
+        assert_eq!(recorder.placed.lock().await.len(), 3);
+    }
+}